@@ -1,14 +1,39 @@
 use proto_builder_trait::tonic::BuilderAttributes;
-use std::process::Command;
+use std::{env, path::PathBuf, process::Command};
 
 fn main() {
+    let descriptor_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("reservation.bin");
     tonic_build::configure()
         .out_dir("src/pb")
+        .file_descriptor_set_path(descriptor_path)
         .with_sqlx_type(&["reservation.ReservationStatus"], None)
+        .with_serde(
+            &["reservation.Reservation", "reservation.ReservationEvent"],
+            true,
+            true,
+            Some(&["#[serde(default)]"]),
+        )
+        .with_field_attributes(
+            &[
+                "reservation.Reservation.start",
+                "reservation.Reservation.end",
+                "reservation.ReservationEvent.created_at",
+            ],
+            &[r#"#[serde(with = "crate::serde_utils::timestamp")]"#],
+        )
+        .with_field_attributes(
+            &["reservation.Reservation.status"],
+            &[r#"#[serde(with = "crate::serde_utils::reservation_status")]"#],
+        )
+        .with_field_attributes(
+            &["reservation.ReservationEvent.event_type"],
+            &[r#"#[serde(with = "crate::serde_utils::reservation_event_type")]"#],
+        )
         .with_derive_builder(
             &[
                 "reservation.ReservationQuery",
                 "reservation.ReservationFilter",
+                "reservation.ReservationEventFilter",
             ],
             None,
         )
@@ -16,6 +41,7 @@ fn main() {
             &[
                 "reservation.ReservationQuery",
                 "reservation.ReservationFilter",
+                "reservation.ReservationEventFilter",
             ],
             &[r#"#[builder(build_fn(name = "private_build"))]"#],
         )
@@ -27,8 +53,12 @@ fn main() {
             &["cursor"],
             &["#[builder(setter(into, strip_option), default)]"],
         )
-        .compile(&["proto/reservation.proto"], &["proto"])
+        .compile(
+            &["proto/reservation.proto", "proto/details.proto"],
+            &["proto"],
+        )
         .unwrap();
     Command::new("cargo").arg("fmt").output().unwrap();
     println!("cargo:rerun-if-changed=proto/reservation.proto");
+    println!("cargo:rerun-if-changed=proto/details.proto");
 }
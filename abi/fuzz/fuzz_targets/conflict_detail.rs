@@ -0,0 +1,11 @@
+#![no_main]
+
+use abi::ReservationConflictInfo;
+use libfuzzer_sys::fuzz_target;
+
+// the detail comes from the database or a status message, parsing it should never panic
+fuzz_target!(|detail: &str| {
+    let info = ReservationConflictInfo::from(detail);
+    let _ = info.detail();
+    let _ = info.to_string();
+});
@@ -1,13 +1,32 @@
-use std::{fs, path::Path};
+mod loader;
 
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::NaiveTime;
 use serde::{Deserialize, Serialize};
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity};
 
-use crate::Error;
+use crate::{parse_timezone, Error, Reservation, ReservationQuery, Validator, DEFAULT_TIMEZONE};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+use loader::percent_encode;
+pub use loader::{ConfigLoader, ENV_PREFIX};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub db: DbConfig,
+    #[serde(default)]
     pub server: ServerConfig,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    /// where the outbox relay publishes reservation events, disabled if not set
+    #[serde(default)]
+    pub outbox: Option<SinkConfig>,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    /// settings of the resources by id, e.g. the time zone
+    #[serde(default)]
+    pub resources: ResourcesConfig,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -15,7 +34,11 @@ pub struct DbConfig {
     pub host: String,
     pub port: u16,
     pub user: String,
+    #[serde(default)]
     pub password: String,
+    /// read the password from the file (e.g. a mounted secret), overrides `password`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<String>,
     pub name: String,
     #[serde(default = "default_pool_size")]
     pub max_connections: u32,
@@ -25,27 +48,284 @@ fn default_pool_size() -> u32 {
     5
 }
 
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 5432,
+            user: "postgres".to_string(),
+            password: "".to_string(),
+            password_file: None,
+            name: "reservation".to_string(),
+            max_connections: default_pool_size(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// port of the REST/JSON gateway, disabled if not set. It's served in plaintext and takes the
+    /// caller from the headers, so it can't be used with mutual TLS
+    #[serde(default)]
+    pub http_port: Option<u16>,
+    /// port of the prometheus /metrics endpoint, disabled if not set
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    /// serve gRPC over TLS, plaintext if not set
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// on SIGTERM/SIGINT, time to wait for the in-flight requests before exiting
+    #[serde(default = "default_drain_timeout_ms")]
+    pub drain_timeout_ms: u64,
+    /// callers allowed to see the owners of others' reservations in the conflicts, only the
+    /// client certificate subjects of mutual TLS are trusted
+    #[serde(default)]
+    pub admins: Vec<String>,
+}
+
+fn default_drain_timeout_ms() -> u64 {
+    30_000
+}
+
+/// PEM files of the server certificate, if the client CA is set the clients must present
+/// a certificate signed by it (mutual TLS), the certificate subject is the caller identity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+    #[serde(default)]
+    pub client_ca: Option<String>,
+    /// CA the clients (e.g. the CLI) verify the server with, the server certificate if not set
+    #[serde(default)]
+    pub ca: Option<String>,
+    /// name in the server certificate the clients verify, the host if not set
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// certificate the clients present with mutual TLS
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    #[serde(default)]
+    pub client_key: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 50051,
+            http_port: None,
+            metrics_port: None,
+            tls: None,
+            drain_timeout_ms: default_drain_timeout_ms(),
+            admins: vec![],
+        }
+    }
+}
+
+/// delivery settings of the webhook dispatcher
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// attempts for an event before it goes to the dead letters
+    pub max_attempts: u32,
+    /// backoff before the first retry, doubled for each retry
+    pub initial_backoff_ms: u64,
+    /// upper limit of the backoff
+    pub max_backoff_ms: u64,
+    /// timeout of each http request
+    pub timeout_ms: u64,
+    /// interval to check new events if no notification received
+    pub poll_interval_ms: u64,
+    /// hex encoded 32 bytes key to encrypt the webhook secrets in the database, they are stored
+    /// in plaintext without it
+    pub secret_key: Option<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            timeout_ms: 5_000,
+            poll_interval_ms: 5_000,
+            secret_key: None,
+        }
+    }
+}
+
+/// tracing settings, spans are exported over OTLP only if the endpoint is set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// OTLP/gRPC endpoint of the collector, e.g. http://localhost:4317
+    pub otlp_endpoint: Option<String>,
+    /// service.name of the exported spans
+    pub service_name: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "reservation-service".to_string(),
+        }
+    }
+}
+
+/// event sink of the outbox relay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// print events to stdout as JSON lines
+    Stdout,
+    /// append events to a file as JSON lines
+    File { path: String },
+}
+
+/// settings of the resources by id, the ones not listed use the defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ResourcesConfig(pub BTreeMap<String, ResourceConfig>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceConfig {
+    /// IANA time zone of the resource, e.g. America/Los_Angeles
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// local time the date-based bookings start on the start date, in HH:MM
+    #[serde(with = "crate::serde_utils::local_time", default = "default_check_in")]
+    pub check_in: NaiveTime,
+    /// local time the date-based bookings end on the end date, in HH:MM
+    #[serde(with = "crate::serde_utils::local_time", default = "default_check_out")]
+    pub check_out: NaiveTime,
+}
+
+fn default_timezone() -> String {
+    DEFAULT_TIMEZONE.to_string()
+}
+
+fn default_check_in() -> NaiveTime {
+    NaiveTime::from_hms_opt(15, 0, 0).unwrap()
+}
+
+fn default_check_out() -> NaiveTime {
+    NaiveTime::from_hms_opt(11, 0, 0).unwrap()
+}
+
+impl Default for ResourceConfig {
+    fn default() -> Self {
+        Self {
+            timezone: default_timezone(),
+            check_in: default_check_in(),
+            check_out: default_check_out(),
+        }
+    }
 }
 
 impl Config {
+    /// load the config from the file on top of the defaults, env vars are not used
     pub fn load(filename: impl AsRef<Path>) -> Result<Self, Error> {
-        let config = fs::read_to_string(filename.as_ref()).map_err(|_| Error::ConfigReadError)?;
-        serde_yaml::from_str(&config).map_err(|_| Error::ConfigParseError)
+        ConfigLoader::new().file(filename).load()
+    }
+}
+
+impl Validator for Config {
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |key: &str, reason: &str| {
+            Err(Error::InvalidConfig {
+                key: key.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+        if self.db.host.is_empty() {
+            return invalid("db.host", "should not be empty");
+        }
+        if self.db.port == 0 {
+            return invalid("db.port", "should not be 0");
+        }
+        if self.db.user.is_empty() {
+            return invalid("db.user", "should not be empty");
+        }
+        if self.db.name.is_empty() {
+            return invalid("db.name", "should not be empty");
+        }
+        if self.db.max_connections == 0 {
+            return invalid("db.max_connections", "should be greater than 0");
+        }
+        if self.server.port == 0 {
+            return invalid("server.port", "should not be 0");
+        }
+        if self.server.http_port == Some(self.server.port) {
+            return invalid("server.http_port", "should be different from server.port");
+        }
+        if self.server.metrics_port == Some(self.server.port) {
+            return invalid(
+                "server.metrics_port",
+                "should be different from server.port",
+            );
+        }
+        if let Some(tls) = &self.server.tls {
+            if tls.cert.is_empty() {
+                return invalid("server.tls.cert", "should not be empty");
+            }
+            if tls.key.is_empty() {
+                return invalid("server.tls.key", "should not be empty");
+            }
+            if tls.client_cert.is_some() != tls.client_key.is_some() {
+                return invalid(
+                    "server.tls.client_key",
+                    "should be set together with server.tls.client_cert",
+                );
+            }
+            // the gateway can't verify the client certificates, it would bypass mutual TLS
+            if tls.client_ca.is_some() && self.server.http_port.is_some() {
+                return invalid(
+                    "server.http_port",
+                    "the REST gateway is not supported with server.tls.client_ca",
+                );
+            }
+        }
+        if self.webhook.max_attempts == 0 {
+            return invalid("webhook.max_attempts", "should be greater than 0");
+        }
+        if let Some(key) = &self.webhook.secret_key {
+            if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                return invalid("webhook.secret_key", "should be 32 bytes in hex");
+            }
+        }
+        if let Some(endpoint) = &self.tracing.otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                return invalid("tracing.otlp_endpoint", "should be a http(s) url");
+            }
+        }
+        for (id, resource) in self.resources.0.iter() {
+            if parse_timezone(&resource.timezone).is_err() {
+                return invalid(
+                    &format!("resources.{}.timezone", id),
+                    "should be an IANA time zone",
+                );
+            }
+        }
+        Ok(())
     }
 }
 
 impl DbConfig {
+    /// url without the database name, the user and the password are percent-encoded
     pub fn server_url(&self) -> String {
+        let user = percent_encode(&self.user);
         if self.password.is_empty() {
-            format!("postgres://{}@{}:{}", self.user, self.host, self.port)
+            format!("postgres://{}@{}:{}", user, self.host, self.port)
         } else {
             format!(
                 "postgres://{}:{}@{}:{}",
-                self.user, self.password, self.host, self.port
+                user,
+                percent_encode(&self.password),
+                self.host,
+                self.port
             )
         }
     }
@@ -55,6 +335,37 @@ impl DbConfig {
     }
 }
 
+impl ResourcesConfig {
+    /// the settings of the resource, the defaults if it's not listed
+    pub fn get(&self, resource_id: &str) -> ResourceConfig {
+        self.0.get(resource_id).cloned().unwrap_or_default()
+    }
+
+    /// the time zone of the resource, UTC if it's not listed
+    pub fn timezone(&self, resource_id: &str) -> &str {
+        self.0
+            .get(resource_id)
+            .map_or(DEFAULT_TIMEZONE, |r| r.timezone.as_str())
+    }
+
+    /// use the time zone of the resource if the reservation doesn't have one, and set the
+    /// times of a date-based booking from the check-in and check-out times of the resource
+    pub fn fill_reservation(&self, reservation: &mut Reservation) -> Result<(), Error> {
+        let resource = self.get(&reservation.resource_id);
+        if reservation.timezone.is_empty() {
+            reservation.timezone = resource.timezone;
+        }
+        reservation.resolve_dates(resource.check_in, resource.check_out)
+    }
+
+    /// use the time zone of the resource for the local day if the query doesn't have one
+    pub fn fill_query(&self, query: &mut ReservationQuery) {
+        if query.timezone.is_empty() {
+            query.timezone = self.timezone(&query.resource_id).to_string();
+        }
+    }
+}
+
 impl ServerConfig {
     pub fn url(&self, https: bool) -> String {
         if https {
@@ -63,6 +374,37 @@ impl ServerConfig {
             format!("http://{}:{}", self.host, self.port)
         }
     }
+
+    /// the endpoint the clients connect to, over TLS if it's enabled
+    pub fn endpoint(&self) -> Result<Endpoint, Error> {
+        let invalid = |key: &str, reason: String| Error::InvalidConfig {
+            key: format!("server.{}", key),
+            reason,
+        };
+        let Some(tls) = &self.tls else {
+            return Endpoint::from_shared(self.url(false))
+                .map_err(|e| invalid("host", e.to_string()));
+        };
+        let read = |key: &str, filename: &str| {
+            std::fs::read(filename).map_err(|e| invalid(key, format!("{}: {}", filename, e)))
+        };
+        let ca = match &tls.ca {
+            Some(ca) => read("tls.ca", ca)?,
+            None => read("tls.cert", &tls.cert)?,
+        };
+        let domain = tls.domain.clone().unwrap_or_else(|| self.host.clone());
+        let mut config = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(ca))
+            .domain_name(domain);
+        if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+            let identity =
+                Identity::from_pem(read("tls.client_cert", cert)?, read("tls.client_key", key)?);
+            config = config.identity(identity);
+        }
+        Endpoint::from_shared(self.url(true))
+            .and_then(|endpoint| endpoint.tls_config(config))
+            .map_err(|e| invalid("tls", e.to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -80,14 +422,119 @@ mod tests {
                     port: 5432,
                     user: "postgres".to_string(),
                     password: "123456".to_string(),
+                    password_file: None,
                     name: "postgres".to_string(),
                     max_connections: 5,
                 },
                 server: ServerConfig {
                     host: "0.0.0.0".to_string(),
                     port: 50051,
-                }
+                    http_port: None,
+                    metrics_port: None,
+                    tls: None,
+                    drain_timeout_ms: 30_000,
+                    admins: vec![],
+                },
+                webhook: WebhookConfig::default(),
+                outbox: None,
+                tracing: TracingConfig::default(),
+                resources: ResourcesConfig::default(),
             }
         )
     }
+
+    #[test]
+    fn resources_config_should_default_to_utc() {
+        let config: ResourcesConfig =
+            serde_yaml::from_str("room-1:\n  timezone: America/Los_Angeles\nroom-2: {}").unwrap();
+        assert_eq!(config.timezone("room-1"), "America/Los_Angeles");
+        assert_eq!(config.timezone("room-2"), "UTC");
+        assert_eq!(config.timezone("room-3"), "UTC");
+
+        let mut rsvp = Reservation {
+            resource_id: "room-1".to_string(),
+            ..Default::default()
+        };
+        config.fill_reservation(&mut rsvp).unwrap();
+        assert_eq!(rsvp.timezone, "America/Los_Angeles");
+        rsvp.resource_id = "room-2".to_string();
+        rsvp.timezone = "Europe/Berlin".to_string();
+        config.fill_reservation(&mut rsvp).unwrap();
+        assert_eq!(rsvp.timezone, "Europe/Berlin");
+
+        let config = Config {
+            resources: ResourcesConfig(BTreeMap::from([(
+                "room-1".to_string(),
+                ResourceConfig {
+                    timezone: "Pacific/Atlantis".to_string(),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(Error::InvalidConfig {
+                key: "resources.room-1.timezone".to_string(),
+                reason: "should be an IANA time zone".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn webhook_secret_key_should_be_32_bytes_in_hex() {
+        let mut config = Config::default();
+        config.webhook.secret_key = Some("ab".repeat(32));
+        assert_eq!(config.validate(), Ok(()));
+
+        for key in ["ab".repeat(16), "zz".repeat(32)] {
+            config.webhook.secret_key = Some(key);
+            assert_eq!(
+                config.validate(),
+                Err(Error::InvalidConfig {
+                    key: "webhook.secret_key".to_string(),
+                    reason: "should be 32 bytes in hex".to_string(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn client_tls_should_be_configured_with_cert_and_key() {
+        let mut config = Config::default();
+        assert!(config.server.endpoint().is_ok());
+
+        config.server.tls = Some(TlsConfig {
+            cert: "/non-existent/server.pem".to_string(),
+            key: "/non-existent/server.key".to_string(),
+            client_ca: None,
+            ca: None,
+            domain: None,
+            client_cert: Some("client.pem".to_string()),
+            client_key: None,
+        });
+        assert_eq!(
+            config.validate(),
+            Err(Error::InvalidConfig {
+                key: "server.tls.client_key".to_string(),
+                reason: "should be set together with server.tls.client_cert".to_string(),
+            })
+        );
+        let err = config.server.endpoint().unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig { key, .. } if key == "server.tls.cert"));
+    }
+
+    #[test]
+    fn sink_config_should_be_parsed() {
+        let config: SinkConfig =
+            serde_yaml::from_str("type: file\npath: /tmp/events.jsonl").unwrap();
+        assert_eq!(
+            config,
+            SinkConfig::File {
+                path: "/tmp/events.jsonl".to_string()
+            }
+        );
+        let config: SinkConfig = serde_yaml::from_str("type: stdout").unwrap();
+        assert_eq!(config, SinkConfig::Stdout);
+    }
 }
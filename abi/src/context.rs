@@ -0,0 +1,107 @@
+use crate::{Reservation, ReservationEvent};
+
+/// Who makes a change and in which request. Recorded in the reservation events.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    /// id of the caller, empty if unknown. Only the client certificate subject is authenticated,
+    /// an id taken from the request headers is what the client claims to be
+    pub actor_id: String,
+    /// id of the request, empty if unknown
+    pub request_id: String,
+    /// the actor id is verified, e.g. it's the subject of the client certificate
+    pub authenticated: bool,
+    /// the caller could see the owners of all the reservations, the others only see their own
+    /// ones in the conflicts and the reads
+    pub admin: bool,
+}
+
+impl RequestContext {
+    pub fn new(actor_id: impl Into<String>, request_id: impl Into<String>) -> Self {
+        Self {
+            actor_id: actor_id.into(),
+            request_id: request_id.into(),
+            authenticated: false,
+            admin: false,
+        }
+    }
+
+    /// context of a caller whose identity is verified
+    pub fn authenticated(actor_id: impl Into<String>, request_id: impl Into<String>) -> Self {
+        Self {
+            authenticated: true,
+            ..Self::new(actor_id, request_id)
+        }
+    }
+
+    /// context of the service itself, e.g. the background tasks and the tests. It sees the owners
+    /// of all the reservations
+    pub fn system() -> Self {
+        Self {
+            admin: true,
+            ..Self::default()
+        }
+    }
+
+    /// mark the caller as admin if it's one of the given actors, a claimed identity never is
+    pub fn with_admins(mut self, admins: &[String]) -> Self {
+        self.admin =
+            self.authenticated && !self.actor_id.is_empty() && admins.contains(&self.actor_id);
+        self
+    }
+
+    /// whether the caller could see the owner of the reservation
+    pub fn can_see_owner(&self, user_id: &str) -> bool {
+        self.admin || (!self.actor_id.is_empty() && self.actor_id == user_id)
+    }
+
+    /// clear the owner of the reservation if the caller couldn't see it
+    pub fn redact(&self, reservation: &mut Reservation) {
+        if !self.can_see_owner(&reservation.user_id) {
+            reservation.user_id.clear();
+        }
+    }
+
+    /// clear the owners of the snapshots in the event the caller couldn't see
+    pub fn redact_event(&self, event: &mut ReservationEvent) {
+        for reservation in [event.old.as_mut(), event.new.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            self.redact(reservation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_owners_and_admins_should_see_the_owner() {
+        let reservation = Reservation {
+            user_id: "alon".to_string(),
+            ..Default::default()
+        };
+        let redacted = |context: RequestContext| {
+            let mut event = ReservationEvent {
+                old: Some(reservation.clone()),
+                new: Some(reservation.clone()),
+                ..Default::default()
+            };
+            context.redact_event(&mut event);
+            event.new.unwrap().user_id
+        };
+        let admins = ["support".to_string()];
+        assert_eq!(redacted(RequestContext::new("alon", "")), "alon");
+        assert_eq!(redacted(RequestContext::system()), "alon");
+        assert_eq!(
+            redacted(RequestContext::authenticated("support", "").with_admins(&admins)),
+            "alon"
+        );
+        assert_eq!(
+            redacted(RequestContext::new("support", "").with_admins(&admins)),
+            ""
+        );
+        assert_eq!(redacted(RequestContext::default()), "");
+    }
+}
@@ -1,106 +1,293 @@
 use chrono::{DateTime, Utc};
-use regex::Regex;
-use std::{collections::HashMap, convert::Infallible, str::FromStr};
+use std::{convert::Infallible, fmt, ops::Bound, str::FromStr};
+
+use super::pg_detail;
+use crate::{
+    convert_to_timestamp, convert_to_utc_time, pb::details, Error, RequestContext, Reservation,
+    ReservationStatus,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ReservationConflictInfo {
-    Parsed(ReservationConflict),
+    Parsed(Box<ReservationConflict>),
     UnParsed(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReservationConflict {
     pub new: ReservationWindow,
+    /// the one reported by the database
     pub old: ReservationWindow,
+    /// all the reservations overlapping the new window ordered by start, empty if unknown
+    pub existing: Vec<ConflictingReservation>,
+}
+
+/// an existing reservation in the way, the owner is None if the caller can't see it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictingReservation {
+    pub id: i64,
+    pub user_id: Option<String>,
+    pub status: ReservationStatus,
+    pub window: ReservationWindow,
 }
 
+/// the timespan of a resource, an infinite bound in postgres is unbounded
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReservationWindow {
     pub rid: String,
-    pub start: DateTime<Utc>,
-    pub end: DateTime<Utc>,
+    pub start: Bound<DateTime<Utc>>,
+    pub end: Bound<DateTime<Utc>>,
 }
 
-impl FromStr for ReservationConflictInfo {
-    type Err = Infallible;
+impl fmt::Display for ReservationConflictInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReservationConflictInfo::Parsed(conflict) => {
+                write!(
+                    f,
+                    "conflicts with the reservation of {} from {} to {}",
+                    conflict.old.rid,
+                    conflict
+                        .old
+                        .start_time()
+                        .map_or("-infinity".to_string(), |t| t.to_rfc3339()),
+                    conflict
+                        .old
+                        .end_time()
+                        .map_or("infinity".to_string(), |t| t.to_rfc3339())
+                )?;
+                if conflict.existing.len() > 1 {
+                    write!(f, " and {} more", conflict.existing.len() - 1)?;
+                }
+                Ok(())
+            }
+            ReservationConflictInfo::UnParsed(s) => write!(f, "{}", s),
+        }
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(conflict) = s.parse() {
-            Ok(ReservationConflictInfo::Parsed(conflict))
+impl ReservationConflictInfo {
+    /// the conflict detail in the same form as postgres reports it, so it could be parsed back
+    pub fn detail(&self) -> String {
+        match self {
+            ReservationConflictInfo::Parsed(conflict) => format!(
+                "{}{}{}{}).",
+                pg_detail::NEW_KEY,
+                conflict.new.detail(),
+                pg_detail::OLD_KEY,
+                conflict.old.detail()
+            ),
+            ReservationConflictInfo::UnParsed(s) => s.clone(),
+        }
+    }
+}
+
+impl ReservationConflictInfo {
+    /// set the overlapping reservations, the owners are hidden unless the caller could see them
+    pub fn with_existing(self, existing: Vec<Reservation>, context: &RequestContext) -> Self {
+        match self {
+            ReservationConflictInfo::Parsed(mut conflict) => {
+                // the saved reservations are valid, the times are always convertible
+                conflict.existing = existing
+                    .into_iter()
+                    .filter_map(|r| ConflictingReservation::new(r, context).ok())
+                    .collect();
+                ReservationConflictInfo::Parsed(conflict)
+            }
+            info => info,
+        }
+    }
+}
+
+impl ConflictingReservation {
+    fn new(reservation: Reservation, context: &RequestContext) -> Result<Self, Error> {
+        let window = ReservationWindow::try_from(&reservation)?;
+        Ok(Self {
+            id: reservation.id,
+            user_id: context
+                .can_see_owner(&reservation.user_id)
+                .then_some(reservation.user_id),
+            status: ReservationStatus::from_i32(reservation.status)
+                .unwrap_or(ReservationStatus::Unknown),
+            window,
+        })
+    }
+}
+
+impl TryFrom<&Reservation> for ReservationWindow {
+    type Error = Error;
+
+    fn try_from(reservation: &Reservation) -> Result<Self, Self::Error> {
+        let timespan = reservation.get_timespan()?;
+        Ok(Self {
+            rid: reservation.resource_id.clone(),
+            start: timespan.start,
+            end: timespan.end,
+        })
+    }
+}
+
+impl ReservationWindow {
+    /// the window of a reservation, [start, end)
+    pub fn new(rid: impl Into<String>, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            rid: rid.into(),
+            start: Bound::Included(start),
+            end: Bound::Excluded(end),
+        }
+    }
+
+    /// None if unbounded
+    pub fn start_time(&self) -> Option<DateTime<Utc>> {
+        bound_time(&self.start)
+    }
+
+    /// None if unbounded
+    pub fn end_time(&self) -> Option<DateTime<Utc>> {
+        bound_time(&self.end)
+    }
+
+    /// the key values without the parentheses, as postgres writes them
+    fn detail(&self) -> String {
+        let lower = if matches!(self.start, Bound::Excluded(_)) {
+            '('
+        } else {
+            '['
+        };
+        let upper = if matches!(self.end, Bound::Included(_)) {
+            ']'
         } else {
-            Ok(ReservationConflictInfo::UnParsed(s.to_string()))
+            ')'
+        };
+        format!(
+            "{}, {}{},{}{}",
+            self.rid,
+            lower,
+            pg_detail::format_bound(&self.start),
+            pg_detail::format_bound(&self.end),
+            upper
+        )
+    }
+}
+
+impl From<&str> for ReservationConflictInfo {
+    fn from(s: &str) -> Self {
+        match pg_detail::parse_conflict(s) {
+            Some((new, old)) => ReservationConflict {
+                new,
+                old,
+                existing: vec![],
+            }
+            .into(),
+            None => ReservationConflictInfo::UnParsed(s.to_string()),
         }
     }
 }
 
-impl FromStr for ReservationConflict {
-    type Err = ();
+impl From<ReservationConflict> for ReservationConflictInfo {
+    fn from(conflict: ReservationConflict) -> Self {
+        ReservationConflictInfo::Parsed(Box::new(conflict))
+    }
+}
+
+impl FromStr for ReservationConflictInfo {
+    type Err = Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        ParsedInfo::from_str(s)?.try_into()
+        Ok(s.into())
+    }
+}
+
+impl From<ReservationConflict> for details::ReservationConflict {
+    fn from(conflict: ReservationConflict) -> Self {
+        Self {
+            new: Some(conflict.new.into()),
+            old: Some(conflict.old.into()),
+            existing: conflict.existing.into_iter().map(Into::into).collect(),
+        }
     }
 }
 
-impl TryFrom<ParsedInfo> for ReservationConflict {
+impl TryFrom<details::ReservationConflict> for ReservationConflict {
     type Error = ();
 
-    fn try_from(value: ParsedInfo) -> Result<Self, Self::Error> {
+    fn try_from(conflict: details::ReservationConflict) -> Result<Self, Self::Error> {
         Ok(Self {
-            new: value.new.try_into()?,
-            old: value.old.try_into()?,
+            new: conflict.new.ok_or(())?.try_into()?,
+            old: conflict.old.ok_or(())?.try_into()?,
+            existing: conflict
+                .existing
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
         })
     }
 }
 
-impl TryFrom<HashMap<String, String>> for ReservationWindow {
+impl From<ConflictingReservation> for details::ConflictingReservation {
+    fn from(reservation: ConflictingReservation) -> Self {
+        Self {
+            id: reservation.id,
+            user_id: reservation.user_id.unwrap_or_default(),
+            status: reservation.status as i32,
+            window: Some(reservation.window.into()),
+        }
+    }
+}
+
+impl TryFrom<details::ConflictingReservation> for ConflictingReservation {
     type Error = ();
 
-    fn try_from(value: HashMap<String, String>) -> Result<Self, Self::Error> {
-        let timespan = value.get("timespan").ok_or(())?.replace('"', "");
-        let mut split = timespan.splitn(2, ',');
-        let start = parse_datetime(split.next().ok_or(())?)?;
-        let end = parse_datetime(split.next().ok_or(())?)?;
+    fn try_from(reservation: details::ConflictingReservation) -> Result<Self, Self::Error> {
+        // user ids are never empty, an empty one is hidden
+        let user_id = Some(reservation.user_id).filter(|id| !id.is_empty());
         Ok(Self {
-            rid: value.get("resource_id").ok_or(())?.to_string(),
-            start,
-            end,
+            id: reservation.id,
+            user_id,
+            status: ReservationStatus::from_i32(reservation.status).ok_or(())?,
+            window: reservation.window.ok_or(())?.try_into()?,
         })
     }
 }
 
-struct ParsedInfo {
-    new: HashMap<String, String>,
-    old: HashMap<String, String>,
+impl From<ReservationWindow> for details::ReservationWindow {
+    fn from(window: ReservationWindow) -> Self {
+        Self {
+            start: window.start_time().map(convert_to_timestamp),
+            end: window.end_time().map(convert_to_timestamp),
+            start_exclusive: matches!(window.start, Bound::Excluded(_)),
+            end_inclusive: matches!(window.end, Bound::Included(_)),
+            resource_id: window.rid,
+        }
+    }
 }
 
-impl FromStr for ParsedInfo {
-    type Err = ();
+impl TryFrom<details::ReservationWindow> for ReservationWindow {
+    type Error = ();
 
-    // "Key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-26 22:00:00+00\",\"2022-12-30 19:00:00+00\")) conflicts with existing key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-25 22:00:00+00\",\"2022-12-28 19:00:00+00\"))."
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // use regular expression to parse the string
-        let re = Regex::new(r#"\((?P<k1>[a-zA-Z0-9_-]+)\s*,\s*(?P<k2>[a-zA-Z0-9_-]+)\)=\((?P<v1>[a-zA-Z0-9_-]+)\s*,\s*\[(?P<v2>[^\)\]]+)"#).unwrap();
-        let mut maps = vec![];
-        for cap in re.captures_iter(s) {
-            let mut map = HashMap::new();
-            map.insert(cap["k1"].to_string(), cap["v1"].to_string());
-            map.insert(cap["k2"].to_string(), cap["v2"].to_string());
-            maps.push(Some(map));
-        }
-        if maps.len() != 2 {
-            return Err(());
-        }
-        Ok(ParsedInfo {
-            new: maps[0].take().unwrap(),
-            old: maps[1].take().unwrap(),
+    fn try_from(window: details::ReservationWindow) -> Result<Self, Self::Error> {
+        // the timestamps are decoded from the status details, don't panic on the invalid ones
+        let bound = |ts: Option<prost_types::Timestamp>, exclusive| {
+            let time = || convert_to_utc_time(ts.as_ref(), "window").map_err(|_| ());
+            match ts {
+                Some(_) if exclusive => time().map(Bound::Excluded),
+                Some(_) => time().map(Bound::Included),
+                None => Ok(Bound::Unbounded),
+            }
+        };
+        Ok(Self {
+            rid: window.resource_id,
+            start: bound(window.start, window.start_exclusive)?,
+            end: bound(window.end, !window.end_inclusive)?,
         })
     }
 }
 
-fn parse_datetime(s: &str) -> Result<DateTime<Utc>, ()> {
-    Ok(DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%#z")
-        .map_err(|_| ())?
-        .with_timezone(&Utc))
+fn bound_time(bound: &Bound<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match bound {
+        Bound::Included(t) | Bound::Excluded(t) => Some(*t),
+        Bound::Unbounded => None,
+    }
 }
 
 #[cfg(test)]
@@ -109,54 +296,125 @@ mod tests {
 
     const ERR_MSG: &str = "Key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-26 22:00:00+00\",\"2022-12-30 19:00:00+00\")) conflicts with existing key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-25 22:00:00+00\",\"2022-12-28 19:00:00+00\")).";
 
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn conflict_error_message_should_parse() {
+        let info: ReservationConflictInfo = ERR_MSG.parse().unwrap();
+        match info {
+            ReservationConflictInfo::Parsed(conflict) => {
+                assert_eq!(
+                    conflict.new,
+                    ReservationWindow::new(
+                        "ocean-view-room-713",
+                        utc("2022-12-26T22:00:00Z"),
+                        utc("2022-12-30T19:00:00Z")
+                    )
+                );
+                assert_eq!(
+                    conflict.old,
+                    ReservationWindow::new(
+                        "ocean-view-room-713",
+                        utc("2022-12-25T22:00:00Z"),
+                        utc("2022-12-28T19:00:00Z")
+                    )
+                );
+            }
+            ReservationConflictInfo::UnParsed(_) => panic!("should be parsed"),
+        }
+    }
+
     #[test]
-    fn parse_datetime_should_work() {
-        let dt = parse_datetime("2022-12-26 22:00:00+00").unwrap();
-        assert_eq!(dt.to_rfc3339(), "2022-12-26T22:00:00+00:00");
+    fn existing_reservations_should_hide_others_owners() {
+        let reservation = |id, uid: &str| Reservation {
+            id,
+            user_id: uid.to_string(),
+            resource_id: "ocean-view-room-713".to_string(),
+            start: Some(convert_to_timestamp(utc("2022-12-25T22:00:00Z"))),
+            end: Some(convert_to_timestamp(utc("2022-12-28T19:00:00Z"))),
+            note: "".to_string(),
+            status: ReservationStatus::Confirmed as i32,
+            ..Default::default()
+        };
+        let existing = vec![reservation(1, "alon"), reservation(2, "tyr")];
+        let info: ReservationConflictInfo = ERR_MSG.parse().unwrap();
+        let info = info.with_existing(existing.clone(), &RequestContext::new("alon", ""));
+        let ReservationConflictInfo::Parsed(conflict) = &info else {
+            panic!("should be parsed");
+        };
+        let owners: Vec<_> = conflict
+            .existing
+            .iter()
+            .map(|r| r.user_id.clone())
+            .collect();
+        assert_eq!(owners, vec![Some("alon".to_string()), None]);
+        assert_eq!(conflict.existing[1].status, ReservationStatus::Confirmed);
+        assert!(info.to_string().ends_with(" and 1 more"));
+
+        // the hidden owner survives the proto conversion
+        let proto = details::ReservationConflict::from(*conflict.clone());
+        assert_eq!(proto.existing[1].user_id, "");
+        assert_eq!(ReservationConflict::try_from(proto), Ok(*conflict.clone()));
+
+        // claiming to be an admin isn't enough
+        let admins = ["support".to_string()];
+        let claimed = RequestContext::new("support", "").with_admins(&admins);
+        let info: ReservationConflictInfo = ERR_MSG.parse().unwrap();
+        let ReservationConflictInfo::Parsed(conflict) =
+            info.with_existing(existing.clone(), &claimed)
+        else {
+            panic!("should be parsed");
+        };
+        assert!(conflict.existing.iter().all(|r| r.user_id.is_none()));
+
+        let admin = RequestContext::authenticated("support", "").with_admins(&admins);
+        let info: ReservationConflictInfo = ERR_MSG.parse().unwrap();
+        let ReservationConflictInfo::Parsed(conflict) = info.with_existing(existing, &admin) else {
+            panic!("should be parsed");
+        };
+        assert!(conflict.existing.iter().all(|r| r.user_id.is_some()));
+    }
+
+    #[test]
+    fn conflict_detail_should_round_trip() {
+        let info: ReservationConflictInfo = ERR_MSG.parse().unwrap();
+        assert_eq!(info.detail(), ERR_MSG);
+        let info = ReservationConflictInfo::UnParsed("unknown conflict".into());
+        assert_eq!(info.detail(), "unknown conflict");
     }
 
     #[test]
-    fn parsed_info_should_work() {
-        let info: ParsedInfo = ERR_MSG.parse().unwrap();
-        assert_eq!(info.new["resource_id"], "ocean-view-room-713");
+    fn bounds_should_survive_detail_and_proto_round_trip() {
+        let detail = r#"Key (resource_id, timespan)=(desk 1, ("2022-12-26 22:00:00.000001+00",)) conflicts with existing key (resource_id, timespan)=(desk 1, [,"2022-12-28 19:00:00.5+00"])."#;
+        let info = ReservationConflictInfo::from(detail);
+        assert_eq!(info.detail(), detail);
+        assert!(info.to_string().contains("from -infinity to"));
+        let ReservationConflictInfo::Parsed(conflict) = info else {
+            panic!("should be parsed");
+        };
         assert_eq!(
-            info.new["timespan"],
-            "\"2022-12-26 22:00:00+00\",\"2022-12-30 19:00:00+00\""
+            conflict.new.start,
+            Bound::Excluded(utc("2022-12-26T22:00:00.000001Z"))
         );
-        assert_eq!(info.old["resource_id"], "ocean-view-room-713");
+        assert_eq!(conflict.new.end_time(), None);
+        assert_eq!(conflict.old.start, Bound::Unbounded);
         assert_eq!(
-            info.old["timespan"],
-            "\"2022-12-25 22:00:00+00\",\"2022-12-28 19:00:00+00\""
+            conflict.old.end,
+            Bound::Included(utc("2022-12-28T19:00:00.5Z"))
         );
-    }
 
-    #[test]
-    fn hash_map_to_reservation_window_should_work() {
-        let mut map = HashMap::new();
-        map.insert("resource_id".to_string(), "ocean-view-room-713".to_string());
-        map.insert(
-            "timespan".to_string(),
-            "\"2022-12-26 22:00:00+00\",\"2022-12-30 19:00:00+00\"".to_string(),
-        );
-        let window: ReservationWindow = map.try_into().unwrap();
-        assert_eq!(window.rid, "ocean-view-room-713");
-        assert_eq!(window.start.to_rfc3339(), "2022-12-26T22:00:00+00:00");
-        assert_eq!(window.end.to_rfc3339(), "2022-12-30T19:00:00+00:00");
+        let proto = details::ReservationConflict::from(*conflict.clone());
+        assert_eq!(ReservationConflict::try_from(proto), Ok(*conflict));
     }
 
     #[test]
-    fn conflict_error_message_should_parse() {
-        let info: ReservationConflictInfo = ERR_MSG.parse().unwrap();
-        match info {
-            ReservationConflictInfo::Parsed(conflict) => {
-                assert_eq!(conflict.new.rid, "ocean-view-room-713");
-                assert_eq!(conflict.new.start.to_rfc3339(), "2022-12-26T22:00:00+00:00");
-                assert_eq!(conflict.new.end.to_rfc3339(), "2022-12-30T19:00:00+00:00");
-                assert_eq!(conflict.old.rid, "ocean-view-room-713");
-                assert_eq!(conflict.old.start.to_rfc3339(), "2022-12-25T22:00:00+00:00");
-                assert_eq!(conflict.old.end.to_rfc3339(), "2022-12-28T19:00:00+00:00");
-            }
-            ReservationConflictInfo::UnParsed(_) => panic!("should be parsed"),
-        }
+    fn unexpected_detail_should_be_kept_unparsed() {
+        let detail = "Key (resource_id, timespan)=(ocean-view-room-713, empty)";
+        assert_eq!(
+            ReservationConflictInfo::from(detail),
+            ReservationConflictInfo::UnParsed(detail.to_string())
+        );
     }
 }
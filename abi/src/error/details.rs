@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use prost_types::Any;
+use tonic_types::pb::{bad_request::FieldViolation, BadRequest, ErrorInfo};
+
+use super::{Error, ReservationConflict, ReservationConflictInfo};
+use crate::pb::details;
+
+/// domain of the ErrorInfo attached to the statuses of the service
+pub const ERROR_DOMAIN: &str = "reservation";
+
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/";
+const ERROR_INFO: &str = "google.rpc.ErrorInfo";
+const BAD_REQUEST: &str = "google.rpc.BadRequest";
+const RESERVATION_CONFLICT: &str = "reservation.details.ReservationConflict";
+/// ErrorInfo metadata key of the invalid value, or the conflict detail
+const VALUE_KEY: &str = "value";
+
+impl Error {
+    /// stable reason code, sent in the ErrorInfo of the gRPC status
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::DbError(_) => "DB_ERROR",
+            Error::ConfigReadError(..) => "CONFIG_READ_ERROR",
+            Error::ConfigParseError(_) => "CONFIG_PARSE_ERROR",
+            Error::InvalidConfig { .. } => "INVALID_CONFIG",
+            Error::InvalidTime { .. } => "INVALID_TIME",
+            Error::InvalidTimezone(_) => "INVALID_TIMEZONE",
+            Error::ConflictReservation(_) => "RESERVATION_CONFLICT",
+            Error::NotFound => "NOT_FOUND",
+            Error::InvalidReservationId(_) => "INVALID_RESERVATION_ID",
+            Error::InvalidUserId(_) => "INVALID_USER_ID",
+            Error::InvalidResourceId(_) => "INVALID_RESOURCE_ID",
+            Error::InvalidPageSize(_) => "INVALID_PAGE_SIZE",
+            Error::InvalidCursor(_) => "INVALID_CURSOR",
+            Error::InvalidStatus(_) => "INVALID_STATUS",
+            Error::InvalidEventType(_) => "INVALID_EVENT_TYPE",
+            Error::InvalidWebhookUrl(_) => "INVALID_WEBHOOK_URL",
+            Error::InvalidWebhookSecret => "INVALID_WEBHOOK_SECRET",
+            Error::InvalidIcal(_) => "INVALID_ICAL",
+            Error::EventSinkError(_) => "EVENT_SINK_ERROR",
+            Error::SchemaMismatch { .. } => "SCHEMA_MISMATCH",
+            Error::ShuttingDown => "SHUTTING_DOWN",
+            Error::PermissionDenied => "PERMISSION_DENIED",
+            Error::RpcError(..) => "RPC_ERROR",
+            Error::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// the request field of the validation errors
+    fn field(&self) -> Option<&str> {
+        let field = match self {
+            Error::InvalidTime { field, .. } => field,
+            Error::InvalidTimezone(_) => "timezone",
+            Error::InvalidReservationId(_) => "id",
+            Error::InvalidUserId(_) => "user_id",
+            Error::InvalidResourceId(_) => "resource_id",
+            Error::InvalidPageSize(_) => "page_size",
+            Error::InvalidCursor(_) => "cursor",
+            Error::InvalidStatus(_) => "status",
+            Error::InvalidEventType(_) => "event_type",
+            Error::InvalidWebhookUrl(_) => "url",
+            Error::InvalidWebhookSecret => "secret",
+            Error::InvalidIcal(_) => "data",
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    /// the value to rebuild the error from
+    fn value(&self) -> Option<String> {
+        match self {
+            Error::ConflictReservation(info) => Some(info.detail()),
+            Error::InvalidTime { reason, .. } => Some(reason.clone()),
+            Error::InvalidReservationId(v)
+            | Error::InvalidPageSize(v)
+            | Error::InvalidCursor(v) => Some(v.to_string()),
+            Error::InvalidStatus(v) | Error::InvalidEventType(v) => Some(v.to_string()),
+            Error::InvalidTimezone(v)
+            | Error::InvalidUserId(v)
+            | Error::InvalidResourceId(v)
+            | Error::InvalidWebhookUrl(v)
+            | Error::InvalidIcal(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// the status with an ErrorInfo, the field violation of the validation errors and
+/// the windows of the conflicts in the details
+pub(super) fn status_with_details(
+    code: tonic::Code,
+    message: String,
+    err: &Error,
+) -> tonic::Status {
+    let info = ErrorInfo {
+        reason: err.reason().to_string(),
+        domain: ERROR_DOMAIN.to_string(),
+        metadata: err
+            .value()
+            .map(|value| HashMap::from([(VALUE_KEY.to_string(), value)]))
+            .unwrap_or_default(),
+    };
+    let mut details = vec![pack(ERROR_INFO, &info)];
+    if let Some(field) = err.field() {
+        let violation = FieldViolation {
+            field: field.to_string(),
+            description: message.clone(),
+        };
+        let bad_request = BadRequest {
+            field_violations: vec![violation],
+        };
+        details.push(pack(BAD_REQUEST, &bad_request));
+    }
+    if let Error::ConflictReservation(ReservationConflictInfo::Parsed(conflict)) = err {
+        let conflict = details::ReservationConflict::from(conflict.as_ref().clone());
+        details.push(pack(RESERVATION_CONFLICT, &conflict));
+    }
+
+    let status = tonic_types::pb::Status {
+        code: code as i32,
+        message: message.clone(),
+        details,
+    };
+    tonic::Status::with_details(code, message, status.encode_to_vec().into())
+}
+
+/// rebuild the error from the details, None if they're not sent by this service
+pub(super) fn error_from_details(status: &tonic::Status) -> Option<Error> {
+    let details = tonic_types::pb::Status::decode(status.details())
+        .ok()?
+        .details;
+    let info: ErrorInfo = unpack(&details, ERROR_INFO)?;
+    if info.domain != ERROR_DOMAIN {
+        return None;
+    }
+    let value = info.metadata.get(VALUE_KEY).map(String::as_str);
+    let number = || value?.parse().ok();
+    let text = || value.map(str::to_string);
+
+    let err = match info.reason.as_str() {
+        "INVALID_TIME" => {
+            // the field is only in the violation
+            let bad_request: BadRequest = unpack(&details, BAD_REQUEST)?;
+            Error::invalid_time(&bad_request.field_violations.first()?.field, value?)
+        }
+        "RESERVATION_CONFLICT" => {
+            let conflict = unpack::<details::ReservationConflict>(&details, RESERVATION_CONFLICT)
+                .and_then(|conflict| ReservationConflict::try_from(conflict).ok());
+            match conflict {
+                Some(conflict) => Error::ConflictReservation(conflict.into()),
+                None => Error::ConflictReservation(value?.into()),
+            }
+        }
+        "INVALID_TIMEZONE" => Error::InvalidTimezone(text()?),
+        "NOT_FOUND" => Error::NotFound,
+        "INVALID_RESERVATION_ID" => Error::InvalidReservationId(number()?),
+        "INVALID_USER_ID" => Error::InvalidUserId(text()?),
+        "INVALID_RESOURCE_ID" => Error::InvalidResourceId(text()?),
+        "INVALID_PAGE_SIZE" => Error::InvalidPageSize(number()?),
+        "INVALID_CURSOR" => Error::InvalidCursor(number()?),
+        "INVALID_STATUS" => Error::InvalidStatus(value?.parse().ok()?),
+        "INVALID_EVENT_TYPE" => Error::InvalidEventType(value?.parse().ok()?),
+        "INVALID_WEBHOOK_URL" => Error::InvalidWebhookUrl(text()?),
+        "INVALID_WEBHOOK_SECRET" => Error::InvalidWebhookSecret,
+        "INVALID_ICAL" => Error::InvalidIcal(text()?),
+        "SHUTTING_DOWN" => Error::ShuttingDown,
+        "PERMISSION_DENIED" => Error::PermissionDenied,
+        "UNKNOWN" => Error::Unknown,
+        // the server side errors are kept as they are
+        _ => return None,
+    };
+    Some(err)
+}
+
+fn pack(name: &str, message: &impl Message) -> Any {
+    Any {
+        type_url: format!("{}{}", TYPE_URL_PREFIX, name),
+        value: message.encode_to_vec(),
+    }
+}
+
+fn unpack<T: Message + Default>(details: &[Any], name: &str) -> Option<T> {
+    details
+        .iter()
+        .find(|any| any.type_url.strip_prefix(TYPE_URL_PREFIX) == Some(name))
+        .and_then(|any| T::decode(any.value.as_slice()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFLICT: &str = "Key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-26 22:00:00+00\",\"2022-12-30 19:00:00+00\")) conflicts with existing key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-25 22:00:00+00\",\"2022-12-28 19:00:00+00\")).";
+
+    fn details_of(status: &tonic::Status) -> Vec<Any> {
+        tonic_types::pb::Status::decode(status.details())
+            .unwrap()
+            .details
+    }
+
+    #[test]
+    fn validation_error_should_have_error_info_and_field_violation() {
+        let status = tonic::Status::from(Error::InvalidPageSize(1000));
+        let details = details_of(&status);
+
+        let info: ErrorInfo = unpack(&details, ERROR_INFO).unwrap();
+        assert_eq!(info.reason, "INVALID_PAGE_SIZE");
+        assert_eq!(info.domain, ERROR_DOMAIN);
+        assert_eq!(info.metadata[VALUE_KEY], "1000");
+
+        let bad_request: BadRequest = unpack(&details, BAD_REQUEST).unwrap();
+        assert_eq!(bad_request.field_violations.len(), 1);
+        assert_eq!(bad_request.field_violations[0].field, "page_size");
+        assert_eq!(
+            bad_request.field_violations[0].description,
+            "Invalid page size: 1000"
+        );
+    }
+
+    #[test]
+    fn conflict_should_be_decoded_from_details() {
+        let err = || Error::ConflictReservation(CONFLICT.into());
+        let status = tonic::Status::from(err());
+        let conflict: details::ReservationConflict =
+            unpack(&details_of(&status), RESERVATION_CONFLICT).unwrap();
+        let old = conflict.old.unwrap();
+        assert_eq!(old.resource_id, "ocean-view-room-713");
+        assert_eq!(old.start.unwrap().seconds, 1672005600);
+
+        // the message is not needed to rebuild the error
+        let status = tonic::Status::with_details(
+            status.code(),
+            "something else",
+            status.details().to_vec().into(),
+        );
+        assert_eq!(Error::from(status), err());
+    }
+
+    #[test]
+    fn details_of_other_domain_should_be_ignored() {
+        let info = ErrorInfo {
+            reason: "NOT_FOUND".to_string(),
+            domain: "example.com".to_string(),
+            metadata: HashMap::new(),
+        };
+        let status = tonic_types::pb::Status {
+            code: tonic::Code::NotFound as i32,
+            message: "no such user".to_string(),
+            details: vec![pack(ERROR_INFO, &info)],
+        };
+        let status = tonic::Status::with_details(
+            tonic::Code::FailedPrecondition,
+            "no such user",
+            status.encode_to_vec().into(),
+        );
+        assert_eq!(
+            Error::from(status),
+            Error::RpcError(tonic::Code::FailedPrecondition, "no such user".to_string())
+        );
+    }
+}
@@ -1,22 +1,33 @@
 mod conflict;
+mod details;
+mod pg_detail;
 
 use sqlx::postgres::PgDatabaseError;
 
-pub use conflict::{ReservationConflict, ReservationConflictInfo, ReservationWindow};
+pub use conflict::{
+    ConflictingReservation, ReservationConflict, ReservationConflictInfo, ReservationWindow,
+};
+pub use details::ERROR_DOMAIN;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Database error")]
     DbError(sqlx::Error),
 
-    #[error("Failed to read configuration file")]
-    ConfigReadError,
+    #[error("Failed to read configuration file {0}: {1}")]
+    ConfigReadError(String, String),
 
-    #[error("Failed to parse configuration file")]
-    ConfigParseError,
+    #[error("Failed to parse configuration file: {0}")]
+    ConfigParseError(String),
 
-    #[error("Invalid start or end time for the reservation")]
-    InvalidTime,
+    #[error("Invalid configuration {key}: {reason}")]
+    InvalidConfig { key: String, reason: String },
+
+    #[error("Invalid time for {field}: {reason}")]
+    InvalidTime { field: String, reason: String },
+
+    #[error("Invalid timezone: {0}")]
+    InvalidTimezone(String),
 
     #[error("Conflict reservation")]
     ConflictReservation(ReservationConflictInfo),
@@ -42,35 +53,116 @@ pub enum Error {
     #[error("Invalid status: {0}")]
     InvalidStatus(i32),
 
+    #[error("Invalid event type: {0}")]
+    InvalidEventType(i32),
+
+    #[error("Invalid webhook url: {0}")]
+    InvalidWebhookUrl(String),
+
+    #[error("Webhook secret should not be empty")]
+    InvalidWebhookSecret,
+
+    #[error("Invalid iCalendar: {0}")]
+    InvalidIcal(String),
+
+    #[error("Failed to publish event: {0}")]
+    EventSinkError(String),
+
+    #[error("Database schema version {actual} doesn't match the expected {expected}")]
+    SchemaMismatch { expected: i64, actual: i64 },
+
+    #[error("Server is shutting down")]
+    ShuttingDown,
+
+    #[error("Permission denied")]
+    PermissionDenied,
+
+    #[error("Rpc error ({0:?}): {1}")]
+    RpcError(tonic::Code, String),
+
     #[error("unknown error")]
     Unknown,
 }
 
+const CONFLICT_PREFIX: &str = "Conflict reservation: ";
+
 impl PartialEq for Error {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::DbError(_), Self::DbError(_)) => true,
-            (Self::InvalidTime, Self::InvalidTime) => true,
+            (Self::ConfigReadError(f1, e1), Self::ConfigReadError(f2, e2)) => f1 == f2 && e1 == e2,
+            (Self::ConfigParseError(v1), Self::ConfigParseError(v2)) => v1 == v2,
+            (
+                Self::InvalidConfig {
+                    key: k1,
+                    reason: r1,
+                },
+                Self::InvalidConfig {
+                    key: k2,
+                    reason: r2,
+                },
+            ) => k1 == k2 && r1 == r2,
+            (
+                Self::InvalidTime {
+                    field: f1,
+                    reason: r1,
+                },
+                Self::InvalidTime {
+                    field: f2,
+                    reason: r2,
+                },
+            ) => f1 == f2 && r1 == r2,
+            (Self::InvalidTimezone(v1), Self::InvalidTimezone(v2)) => v1 == v2,
             (Self::ConflictReservation(v1), Self::ConflictReservation(v2)) => v1 == v2,
             (Self::NotFound, Self::NotFound) => true,
             (Self::InvalidReservationId(v1), Self::InvalidReservationId(v2)) => v1 == v2,
             (Self::InvalidUserId(v1), Self::InvalidUserId(v2)) => v1 == v2,
             (Self::InvalidResourceId(v1), Self::InvalidResourceId(v2)) => v1 == v2,
+            (Self::InvalidPageSize(v1), Self::InvalidPageSize(v2)) => v1 == v2,
+            (Self::InvalidCursor(v1), Self::InvalidCursor(v2)) => v1 == v2,
+            (Self::InvalidStatus(v1), Self::InvalidStatus(v2)) => v1 == v2,
+            (Self::InvalidEventType(v1), Self::InvalidEventType(v2)) => v1 == v2,
+            (Self::InvalidWebhookUrl(v1), Self::InvalidWebhookUrl(v2)) => v1 == v2,
+            (Self::InvalidWebhookSecret, Self::InvalidWebhookSecret) => true,
+            (Self::EventSinkError(v1), Self::EventSinkError(v2)) => v1 == v2,
+            (Self::InvalidIcal(v1), Self::InvalidIcal(v2)) => v1 == v2,
+            (
+                Self::SchemaMismatch {
+                    expected: e1,
+                    actual: a1,
+                },
+                Self::SchemaMismatch {
+                    expected: e2,
+                    actual: a2,
+                },
+            ) => e1 == e2 && a1 == a2,
+            (Self::ShuttingDown, Self::ShuttingDown) => true,
+            (Self::PermissionDenied, Self::PermissionDenied) => true,
+            (Self::RpcError(c1, m1), Self::RpcError(c2, m2)) => c1 == c2 && m1 == m2,
             (Self::Unknown, Self::Unknown) => true,
             _ => false,
         }
     }
 }
 
+impl Error {
+    pub fn invalid_time(field: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidTime {
+            field: field.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
 impl From<sqlx::Error> for Error {
     fn from(e: sqlx::Error) -> Self {
         match e {
             sqlx::Error::Database(e) => {
                 let err: &PgDatabaseError = e.downcast_ref();
                 match (err.code(), err.table()) {
-                    ("23P01", Some("reservations")) => {
-                        Error::ConflictReservation(err.detail().unwrap().parse().unwrap())
-                    }
+                    ("23P01", Some("reservations")) => Error::ConflictReservation(
+                        err.detail().unwrap_or_else(|| err.message()).into(),
+                    ),
                     _ => Error::DbError(sqlx::Error::Database(e)),
                 }
             }
@@ -82,24 +174,136 @@ impl From<sqlx::Error> for Error {
 
 impl From<Error> for tonic::Status {
     fn from(e: Error) -> Self {
-        match e {
-            Error::DbError(_) | Error::ConfigReadError | Error::ConfigParseError => {
-                tonic::Status::internal(e.to_string())
-            }
-            Error::InvalidTime
+        let (code, message) = match &e {
+            Error::DbError(_)
+            | Error::ConfigReadError(..)
+            | Error::ConfigParseError(_)
+            | Error::InvalidConfig { .. }
+            | Error::SchemaMismatch { .. }
+            | Error::EventSinkError(_) => (tonic::Code::Internal, e.to_string()),
+            Error::InvalidTime { .. }
+            | Error::InvalidTimezone(_)
             | Error::InvalidReservationId(_)
             | Error::InvalidUserId(_)
             | Error::InvalidResourceId(_)
             | Error::InvalidPageSize(_)
             | Error::InvalidCursor(_)
-            | Error::InvalidStatus(_) => tonic::Status::invalid_argument(e.to_string()),
-            Error::ConflictReservation(info) => {
-                tonic::Status::failed_precondition(format!("Conflict reservation: {:?}", info))
+            | Error::InvalidStatus(_)
+            | Error::InvalidEventType(_)
+            | Error::InvalidWebhookUrl(_)
+            | Error::InvalidWebhookSecret
+            | Error::InvalidIcal(_) => (tonic::Code::InvalidArgument, e.to_string()),
+            Error::ConflictReservation(info) => (
+                tonic::Code::FailedPrecondition,
+                format!("{}{}", CONFLICT_PREFIX, info.detail()),
+            ),
+            Error::NotFound => (
+                tonic::Code::NotFound,
+                "No reservation found by the given condition".to_string(),
+            ),
+            Error::ShuttingDown => (tonic::Code::Unavailable, e.to_string()),
+            Error::PermissionDenied => (tonic::Code::PermissionDenied, e.to_string()),
+            // relayed as it is
+            Error::RpcError(code, message) => return tonic::Status::new(*code, message.clone()),
+            Error::Unknown => (tonic::Code::Unknown, "unknown error".to_string()),
+        };
+        details::status_with_details(code, message, &e)
+    }
+}
+
+/// the reverse of `From<Error> for tonic::Status`, used by the clients. The error is
+/// rebuilt from the status details, or the message if the details are not available
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        if let Some(err) = details::error_from_details(&status) {
+            return err;
+        }
+        let rpc_error = || Error::RpcError(status.code(), status.message().to_string());
+        match status.code() {
+            tonic::Code::NotFound => Error::NotFound,
+            tonic::Code::FailedPrecondition => match status.message().strip_prefix(CONFLICT_PREFIX)
+            {
+                Some(detail) => Error::ConflictReservation(detail.into()),
+                None => rpc_error(),
+            },
+            tonic::Code::InvalidArgument => {
+                parse_invalid_argument(status.message()).unwrap_or_else(rpc_error)
             }
-            Error::NotFound => {
-                tonic::Status::not_found("No reservation found by the given condition")
+            tonic::Code::Unavailable if status.message() == "Server is shutting down" => {
+                Error::ShuttingDown
             }
-            Error::Unknown => tonic::Status::unknown("unknown error"),
+            tonic::Code::PermissionDenied if status.message() == "Permission denied" => {
+                Error::PermissionDenied
+            }
+            tonic::Code::Unknown if status.message() == "unknown error" => Error::Unknown,
+            _ => rpc_error(),
         }
     }
 }
+
+fn parse_invalid_argument(message: &str) -> Option<Error> {
+    let (name, value) = message.split_once(": ").unwrap_or((message, ""));
+    if let Some(field) = name.strip_prefix("Invalid time for ") {
+        return Some(Error::invalid_time(field, value));
+    }
+    let err = match name {
+        "Invalid timezone" => Error::InvalidTimezone(value.into()),
+        "Invalid reservation id" => Error::InvalidReservationId(value.parse().ok()?),
+        "Invalid user id" => Error::InvalidUserId(value.into()),
+        "Invalid resource id" => Error::InvalidResourceId(value.into()),
+        "Invalid page size" => Error::InvalidPageSize(value.parse().ok()?),
+        "Invalid cursor" => Error::InvalidCursor(value.parse().ok()?),
+        "Invalid status" => Error::InvalidStatus(value.parse().ok()?),
+        "Invalid event type" => Error::InvalidEventType(value.parse().ok()?),
+        "Invalid webhook url" => Error::InvalidWebhookUrl(value.into()),
+        "Webhook secret should not be empty" => Error::InvalidWebhookSecret,
+        "Invalid iCalendar" => Error::InvalidIcal(value.into()),
+        _ => return None,
+    };
+    Some(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFLICT: &str = "Key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-26 22:00:00+00\",\"2022-12-30 19:00:00+00\")) conflicts with existing key (resource_id, timespan)=(ocean-view-room-713, [\"2022-12-25 22:00:00+00\",\"2022-12-28 19:00:00+00\")).";
+
+    #[test]
+    fn error_should_survive_status_round_trip() {
+        let errors = || {
+            vec![
+                Error::invalid_time("end", "not after the start"),
+                Error::InvalidTimezone("Mars/Olympus_Mons".into()),
+                Error::ConflictReservation(CONFLICT.into()),
+                Error::NotFound,
+                Error::InvalidReservationId(-1),
+                Error::InvalidUserId("".into()),
+                Error::InvalidResourceId("".into()),
+                Error::InvalidPageSize(1000),
+                Error::InvalidCursor(-2),
+                Error::InvalidStatus(9),
+                Error::InvalidEventType(9),
+                Error::InvalidWebhookUrl("ftp://example.com".into()),
+                Error::InvalidWebhookSecret,
+                Error::InvalidIcal("missing DTSTART".into()),
+                Error::ShuttingDown,
+                Error::PermissionDenied,
+                Error::Unknown,
+            ]
+        };
+        for (err, expected) in errors().into_iter().zip(errors()) {
+            let status: tonic::Status = err.into();
+            assert_eq!(Error::from(status), expected);
+        }
+    }
+
+    #[test]
+    fn unmapped_status_should_be_kept() {
+        let err = Error::from(tonic::Status::unavailable("connection refused"));
+        assert_eq!(
+            err,
+            Error::RpcError(tonic::Code::Unavailable, "connection refused".into())
+        );
+    }
+}
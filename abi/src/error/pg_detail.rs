@@ -0,0 +1,263 @@
+//! parser of the postgres exclusion violation detail of the reservations, e.g.
+//! `Key (resource_id, timespan)=(room 1, ["2022-12-26 22:00:00+00","2022-12-30 19:00:00+00"))
+//! conflicts with existing key (resource_id, timespan)=(room 1, [...)).`
+//!
+//! The resource id is not quoted by postgres, so every ", " in the key is tried as the
+//! separator until the rest is a valid range. Nothing here should panic on any input.
+
+use std::ops::Bound;
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+use super::ReservationWindow;
+
+type TimeBound = Bound<DateTime<Utc>>;
+
+pub(super) const NEW_KEY: &str = "Key (resource_id, timespan)=(";
+pub(super) const OLD_KEY: &str = ") conflicts with existing key (resource_id, timespan)=(";
+
+/// the new and the old windows of the detail, None if it's not in the expected form
+pub(super) fn parse_conflict(s: &str) -> Option<(ReservationWindow, ReservationWindow)> {
+    let body = s.trim().strip_prefix(NEW_KEY)?;
+    for (new, rest) in keys(body) {
+        let Some(rest) = rest.strip_prefix(OLD_KEY) else {
+            continue;
+        };
+        for (old, rest) in keys(rest) {
+            // the trailing period is not part of the key
+            if matches!(rest, ")" | ").") {
+                return Some((new, old));
+            }
+        }
+    }
+    None
+}
+
+/// all the ways to read `<resource_id>, <range>` at the start of s, with the rest after the range
+fn keys(s: &str) -> Vec<(ReservationWindow, &str)> {
+    s.match_indices(", ")
+        .filter_map(|(i, sep)| {
+            let (start, end, rest) = parse_range(&s[i + sep.len()..])?;
+            let window = ReservationWindow {
+                rid: s[..i].to_string(),
+                start,
+                end,
+            };
+            Some((window, rest))
+        })
+        .collect()
+}
+
+/// a tstzrange literal as `range_in` of postgres reads it, the rest after it is returned
+fn parse_range(s: &str) -> Option<(TimeBound, TimeBound, &str)> {
+    let mut chars = s.char_indices().peekable();
+    let lower_inclusive = match chars.next()?.1 {
+        '[' => true,
+        '(' => false,
+        _ => return None,
+    };
+    let lower = parse_bound(&mut chars)?;
+    if chars.next()?.1 != ',' {
+        return None;
+    }
+    let upper = parse_bound(&mut chars)?;
+    let (i, c) = chars.next()?;
+    let upper_inclusive = match c {
+        ']' => true,
+        ')' => false,
+        _ => return None,
+    };
+    let lower = to_bound(lower, lower_inclusive)?;
+    let upper = to_bound(upper, upper_inclusive)?;
+    Some((lower, upper, &s[i + c.len_utf8()..]))
+}
+
+/// the text of a bound, None for an empty one which means unbounded. Like postgres, the
+/// double quotes could appear anywhere, `\` escapes the next char and `""` in quotes is `"`
+fn parse_bound(
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+) -> Option<Option<String>> {
+    if matches!(chars.peek(), Some((_, ',' | ')' | ']'))) {
+        return Some(None);
+    }
+    let mut text = String::new();
+    let mut in_quote = false;
+    loop {
+        let &(_, c) = chars.peek()?;
+        if !in_quote && matches!(c, ',' | ')' | ']') {
+            return Some(Some(text));
+        }
+        chars.next();
+        match c {
+            '\\' => text.push(chars.next()?.1),
+            '"' if in_quote && matches!(chars.peek(), Some((_, '"'))) => {
+                chars.next();
+                text.push('"');
+            }
+            '"' => in_quote = !in_quote,
+            c => text.push(c),
+        }
+    }
+}
+
+fn to_bound(text: Option<String>, inclusive: bool) -> Option<TimeBound> {
+    let time = match text.as_deref() {
+        None | Some("infinity" | "-infinity") => return Some(Bound::Unbounded),
+        Some(text) => parse_timestamp(text)?,
+    };
+    Some(if inclusive {
+        Bound::Included(time)
+    } else {
+        Bound::Excluded(time)
+    })
+}
+
+/// a timestamptz in the ISO output style of postgres, e.g. `2022-12-26 22:00:00.5+05:30`
+pub(super) fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    // the date has '-' in it, the offset starts after the time
+    let time_start = s.find(' ')?;
+    let offset_start = time_start + s[time_start..].find(['+', '-'])?;
+    let local = NaiveDateTime::parse_from_str(&s[..offset_start], "%Y-%m-%d %H:%M:%S%.f").ok()?;
+    let offset = parse_offset(&s[offset_start..])?;
+    let utc = local.checked_sub_signed(Duration::seconds(offset))?;
+    Some(DateTime::from_utc(utc, Utc))
+}
+
+/// `+HH`, `+HH:MM` or `+HH:MM:SS` in seconds
+fn parse_offset(s: &str) -> Option<i64> {
+    let (sign, s) = match (s.strip_prefix('+'), s.strip_prefix('-')) {
+        (Some(s), _) => (1, s),
+        (_, Some(s)) => (-1, s),
+        _ => return None,
+    };
+    let mut seconds = 0;
+    let mut parts = 0;
+    for (part, unit) in s.split(':').zip([3600, 60, 1]) {
+        if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        seconds += part.parse::<i64>().ok()? * unit;
+        parts += 1;
+    }
+    if s.split(':').count() != parts || seconds >= 24 * 3600 {
+        return None;
+    }
+    Some(sign * seconds)
+}
+
+/// the bound in a range literal, quoted as postgres does for timestamps
+pub(super) fn format_bound(bound: &TimeBound) -> String {
+    match bound {
+        Bound::Included(t) | Bound::Excluded(t) => format!("\"{}\"", format_timestamp(t)),
+        Bound::Unbounded => String::new(),
+    }
+}
+
+fn format_timestamp(t: &DateTime<Utc>) -> String {
+    let nanos = t.timestamp_subsec_nanos();
+    let fraction = if nanos == 0 {
+        String::new()
+    } else {
+        format!(".{:09}", nanos).trim_end_matches('0').to_string()
+    };
+    format!("{}{}+00", t.format("%Y-%m-%d %H:%M:%S"), fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_timestamp_should_support_fractions_and_offsets() {
+        let cases = [
+            ("2022-12-26 22:00:00+00", "2022-12-26T22:00:00Z"),
+            (
+                "2022-12-26 22:00:00.123456+00",
+                "2022-12-26T22:00:00.123456Z",
+            ),
+            ("2022-12-26 22:00:00.5-07", "2022-12-27T05:00:00.5Z"),
+            ("2022-12-26 22:00:00+05:30", "2022-12-26T16:30:00Z"),
+            ("1883-11-18 12:00:00+00:09:21", "1883-11-18T11:50:39Z"),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(parse_timestamp(s), Some(utc(expected)), "{}", s);
+        }
+        for s in [
+            "2022-12-26",
+            "2022-12-26 22:00:00",
+            "2022-12-26 22:00:00+5",
+            "2022-12-26 22:00:00+05:",
+            "2022-12-26 22:00:00+24",
+            "2022-12-26 22:00:00+00 BC",
+        ] {
+            assert_eq!(parse_timestamp(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_range_should_support_bound_styles() {
+        let (start, end, rest) =
+            parse_range(r#"("2022-12-26 22:00:00+00","2022-12-27 22:00:00+00"]) tail"#).unwrap();
+        assert_eq!(start, Bound::Excluded(utc("2022-12-26T22:00:00Z")));
+        assert_eq!(end, Bound::Included(utc("2022-12-27T22:00:00Z")));
+        assert_eq!(rest, ") tail");
+
+        let (start, end, _) = parse_range(r#"[-infinity,"2022-12-27 22:00:00+00")"#).unwrap();
+        assert_eq!(start, Bound::Unbounded);
+        assert_eq!(end, Bound::Excluded(utc("2022-12-27T22:00:00Z")));
+
+        let (start, end, _) = parse_range(r#"["2022-12-26 22:00:00+00",)"#).unwrap();
+        assert_eq!(start, Bound::Included(utc("2022-12-26T22:00:00Z")));
+        assert_eq!(end, Bound::Unbounded);
+
+        // quotes could be anywhere and escaped
+        let (start, ..) = parse_range(r#"[2022-12-26" 22:00:00"\+00,)"#).unwrap();
+        assert_eq!(start, Bound::Included(utc("2022-12-26T22:00:00Z")));
+
+        for s in [
+            "",
+            "[",
+            "[,",
+            "[,,)",
+            "{,)",
+            r#"["2022-12-26 22:00:00+00,)"#,
+            "[x,)",
+        ] {
+            assert_eq!(parse_range(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_conflict_should_support_any_resource_id() {
+        let s = r#"Key (resource_id, timespan)=(room.1:x/y z, ["2022-12-26 22:00:00+00","2022-12-27 22:00:00+00")) conflicts with existing key (resource_id, timespan)=(a, b) "x" \y, ("2022-12-25 22:00:00.123456+00",infinity])."#;
+        let (new, old) = parse_conflict(s).unwrap();
+        assert_eq!(new.rid, "room.1:x/y z");
+        assert_eq!(old.rid, r#"a, b) "x" \y"#);
+        assert_eq!(
+            old.start,
+            Bound::Excluded(utc("2022-12-25T22:00:00.123456Z"))
+        );
+        assert_eq!(old.end, Bound::Unbounded);
+    }
+
+    #[test]
+    fn parse_conflict_should_not_panic_on_mangled_input() {
+        let s = r#"Key (resource_id, timespan)=(ré, ["2022-12-26 22:00:00.5+05:30","2022-12-27 22:00:00+00")) conflicts with existing key (resource_id, timespan)=(ré, [,"2022-12-28 22:00:00+00"))."#;
+        assert!(parse_conflict(s).is_some());
+        // the trailing period is optional
+        for i in 0..s.len() - 1 {
+            if !s.is_char_boundary(i) {
+                continue;
+            }
+            // truncated, or with a char removed or doubled
+            assert!(parse_conflict(&s[..i]).is_none(), "{}", &s[..i]);
+            let next = s[i..].chars().next().map_or(0, char::len_utf8);
+            let _ = parse_conflict(&format!("{}{}", &s[..i], &s[i + next..]));
+            let _ = parse_conflict(&format!("{}{}", &s[..i + next], &s[i..]));
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! iCalendar (RFC 5545) serialization and parsing of reservations
+
+mod parse;
+
+use chrono::{DateTime, Utc};
+
+use crate::{convert_to_utc_time, Reservation, ReservationStatus};
+
+pub use parse::parse_ical;
+
+/// domain part of the UID, the UID of a reservation is stable as long as its id doesn't change
+const UID_DOMAIN: &str = "reservation-service";
+const PRODID: &str = "-//reservation-service//reservation//EN";
+/// the reservation has no end, the event has neither DTEND nor DURATION
+const OPEN_END: &str = "X-RESERVATION-OPEN-END";
+const START_EXCLUSIVE: &str = "X-RESERVATION-START-EXCLUSIVE";
+const END_INCLUSIVE: &str = "X-RESERVATION-END-INCLUSIVE";
+/// the status which has no iCalendar equivalent, i.e. BLOCKED
+const STATUS: &str = "X-RESERVATION-STATUS";
+/// max octets of a content line, longer lines are folded
+const MAX_LINE_OCTETS: usize = 75;
+
+/// beginning of an iCalendar document, followed by the VEVENTs
+pub fn ical_header() -> String {
+    [
+        content_line("BEGIN", "VCALENDAR"),
+        content_line("VERSION", "2.0"),
+        content_line("PRODID", PRODID),
+        content_line("CALSCALE", "GREGORIAN"),
+    ]
+    .concat()
+}
+
+/// end of an iCalendar document
+pub fn ical_footer() -> String {
+    content_line("END", "VCALENDAR")
+}
+
+/// iCalendar document of the reservations
+pub fn to_ical<'a>(
+    reservations: impl IntoIterator<Item = &'a Reservation>,
+    stamp: DateTime<Utc>,
+) -> String {
+    let mut ical = ical_header();
+    for reservation in reservations {
+        ical.push_str(&reservation.to_vevent(stamp));
+    }
+    ical.push_str(&ical_footer());
+    ical
+}
+
+impl Reservation {
+    /// VEVENT of the reservation, `stamp` is the time when the iCalendar object is created
+    pub fn to_vevent(&self, stamp: DateTime<Utc>) -> String {
+        let mut lines = vec![
+            content_line("BEGIN", "VEVENT"),
+            content_line("UID", &format!("{}@{}", self.id, UID_DOMAIN)),
+            content_line("DTSTAMP", &format_time(stamp)),
+        ];
+        // the missing or invalid times are left out
+        if let Ok(start) = convert_to_utc_time(self.start.as_ref(), "start") {
+            lines.push(content_line("DTSTART", &format_time(start)));
+        }
+        if let Ok(end) = convert_to_utc_time(self.end.as_ref(), "end") {
+            lines.push(content_line("DTEND", &format_time(end)));
+        } else if self.end.is_none() {
+            // without DTEND an event would last for a moment, mark it to be read back
+            lines.push(content_line(OPEN_END, "TRUE"));
+        }
+        lines.push(content_line("SUMMARY", &escape_text(&self.resource_id)));
+        if !self.note.is_empty() {
+            lines.push(content_line("DESCRIPTION", &escape_text(&self.note)));
+        }
+        lines.push(content_line("STATUS", ical_status(self.status)));
+        // the cancelled events are skipped on import, keep the blocked ones
+        if self.status == ReservationStatus::Blocked as i32 {
+            lines.push(content_line(STATUS, "BLOCKED"));
+        }
+        lines.push(content_line(
+            "X-RESERVATION-USER-ID",
+            &escape_text(&self.user_id),
+        ));
+        lines.push(content_line(
+            "X-RESERVATION-RESOURCE-ID",
+            &escape_text(&self.resource_id),
+        ));
+        // iCalendar events are [start, end), the other bounds are kept in the extensions
+        if self.start_exclusive {
+            lines.push(content_line(START_EXCLUSIVE, "TRUE"));
+        }
+        if self.end_inclusive {
+            lines.push(content_line(END_INCLUSIVE, "TRUE"));
+        }
+        lines.push(content_line("END", "VEVENT"));
+        lines.concat()
+    }
+}
+
+/// pending reservations are tentative, blocked ones are not bookings and shown as cancelled
+fn ical_status(status: i32) -> &'static str {
+    match ReservationStatus::from_i32(status) {
+        Some(ReservationStatus::Confirmed) => "CONFIRMED",
+        Some(ReservationStatus::Blocked) => "CANCELLED",
+        _ => "TENTATIVE",
+    }
+}
+
+fn format_time(dt: DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// build a content line terminated by CRLF, folded if longer than 75 octets
+fn content_line(name: &str, value: &str) -> String {
+    let line = format!("{}:{}", name, value);
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // the leading space counts
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservation_should_convert_to_vevent() {
+        let mut reservation = Reservation::new(
+            "alon",
+            "ocean-view-room-713",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "late check-in; bring keys, please",
+            ReservationStatus::Confirmed,
+        );
+        reservation.id = 42;
+        let stamp = "2023-01-01T00:00:00Z".parse().unwrap();
+        assert_eq!(
+            reservation.to_vevent(stamp),
+            "BEGIN:VEVENT\r\n\
+             UID:42@reservation-service\r\n\
+             DTSTAMP:20230101T000000Z\r\n\
+             DTSTART:20221226T220000Z\r\n\
+             DTEND:20221230T190000Z\r\n\
+             SUMMARY:ocean-view-room-713\r\n\
+             DESCRIPTION:late check-in\\; bring keys\\, please\r\n\
+             STATUS:CONFIRMED\r\n\
+             X-RESERVATION-USER-ID:alon\r\n\
+             X-RESERVATION-RESOURCE-ID:ocean-view-room-713\r\n\
+             END:VEVENT\r\n"
+        );
+    }
+
+    #[test]
+    fn status_should_map_to_ical_status() {
+        assert_eq!(ical_status(ReservationStatus::Pending as i32), "TENTATIVE");
+        assert_eq!(
+            ical_status(ReservationStatus::Confirmed as i32),
+            "CONFIRMED"
+        );
+        assert_eq!(ical_status(ReservationStatus::Blocked as i32), "CANCELLED");
+    }
+
+    #[test]
+    fn long_content_line_should_be_folded() {
+        let line = content_line("DESCRIPTION", &"a".repeat(100));
+        let lines: Vec<_> = line.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "a".repeat(100 - (75 - 12))));
+    }
+
+    #[test]
+    fn open_end_and_bounds_should_round_trip() {
+        let start = "2023-01-01T09:00:00Z".parse().unwrap();
+        let mut open_ended = Reservation::new_open_ended(
+            "alon",
+            "ocean-view-room-713",
+            start,
+            "",
+            ReservationStatus::Confirmed,
+        );
+        open_ended.start_exclusive = true;
+        let mut bounded = Reservation::new(
+            "alice",
+            "ocean-view-room-713",
+            "2023-01-02T09:00:00Z".parse().unwrap(),
+            "2023-01-03T09:00:00Z".parse().unwrap(),
+            "",
+            ReservationStatus::Pending,
+        );
+        bounded.end_inclusive = true;
+        let blocked = Reservation::new(
+            "maintenance",
+            "ocean-view-room-713",
+            "2023-01-04T09:00:00Z".parse().unwrap(),
+            "2023-01-05T09:00:00Z".parse().unwrap(),
+            "",
+            ReservationStatus::Blocked,
+        );
+
+        let ical = to_ical(
+            &[open_ended.clone(), bounded.clone(), blocked.clone()],
+            "2023-01-01T00:00:00Z".parse().unwrap(),
+        );
+        assert!(ical.contains("X-RESERVATION-OPEN-END:TRUE\r\n"));
+        assert!(ical.contains("STATUS:CANCELLED\r\nX-RESERVATION-STATUS:BLOCKED\r\n"));
+        let parsed = parse_ical(&ical, "ocean-view-room-713", "", "UTC").unwrap();
+        assert_eq!(parsed.len(), 3);
+        for (parsed, source) in parsed.iter().zip([&open_ended, &bounded, &blocked]) {
+            assert_eq!(parsed.user_id, source.user_id);
+            assert_eq!(parsed.status, source.status);
+            assert_eq!(parsed.start, source.start);
+            assert_eq!(parsed.end, source.end);
+            assert_eq!(parsed.start_exclusive, source.start_exclusive);
+            assert_eq!(parsed.end_inclusive, source.end_inclusive);
+        }
+
+        let recurring = "BEGIN:VEVENT\nDTSTART:20230101T090000Z\nX-RESERVATION-OPEN-END:TRUE\nRRULE:FREQ=DAILY;COUNT=2\nX-RESERVATION-USER-ID:alon\nEND:VEVENT\n";
+        assert!(parse_ical(recurring, "ocean-view-room-713", "", "UTC").is_err());
+    }
+
+    #[test]
+    fn to_ical_should_wrap_events_in_calendar() {
+        let ical = to_ical(&[], "2023-01-01T00:00:00Z".parse().unwrap());
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+    }
+}
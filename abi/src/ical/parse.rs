@@ -0,0 +1,717 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    convert_to_timestamp, parse_timezone, types::local_to_utc, Error, Reservation,
+    ReservationStatus, Validator, DEFAULT_TIMEZONE,
+};
+
+use super::{END_INCLUSIVE, OPEN_END, START_EXCLUSIVE, STATUS};
+
+/// max occurrences expanded from a recurring event
+const MAX_OCCURRENCES: usize = 1000;
+/// max steps of a recurring event, including the skipped invalid dates like Feb 30
+const MAX_STEPS: u32 = 10 * MAX_OCCURRENCES as u32;
+/// seconds of a day
+const DAY: i64 = 24 * 3600;
+
+/// A property of an iCalendar component, e.g. `DTSTART;TZID=Europe/Berlin:20230101T100000`
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+/// A date-time in the wall clock of a time zone, floating times and dates are in the zone
+/// of the resource
+#[derive(Debug, Clone, Copy)]
+struct IcalTime {
+    naive: NaiveDateTime,
+    tz: Tz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Simple recurrence rule, only FREQ, INTERVAL, COUNT and UNTIL are supported
+#[derive(Debug)]
+struct RecurrenceRule {
+    freq: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+}
+
+/// Parse the VEVENTs of an iCalendar document into reservations of the resource. Recurring
+/// events are expanded into one reservation per occurrence, cancelled events are skipped.
+/// If `user_id` is empty, the X-RESERVATION-USER-ID of each event is used. The floating times
+/// and dates are in `timezone`, the zone of the resource, UTC if empty.
+pub fn parse_ical(
+    data: &str,
+    resource_id: &str,
+    user_id: &str,
+    timezone: &str,
+) -> Result<Vec<Reservation>, Error> {
+    let floating = match timezone {
+        "" => parse_timezone(DEFAULT_TIMEZONE)?,
+        timezone => parse_timezone(timezone)?,
+    };
+    let mut reservations = vec![];
+    let mut event: Option<Vec<Property>> = None;
+    // depth of the components nested in VEVENT, e.g. VALARM
+    let mut nested = 0;
+
+    for line in unfold(data).iter().filter(|l| !l.is_empty()) {
+        let property = parse_property(line)?;
+        match (
+            property.name.as_str(),
+            property.value.to_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VEVENT") if event.is_none() => event = Some(vec![]),
+            ("BEGIN", _) if event.is_some() => nested += 1,
+            ("END", "VEVENT") if nested == 0 => {
+                let properties = event
+                    .take()
+                    .ok_or_else(|| invalid("END:VEVENT without BEGIN:VEVENT"))?;
+                reservations.extend(to_reservations(
+                    &properties,
+                    resource_id,
+                    user_id,
+                    floating,
+                )?);
+            }
+            ("END", _) if event.is_some() => nested -= 1,
+            _ => {
+                if let (Some(properties), 0) = (event.as_mut(), nested) {
+                    properties.push(property);
+                }
+            }
+        }
+    }
+    if event.is_some() {
+        return Err(invalid("VEVENT is not closed"));
+    }
+    Ok(reservations)
+}
+
+fn to_reservations(
+    properties: &[Property],
+    resource_id: &str,
+    user_id: &str,
+    floating: Tz,
+) -> Result<Vec<Reservation>, Error> {
+    let get = |name: &str| properties.iter().find(|p| p.name == name);
+    let flag = |name: &str| get(name).is_some_and(|p| p.value.eq_ignore_ascii_case("TRUE"));
+
+    let blocked = get(STATUS).is_some_and(|p| p.value.eq_ignore_ascii_case("BLOCKED"));
+    let status = match get("STATUS").map(|p| p.value.to_uppercase()).as_deref() {
+        // exported from a blocked reservation, shown as cancelled in the calendars
+        _ if blocked => ReservationStatus::Blocked,
+        Some("CANCELLED") => return Ok(vec![]),
+        Some("CONFIRMED") => ReservationStatus::Confirmed,
+        _ => ReservationStatus::Pending,
+    };
+
+    let dtstart = get("DTSTART").ok_or_else(|| invalid("missing DTSTART"))?;
+    let start = parse_time(dtstart, floating)?;
+    let duration = match (get("DTEND"), get("DURATION")) {
+        (Some(dtend), _) => {
+            let end = parse_time(dtend, floating)?;
+            // measure the duration in the wall clock of the start
+            let end = end.to_utc()?.with_timezone(&start.tz).naive_local();
+            Some(end - start.naive)
+        }
+        (None, Some(duration)) => Some(parse_duration(&duration.value)?),
+        // exported from an open-ended reservation
+        (None, None) if flag(OPEN_END) => None,
+        // a date event lasts one day
+        (None, None) if is_date(dtstart) => Some(Duration::days(1)),
+        (None, None) => return Err(invalid("missing DTEND or DURATION")),
+    };
+
+    let user_id = match (user_id, get("X-RESERVATION-USER-ID")) {
+        ("", Some(p)) => unescape_text(&p.value),
+        ("", None) => return Err(Error::InvalidUserId(String::new())),
+        (user_id, _) => user_id.to_string(),
+    };
+    let note = get("DESCRIPTION")
+        .or_else(|| get("SUMMARY"))
+        .map(|p| unescape_text(&p.value))
+        .unwrap_or_default();
+
+    let excluded = properties
+        .iter()
+        .filter(|p| p.name == "EXDATE")
+        .flat_map(|p| {
+            p.value.split(',').map(|v| {
+                let time = parse_time_value(v, p, floating)?;
+                time.to_utc()
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the zone of the resource is used if the start is not in a TZID
+    let timezone = match dtstart.param("TZID") {
+        Some(_) => start.tz.name().to_string(),
+        None => String::new(),
+    };
+
+    let occurrences = match get("RRULE") {
+        Some(_) if duration.is_none() => return Err(invalid("open-ended event should not recur")),
+        Some(rule) => expand(start, &parse_rule(&rule.value, floating)?)?,
+        None => vec![start],
+    };
+
+    occurrences
+        .into_iter()
+        .map(|start| {
+            let Some(duration) = duration else {
+                return Ok((start.to_utc()?, None));
+            };
+            let naive = start
+                .naive
+                .checked_add_signed(duration)
+                .ok_or_else(|| invalid("event end is out of range"))?;
+            let end = IcalTime {
+                naive,
+                tz: start.tz,
+            };
+            Ok::<_, Error>((start.to_utc()?, Some(end.to_utc()?)))
+        })
+        .filter(|r| !matches!(r, Ok((start, _)) if excluded.contains(start)))
+        .map(|r| {
+            let (start, end) = r?;
+            let reservation = Reservation {
+                id: 0,
+                user_id: user_id.clone(),
+                status: status as i32,
+                resource_id: resource_id.to_string(),
+                start: Some(convert_to_timestamp(start)),
+                end: end.map(convert_to_timestamp),
+                note: note.clone(),
+                start_exclusive: flag(START_EXCLUSIVE),
+                end_inclusive: flag(END_INCLUSIVE),
+                timezone: timezone.clone(),
+                start_date: String::new(),
+                end_date: String::new(),
+            };
+            reservation.validate()?;
+            Ok(reservation)
+        })
+        .collect()
+}
+
+/// occurrences of the recurring event, including the first one
+fn expand(start: IcalTime, rule: &RecurrenceRule) -> Result<Vec<IcalTime>, Error> {
+    let mut occurrences = vec![];
+    for i in 0..MAX_STEPS {
+        let step = rule
+            .interval
+            .checked_mul(i)
+            .ok_or_else(|| invalid("recurring event is out of range"))?;
+        let naive = add_step(start.naive, rule.freq, step)?;
+        // skip the invalid dates like Feb 30, as RFC 5545 does
+        let occurrence = match naive {
+            Some(naive) => IcalTime { naive, ..start },
+            None => continue,
+        };
+        if let Some(until) = rule.until {
+            if occurrence.to_utc()? > until {
+                return Ok(occurrences);
+            }
+        }
+        occurrences.push(occurrence);
+        if Some(occurrences.len()) == rule.count {
+            return Ok(occurrences);
+        }
+        if occurrences.len() > MAX_OCCURRENCES {
+            return Err(invalid(format!(
+                "recurring event has more than {} occurrences",
+                MAX_OCCURRENCES
+            )));
+        }
+    }
+    Err(invalid(format!(
+        "recurring event doesn't end in {} steps",
+        MAX_STEPS
+    )))
+}
+
+/// the date-time `step` periods after, None if the day doesn't exist in the month
+fn add_step(dt: NaiveDateTime, freq: Frequency, step: u32) -> Result<Option<NaiveDateTime>, Error> {
+    let out_of_range = || invalid("recurring event is out of range");
+    let months = match freq {
+        Frequency::Daily | Frequency::Weekly => {
+            let unit = if freq == Frequency::Daily {
+                DAY
+            } else {
+                7 * DAY
+            };
+            return checked_duration(step as i64, unit)
+                .and_then(|d| dt.checked_add_signed(d))
+                .map(Some)
+                .ok_or_else(out_of_range);
+        }
+        Frequency::Monthly => step,
+        Frequency::Yearly => step.checked_mul(12).ok_or_else(out_of_range)?,
+    };
+    let v = dt
+        .checked_add_months(Months::new(months))
+        .ok_or_else(out_of_range)?;
+    Ok(Some(v).filter(|v| v.day() == dt.day()))
+}
+
+/// `n` units of seconds, None if it's out of the range of Duration, which panics on overflow
+fn checked_duration(n: i64, unit: i64) -> Option<Duration> {
+    let seconds = n.checked_mul(unit)?;
+    (seconds.checked_abs()? <= i64::MAX / 1000).then(|| Duration::seconds(seconds))
+}
+
+fn parse_rule(value: &str, floating: Tz) -> Result<RecurrenceRule, Error> {
+    let mut freq = None;
+    let mut rule = RecurrenceRule {
+        freq: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+    };
+    for part in value.split(';').filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("invalid RRULE part: {}", part)))?;
+        match key.to_uppercase().as_str() {
+            "FREQ" => {
+                freq = Some(match value.to_uppercase().as_str() {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    v => return Err(invalid(format!("unsupported RRULE frequency: {}", v))),
+                })
+            }
+            "INTERVAL" => {
+                rule.interval = parse_number(value)
+                    .filter(|v| *v > 0)
+                    .ok_or_else(|| invalid(format!("invalid RRULE interval: {}", value)))?
+            }
+            "COUNT" => {
+                rule.count = Some(
+                    parse_number(value)
+                        .ok_or_else(|| invalid(format!("invalid RRULE count: {}", value)))?
+                        as usize,
+                )
+            }
+            "UNTIL" => {
+                let property = Property {
+                    name: "UNTIL".into(),
+                    params: vec![],
+                    value: value.into(),
+                };
+                rule.until = Some(parse_time(&property, floating)?.to_utc()?);
+            }
+            "WKST" => {}
+            k => return Err(invalid(format!("unsupported RRULE part: {}", k))),
+        }
+    }
+    rule.freq = freq.ok_or_else(|| invalid("missing RRULE frequency"))?;
+    if rule.count.is_none() && rule.until.is_none() {
+        return Err(invalid("RRULE without COUNT or UNTIL is not supported"));
+    }
+    Ok(rule)
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    value.parse().ok()
+}
+
+fn is_date(property: &Property) -> bool {
+    property.value.len() == 8 || property.param("VALUE") == Some("DATE")
+}
+
+fn parse_time(property: &Property, floating: Tz) -> Result<IcalTime, Error> {
+    parse_time_value(&property.value, property, floating)
+}
+
+/// parse DATE or DATE-TIME value of the property, the TZID parameter is respected. The
+/// dates and the floating times are in the `floating` zone
+fn parse_time_value(value: &str, property: &Property, floating: Tz) -> Result<IcalTime, Error> {
+    let err = || invalid(format!("invalid {}: {}", property.name, value));
+    let (naive, tz) = if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| err())?;
+        (date.and_hms_opt(0, 0, 0).ok_or_else(err)?, floating)
+    } else if let Some(value) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| err())?;
+        (naive, Tz::UTC)
+    } else {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| err())?;
+        let tz = match property.param("TZID") {
+            Some(tzid) => tzid
+                .parse()
+                .map_err(|_| invalid(format!("unknown TZID: {}", tzid)))?,
+            None => floating,
+        };
+        (naive, tz)
+    };
+    Ok(IcalTime { naive, tz })
+}
+
+/// parse DURATION value, e.g. `P1D`, `PT1H30M`, `P2W`
+fn parse_duration(value: &str) -> Result<Duration, Error> {
+    let err = || invalid(format!("invalid DURATION: {}", value));
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P').ok_or_else(err)?;
+
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            _ => {
+                let n: i64 = number.parse().map_err(|_| err())?;
+                number.clear();
+                let unit = match (c, in_time) {
+                    ('W', false) => 7 * DAY,
+                    ('D', false) => DAY,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return Err(err()),
+                };
+                duration = checked_duration(n, unit)
+                    .and_then(|v| duration.checked_add(&v))
+                    .ok_or_else(err)?;
+            }
+        }
+    }
+    if !number.is_empty() {
+        return Err(err());
+    }
+    Ok(if negative { -duration } else { duration })
+}
+
+impl IcalTime {
+    fn to_utc(self) -> Result<DateTime<Utc>, Error> {
+        // the local times in the DST gap are shifted forward, like the reservation dates
+        local_to_utc(self.naive, self.tz)
+            .ok_or_else(|| invalid(format!("nonexistent local time: {}", self.naive)))
+    }
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// join the folded lines
+fn unfold(data: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in data.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// parse a content line: `name *(";" param) ":" value`, param values might be quoted
+fn parse_property(line: &str) -> Result<Property, Error> {
+    let mut in_quotes = false;
+    let colon = line
+        .char_indices()
+        .find(|(_, c)| {
+            if *c == '"' {
+                in_quotes = !in_quotes;
+            }
+            *c == ':' && !in_quotes
+        })
+        .map(|(i, _)| i)
+        .ok_or_else(|| invalid(format!("invalid content line: {}", line)))?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next().unwrap_or_default().trim().to_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.trim().to_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Ok(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn unescape_text(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::InvalidIcal(msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert_to_utc_time, to_ical};
+
+    fn times(reservations: &[Reservation]) -> Vec<(String, String)> {
+        reservations
+            .iter()
+            .map(|r| {
+                (
+                    convert_to_utc_time(r.start.as_ref(), "start")
+                        .unwrap()
+                        .to_rfc3339(),
+                    convert_to_utc_time(r.end.as_ref(), "end")
+                        .unwrap()
+                        .to_rfc3339(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exported_ical_should_parse_back() {
+        let mut reservation = Reservation::new(
+            "alon",
+            "ocean-view-room-713",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "late check-in; bring keys, please. ".repeat(3),
+            ReservationStatus::Confirmed,
+        );
+        let ical = to_ical(&[reservation.clone()], Utc::now());
+        let reservations = parse_ical(&ical, "ocean-view-room-713", "", "UTC").unwrap();
+        reservation.id = 0;
+        assert_eq!(reservations, vec![reservation]);
+    }
+
+    #[test]
+    fn recurring_event_should_expand() {
+        let ical = "BEGIN:VCALENDAR\r\n\
+                    BEGIN:VEVENT\r\n\
+                    DTSTART;TZID=Europe/Berlin:20230324T100000\r\n\
+                    DTEND;TZID=Europe/Berlin:20230324T110000\r\n\
+                    RRULE:FREQ=DAILY;INTERVAL=2;COUNT=4\r\n\
+                    EXDATE;TZID=Europe/Berlin:20230328T100000\r\n\
+                    SUMMARY:standup\r\n\
+                    BEGIN:VALARM\r\n\
+                    TRIGGER:-PT15M\r\n\
+                    END:VALARM\r\n\
+                    END:VEVENT\r\n\
+                    END:VCALENDAR\r\n";
+        let reservations = parse_ical(ical, "room-1", "alon", "UTC").unwrap();
+        // DST starts on 2023-03-26 in Berlin, the wall clock time is kept
+        assert_eq!(
+            times(&reservations),
+            vec![
+                (
+                    "2023-03-24T09:00:00+00:00".into(),
+                    "2023-03-24T10:00:00+00:00".into()
+                ),
+                (
+                    "2023-03-26T08:00:00+00:00".into(),
+                    "2023-03-26T09:00:00+00:00".into()
+                ),
+                (
+                    "2023-03-30T08:00:00+00:00".into(),
+                    "2023-03-30T09:00:00+00:00".into()
+                ),
+            ]
+        );
+        assert!(reservations
+            .iter()
+            .all(|r| r.note == "standup" && r.status == ReservationStatus::Pending as i32));
+        assert!(reservations.iter().all(|r| r.timezone == "Europe/Berlin"));
+    }
+
+    #[test]
+    fn monthly_event_should_skip_invalid_dates() {
+        let ical = "BEGIN:VEVENT\n\
+                    DTSTART:20230131T090000Z\n\
+                    DURATION:PT1H30M\n\
+                    RRULE:FREQ=MONTHLY;UNTIL=20230430T000000Z\n\
+                    END:VEVENT\n";
+        let reservations = parse_ical(ical, "room-1", "alon", "UTC").unwrap();
+        assert_eq!(
+            times(&reservations),
+            vec![
+                (
+                    "2023-01-31T09:00:00+00:00".into(),
+                    "2023-01-31T10:30:00+00:00".into()
+                ),
+                (
+                    "2023-03-31T09:00:00+00:00".into(),
+                    "2023-03-31T10:30:00+00:00".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn date_event_should_last_one_day() {
+        let ical = "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20230101\nEND:VEVENT\n";
+        let reservations = parse_ical(ical, "room-1", "alon", "UTC").unwrap();
+        assert_eq!(
+            times(&reservations),
+            vec![(
+                "2023-01-01T00:00:00+00:00".into(),
+                "2023-01-02T00:00:00+00:00".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn cancelled_event_should_be_skipped() {
+        let ical = "BEGIN:VEVENT\nDTSTART:20230101T090000Z\nDTEND:20230101T100000Z\nSTATUS:CANCELLED\nEND:VEVENT\n";
+        assert!(parse_ical(ical, "room-1", "alon", "UTC")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_ical_should_be_rejected() {
+        let unbounded = "BEGIN:VEVENT\nDTSTART:20230101T090000Z\nDTEND:20230101T100000Z\nRRULE:FREQ=WEEKLY\nEND:VEVENT\n";
+        assert_eq!(
+            parse_ical(unbounded, "room-1", "alon", "UTC").unwrap_err(),
+            Error::InvalidIcal("RRULE without COUNT or UNTIL is not supported".into())
+        );
+        let by_day = "BEGIN:VEVENT\nDTSTART:20230101T090000Z\nDTEND:20230101T100000Z\nRRULE:FREQ=WEEKLY;BYDAY=MO;COUNT=2\nEND:VEVENT\n";
+        assert_eq!(
+            parse_ical(by_day, "room-1", "alon", "UTC").unwrap_err(),
+            Error::InvalidIcal("unsupported RRULE part: BYDAY".into())
+        );
+        let no_user =
+            "BEGIN:VEVENT\nDTSTART:20230101T090000Z\nDTEND:20230101T100000Z\nEND:VEVENT\n";
+        assert_eq!(
+            parse_ical(no_user, "room-1", "", "UTC").unwrap_err(),
+            Error::InvalidUserId(String::new())
+        );
+        let not_closed = "BEGIN:VEVENT\nDTSTART:20230101T090000Z\n";
+        assert_eq!(
+            parse_ical(not_closed, "room-1", "alon", "UTC").unwrap_err(),
+            Error::InvalidIcal("VEVENT is not closed".into())
+        );
+    }
+
+    #[test]
+    fn huge_values_should_be_rejected() {
+        let event = |rule: &str| {
+            format!(
+                "BEGIN:VEVENT\nDTSTART:20230131T090000Z\nDURATION:PT1H\nRRULE:{}\nEND:VEVENT\n",
+                rule
+            )
+        };
+        let out_of_range = Error::InvalidIcal("recurring event is out of range".into());
+        for rule in [
+            "FREQ=DAILY;INTERVAL=4294967295;COUNT=3",
+            "FREQ=WEEKLY;INTERVAL=4000000000;COUNT=2",
+            "FREQ=MONTHLY;INTERVAL=4294967295;COUNT=3",
+            "FREQ=YEARLY;INTERVAL=400000000;COUNT=3",
+            "FREQ=MONTHLY;INTERVAL=1000000000;UNTIL=99991231T000000Z",
+        ] {
+            let err = parse_ical(&event(rule), "room-1", "alon", "UTC").unwrap_err();
+            assert_eq!(err, out_of_range, "{}", rule);
+        }
+        for duration in ["P9999999999999W", "PT9223372036854775807S", "P100000000D"] {
+            let ical = format!(
+                "BEGIN:VEVENT\nDTSTART:20230131T090000Z\nDURATION:{}\nEND:VEVENT\n",
+                duration
+            );
+            assert!(
+                parse_ical(&ical, "room-1", "alon", "UTC").is_err(),
+                "{}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn floating_times_should_be_in_resource_zone() {
+        let ical = "BEGIN:VEVENT\n\
+                    DTSTART:20230324T100000\n\
+                    DTEND:20230324T110000\n\
+                    END:VEVENT\n\
+                    BEGIN:VEVENT\n\
+                    DTSTART;VALUE=DATE:20230325\n\
+                    END:VEVENT\n";
+        let reservations = parse_ical(ical, "room-1", "alon", "Europe/Berlin").unwrap();
+        assert_eq!(
+            times(&reservations),
+            vec![
+                (
+                    "2023-03-24T09:00:00+00:00".into(),
+                    "2023-03-24T10:00:00+00:00".into()
+                ),
+                // DST starts on 2023-03-26, the whole day is still in UTC+1
+                (
+                    "2023-03-24T23:00:00+00:00".into(),
+                    "2023-03-25T23:00:00+00:00".into()
+                ),
+            ]
+        );
+        assert_eq!(
+            parse_ical(ical, "room-1", "alon", "Mars/Olympus_Mons").unwrap_err(),
+            Error::InvalidTimezone("Mars/Olympus_Mons".into())
+        );
+    }
+
+    #[test]
+    fn times_in_dst_gap_should_be_shifted_forward() {
+        // 02:30 doesn't exist on 2023-03-26 in Berlin, it's 03:30 CEST
+        let ical = "BEGIN:VEVENT\n\
+                    DTSTART;TZID=Europe/Berlin:20230326T023000\n\
+                    DTEND;TZID=Europe/Berlin:20230326T040000\n\
+                    END:VEVENT\n";
+        let reservations = parse_ical(ical, "room-1", "alon", "UTC").unwrap();
+        assert_eq!(
+            times(&reservations),
+            vec![(
+                "2023-03-26T01:30:00+00:00".into(),
+                "2023-03-26T02:00:00+00:00".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn duration_should_parse() {
+        assert_eq!(parse_duration("P1W").unwrap(), Duration::weeks(1));
+        assert_eq!(
+            parse_duration("P1DT2H30M").unwrap(),
+            Duration::days(1) + Duration::hours(2) + Duration::minutes(30)
+        );
+        assert!(parse_duration("PT1D").is_err());
+    }
+}
@@ -1,11 +1,17 @@
 mod config;
+mod context;
 mod error;
+mod ical;
 mod pb;
+pub mod serde_utils;
 mod types;
 
 pub use config::*;
+pub use context::*;
 pub use error::*;
+pub use ical::*;
 pub use pb::*;
+pub use types::{local_day_range, parse_timezone, DEFAULT_TIMEZONE};
 
 use chrono::{DateTime, NaiveDateTime, Utc};
 use prost_types::Timestamp;
@@ -45,16 +51,27 @@ pub enum SqlxReservationStatus {
     Blocked,
 }
 
-pub fn convert_to_utc_time(ts: Option<&Timestamp>) -> DateTime<Utc> {
-    DateTime::<Utc>::from_utc(
-        {
-            let secs = ts.unwrap().seconds;
-            let nsecs = ts.unwrap().nanos as _;
-            let datetime = NaiveDateTime::from_timestamp_opt(secs, nsecs);
-            datetime.unwrap()
-        },
-        Utc,
-    )
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "reservation_event", rename_all = "lowercase")]
+pub enum SqlxReservationEventType {
+    Unknown,
+    Create,
+    Update,
+    Delete,
+}
+
+/// the timestamp of the given field with the full precision, InvalidTime if it's not set
+/// or out of the range, so the client input never panics
+pub fn convert_to_utc_time(ts: Option<&Timestamp>, field: &str) -> Result<DateTime<Utc>, Error> {
+    let ts = ts.ok_or_else(|| Error::invalid_time(field, "missing"))?;
+    let nanos = u32::try_from(ts.nanos)
+        .ok()
+        .filter(|nanos| *nanos < 1_000_000_000)
+        .ok_or_else(|| Error::invalid_time(field, format!("nanos {} out of range", ts.nanos)))?;
+    let datetime = NaiveDateTime::from_timestamp_opt(ts.seconds, nanos).ok_or_else(|| {
+        Error::invalid_time(field, format!("seconds {} out of range", ts.seconds))
+    })?;
+    Ok(DateTime::from_utc(datetime, Utc))
 }
 
 pub fn convert_to_timestamp(dt: DateTime<Utc>) -> Timestamp {
@@ -2,3 +2,12 @@
 mod reservation;
 
 pub use reservation::*;
+
+/// messages attached to the error statuses as details
+#[allow(clippy::all)]
+pub mod details {
+    include!("reservation.details.rs");
+}
+
+/// encoded file descriptor set of reservation.proto, used by the reflection service
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/reservation.bin"));
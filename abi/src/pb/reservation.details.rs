@@ -0,0 +1,45 @@
+/// timespan of a resource, [start, end) unless the flags say otherwise
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReservationWindow {
+    #[prost(string, tag = "1")]
+    pub resource_id: ::prost::alloc::string::String,
+    /// absent if unbounded
+    #[prost(message, optional, tag = "2")]
+    pub start: ::core::option::Option<::prost_types::Timestamp>,
+    /// absent if unbounded
+    #[prost(message, optional, tag = "3")]
+    pub end: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(bool, tag = "4")]
+    pub start_exclusive: bool,
+    #[prost(bool, tag = "5")]
+    pub end_inclusive: bool,
+}
+/// attached to the FAILED_PRECONDITION status when a reservation conflicts with an existing one
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReservationConflict {
+    /// the timespan to reserve
+    #[prost(message, optional, tag = "1")]
+    pub new: ::core::option::Option<ReservationWindow>,
+    /// the timespan already reserved, as reported by the database
+    #[prost(message, optional, tag = "2")]
+    pub old: ::core::option::Option<ReservationWindow>,
+    /// all the reservations overlapping the timespan to reserve, ordered by start
+    #[prost(message, repeated, tag = "3")]
+    pub existing: ::prost::alloc::vec::Vec<ConflictingReservation>,
+}
+/// an existing reservation in the way
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConflictingReservation {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    /// empty if the caller is not allowed to see the owner
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "super::ReservationStatus", tag = "3")]
+    pub status: i32,
+    #[prost(message, optional, tag = "4")]
+    pub window: ::core::option::Option<ReservationWindow>,
+}
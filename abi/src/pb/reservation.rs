@@ -1,5 +1,6 @@
 /// Core reservation object. Contains all the information for a reservation
-/// if ListenResponse event is DELETE, only id will be populated
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Reservation {
@@ -11,19 +12,37 @@ pub struct Reservation {
     pub user_id: ::prost::alloc::string::String,
     /// reservation status, used for different purpose
     #[prost(enumeration = "ReservationStatus", tag = "3")]
+    #[serde(with = "crate::serde_utils::reservation_status")]
     pub status: i32,
     /// resource id for the reservation
     #[prost(string, tag = "4")]
     pub resource_id: ::prost::alloc::string::String,
     /// start time for the reservation
     #[prost(message, optional, tag = "5")]
+    #[serde(with = "crate::serde_utils::timestamp")]
     pub start: ::core::option::Option<::prost_types::Timestamp>,
-    /// end time for the reservation
+    /// end time for the reservation, absent for an open-ended one, e.g. a permanent desk assignment
     #[prost(message, optional, tag = "6")]
+    #[serde(with = "crate::serde_utils::timestamp")]
     pub end: ::core::option::Option<::prost_types::Timestamp>,
     /// extra note
     #[prost(string, tag = "7")]
     pub note: ::prost::alloc::string::String,
+    /// the timespan is [start, end) by default, these flip the bounds
+    #[prost(bool, tag = "8")]
+    pub start_exclusive: bool,
+    #[prost(bool, tag = "9")]
+    pub end_inclusive: bool,
+    /// IANA time zone of the reservation, e.g. America/Los_Angeles. If empty, use the zone of the resource
+    #[prost(string, tag = "10")]
+    pub timezone: ::prost::alloc::string::String,
+    /// check-in and check-out dates in YYYY-MM-DD for the date-based bookings, start and end
+    /// are set from the check-in and check-out times of the resource in the timezone
+    #[prost(string, tag = "11")]
+    pub start_date: ::prost::alloc::string::String,
+    /// if empty, the day after the start date
+    #[prost(string, tag = "12")]
+    pub end_date: ::prost::alloc::string::String,
 }
 /// To make a reservation, send a ReserveRequest with Reservation object (id should be empty)
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -122,6 +141,12 @@ pub struct ReservationQuery {
     /// sort direction
     #[prost(bool, tag = "6")]
     pub desc: bool,
+    /// local day in YYYY-MM-DD, the query covers the whole day in the timezone, start and end should not be set
+    #[prost(string, tag = "7")]
+    pub local_day: ::prost::alloc::string::String,
+    /// IANA time zone of the local day. If empty, use the zone of the resource
+    #[prost(string, tag = "8")]
+    pub timezone: ::prost::alloc::string::String,
 }
 /// To query reservations, send a QueryRequest
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -130,6 +155,60 @@ pub struct QueryRequest {
     #[prost(message, optional, tag = "1")]
     pub query: ::core::option::Option<ReservationQuery>,
 }
+/// To export reservations as an iCalendar (.ics) document, send an ExportRequest
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportRequest {
+    #[prost(message, optional, tag = "1")]
+    pub query: ::core::option::Option<ReservationQuery>,
+}
+/// A chunk of the iCalendar document, concatenate all the chunks to get the document
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportResponse {
+    #[prost(string, tag = "1")]
+    pub data: ::prost::alloc::string::String,
+}
+/// To import reservations from an iCalendar (.ics) document, send an ImportRequest
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportRequest {
+    /// iCalendar document, recurring events are expanded
+    #[prost(string, tag = "1")]
+    pub data: ::prost::alloc::string::String,
+    /// resource id for the imported reservations
+    #[prost(string, tag = "2")]
+    pub resource_id: ::prost::alloc::string::String,
+    /// user id for the imported reservations. If empty, use X-RESERVATION-USER-ID of each event
+    #[prost(string, tag = "3")]
+    pub user_id: ::prost::alloc::string::String,
+    /// only report the conflicts, nothing is saved
+    #[prost(bool, tag = "4")]
+    pub dry_run: bool,
+    /// save all the reservations, or nothing if any of them conflicts
+    #[prost(bool, tag = "5")]
+    pub atomic: bool,
+}
+/// A reservation which could not be imported
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportConflict {
+    #[prost(message, optional, tag = "1")]
+    pub reservation: ::core::option::Option<Reservation>,
+    /// why the reservation is rejected
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
+/// Imported reservations and the conflicts will be returned in ImportResponse
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportResponse {
+    /// saved reservations, or the ones would be saved in dry run
+    #[prost(message, repeated, tag = "1")]
+    pub reservations: ::prost::alloc::vec::Vec<Reservation>,
+    #[prost(message, repeated, tag = "2")]
+    pub conflicts: ::prost::alloc::vec::Vec<ImportConflict>,
+}
 /// query reservations, order by reservation id
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -174,16 +253,147 @@ pub struct FilterResponse {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ListenRequest {}
-/// Server will send ListenResponse to client in streaming response
+/// A change recorded for a reservation, with the snapshots before and after the change
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct ListenResponse {
+pub struct ReservationEvent {
+    /// unique id for the event, events are ordered by id
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    /// id of the changed reservation
+    #[prost(int64, tag = "2")]
+    pub reservation_id: i64,
     /// event type
-    #[prost(enumeration = "ReservationEvent", tag = "1")]
-    pub event: i32,
-    /// id for updated reservation
-    #[prost(message, optional, tag = "2")]
-    pub reservation: ::core::option::Option<Reservation>,
+    #[prost(enumeration = "ReservationEventType", tag = "3")]
+    #[serde(with = "crate::serde_utils::reservation_event_type")]
+    pub event_type: i32,
+    /// reservation before the change, empty for a created event
+    #[prost(message, optional, tag = "4")]
+    pub old: ::core::option::Option<Reservation>,
+    /// reservation after the change, empty for a deleted event
+    #[prost(message, optional, tag = "5")]
+    pub new: ::core::option::Option<Reservation>,
+    /// time when the change happened
+    #[prost(message, optional, tag = "6")]
+    #[serde(with = "crate::serde_utils::timestamp")]
+    pub created_at: ::core::option::Option<::prost_types::Timestamp>,
+    /// id of the caller who made the change, empty if unknown
+    #[prost(string, tag = "7")]
+    pub actor_id: ::prost::alloc::string::String,
+    /// id of the request which made the change, empty if unknown
+    #[prost(string, tag = "8")]
+    pub request_id: ::prost::alloc::string::String,
+}
+/// To get the change history of a reservation, send a HistoryRequest
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryRequest {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+}
+/// Events of the reservation will be returned in HistoryResponse, order by event id
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::prost::alloc::vec::Vec<ReservationEvent>,
+}
+/// query reservation events, order by event id
+#[derive(derive_builder::Builder)]
+#[builder(setter(into, strip_option), default)]
+#[builder(build_fn(name = "private_build"))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReservationEventFilter {
+    /// reservation id for the event query. If 0, query all reservations
+    #[prost(int64, tag = "1")]
+    pub reservation_id: i64,
+    /// resource id for the event query. If empty, query all resources
+    #[prost(string, tag = "2")]
+    pub resource_id: ::prost::alloc::string::String,
+    /// user id for the event query. If empty, query all users
+    #[prost(string, tag = "3")]
+    pub user_id: ::prost::alloc::string::String,
+    /// use event type to filter result. If UNKNOWN, return all events
+    #[prost(enumeration = "ReservationEventType", tag = "4")]
+    pub event_type: i32,
+    #[prost(int64, optional, tag = "5")]
+    #[builder(setter(into, strip_option), default)]
+    pub cursor: ::core::option::Option<i64>,
+    /// page size for the query
+    #[prost(int64, tag = "6")]
+    #[builder(setter(into), default = "10")]
+    pub page_size: i64,
+    /// sort direction
+    #[prost(bool, tag = "7")]
+    pub desc: bool,
+}
+/// To filter reservation events, send an EventsRequest
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EventsRequest {
+    #[prost(message, optional, tag = "1")]
+    pub filter: ::core::option::Option<ReservationEventFilter>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EventsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::prost::alloc::vec::Vec<ReservationEvent>,
+}
+/// Webhook subscription, reservation events will be posted to the url as JSON
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Webhook {
+    /// unique id for the webhook, if put into SubscribeRequest, id should be empty
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    /// url to receive the events
+    #[prost(string, tag = "2")]
+    pub url: ::prost::alloc::string::String,
+    /// secret to sign the payload with HMAC-SHA256, not returned when listing webhooks
+    #[prost(string, tag = "3")]
+    pub secret: ::prost::alloc::string::String,
+}
+/// To subscribe reservation events, send a SubscribeRequest with Webhook object (id should be empty)
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeRequest {
+    #[prost(message, optional, tag = "1")]
+    pub webhook: ::core::option::Option<Webhook>,
+}
+/// Created webhook will be returned in SubscribeResponse
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub webhook: ::core::option::Option<Webhook>,
+}
+/// To unsubscribe reservation events, send an UnsubscribeRequest
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UnsubscribeRequest {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+}
+/// Removed webhook will be returned in UnsubscribeResponse
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UnsubscribeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub webhook: ::core::option::Option<Webhook>,
+}
+/// To list all webhooks, send a ListWebhooksRequest
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListWebhooksRequest {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListWebhooksResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub webhooks: ::prost::alloc::vec::Vec<Webhook>,
 }
 /// reservation status for a given time period
 #[derive(
@@ -213,23 +423,23 @@ impl ReservationStatus {
 /// when reservation is updated, record the reservation event type
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
-pub enum ReservationEvent {
+pub enum ReservationEventType {
     Unknown = 0,
     Created = 1,
     Updated = 2,
     Deleted = 3,
 }
-impl ReservationEvent {
+impl ReservationEventType {
     /// String value of the enum field names used in the ProtoBuf definition.
     ///
     /// The values are not transformed in any way and thus are considered stable
     /// (if the ProtoBuf definition does not change) and safe for programmatic use.
     pub fn as_str_name(&self) -> &'static str {
         match self {
-            ReservationEvent::Unknown => "RESERVATION_EVENT_UNKNOWN",
-            ReservationEvent::Created => "RESERVATION_EVENT_CREATED",
-            ReservationEvent::Updated => "RESERVATION_EVENT_UPDATED",
-            ReservationEvent::Deleted => "RESERVATION_EVENT_DELETED",
+            ReservationEventType::Unknown => "RESERVATION_EVENT_TYPE_UNKNOWN",
+            ReservationEventType::Created => "RESERVATION_EVENT_TYPE_CREATED",
+            ReservationEventType::Updated => "RESERVATION_EVENT_TYPE_UPDATED",
+            ReservationEventType::Deleted => "RESERVATION_EVENT_TYPE_DELETED",
         }
     }
 }
@@ -400,6 +610,41 @@ pub mod reservation_service_client {
                 .server_streaming(request.into_request(), path, codec)
                 .await
         }
+        /// export reservations of a user or resource as an iCalendar (.ics) document
+        pub async fn export(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportRequest>,
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::ExportResponse>>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/export");
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
+        /// import reservations of a resource from an iCalendar (.ics) document
+        pub async fn import(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportRequest>,
+        ) -> Result<tonic::Response<super::ImportResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/import");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         /// filter reservations, order by reservation id
         pub async fn filter(
             &mut self,
@@ -416,11 +661,11 @@ pub mod reservation_service_client {
                 http::uri::PathAndQuery::from_static("/reservation.ReservationService/filter");
             self.inner.unary(request.into_request(), path, codec).await
         }
-        /// another system could monitor reservation events: added/confirmed/cancelled
+        /// another system could monitor reservation events: added/confirmed/updated/cancelled
         pub async fn listen(
             &mut self,
             request: impl tonic::IntoRequest<super::ListenRequest>,
-        ) -> Result<tonic::Response<tonic::codec::Streaming<super::Reservation>>, tonic::Status>
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::ReservationEvent>>, tonic::Status>
         {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::new(
@@ -435,6 +680,156 @@ pub mod reservation_service_client {
                 .server_streaming(request.into_request(), path, codec)
                 .await
         }
+        /// get the change history of a reservation
+        pub async fn history(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HistoryRequest>,
+        ) -> Result<tonic::Response<super::HistoryResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/history");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// filter reservation events of all reservations, order by event id (admin)
+        pub async fn events(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EventsRequest>,
+        ) -> Result<tonic::Response<super::EventsResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/events");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
+/// Generated client implementations.
+pub mod webhook_service_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::http::Uri;
+    use tonic::codegen::*;
+    /// Webhook service, manage the subscribers which receive reservation events by HTTP callbacks
+    #[derive(Debug, Clone)]
+    pub struct WebhookServiceClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl WebhookServiceClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> WebhookServiceClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> WebhookServiceClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
+        {
+            WebhookServiceClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// register a webhook
+        pub async fn subscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SubscribeRequest>,
+        ) -> Result<tonic::Response<super::SubscribeResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.WebhookService/subscribe");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// remove a webhook
+        pub async fn unsubscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UnsubscribeRequest>,
+        ) -> Result<tonic::Response<super::UnsubscribeResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.WebhookService/unsubscribe");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// list all webhooks
+        pub async fn list(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListWebhooksRequest>,
+        ) -> Result<tonic::Response<super::ListWebhooksResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/reservation.WebhookService/list");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -478,20 +873,44 @@ pub mod reservation_service_server {
             &self,
             request: tonic::Request<super::QueryRequest>,
         ) -> Result<tonic::Response<Self::queryStream>, tonic::Status>;
+        /// Server streaming response type for the export method.
+        type exportStream: futures_core::Stream<Item = Result<super::ExportResponse, tonic::Status>>
+            + Send
+            + 'static;
+        /// export reservations of a user or resource as an iCalendar (.ics) document
+        async fn export(
+            &self,
+            request: tonic::Request<super::ExportRequest>,
+        ) -> Result<tonic::Response<Self::exportStream>, tonic::Status>;
+        /// import reservations of a resource from an iCalendar (.ics) document
+        async fn import(
+            &self,
+            request: tonic::Request<super::ImportRequest>,
+        ) -> Result<tonic::Response<super::ImportResponse>, tonic::Status>;
         /// filter reservations, order by reservation id
         async fn filter(
             &self,
             request: tonic::Request<super::FilterRequest>,
         ) -> Result<tonic::Response<super::FilterResponse>, tonic::Status>;
         /// Server streaming response type for the listen method.
-        type listenStream: futures_core::Stream<Item = Result<super::Reservation, tonic::Status>>
+        type listenStream: futures_core::Stream<Item = Result<super::ReservationEvent, tonic::Status>>
             + Send
             + 'static;
-        /// another system could monitor reservation events: added/confirmed/cancelled
+        /// another system could monitor reservation events: added/confirmed/updated/cancelled
         async fn listen(
             &self,
             request: tonic::Request<super::ListenRequest>,
         ) -> Result<tonic::Response<Self::listenStream>, tonic::Status>;
+        /// get the change history of a reservation
+        async fn history(
+            &self,
+            request: tonic::Request<super::HistoryRequest>,
+        ) -> Result<tonic::Response<super::HistoryResponse>, tonic::Status>;
+        /// filter reservation events of all reservations, order by event id (admin)
+        async fn events(
+            &self,
+            request: tonic::Request<super::EventsRequest>,
+        ) -> Result<tonic::Response<super::EventsResponse>, tonic::Status>;
     }
     /// Reservation service
     #[derive(Debug)]
@@ -737,6 +1156,73 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/export" => {
+                    #[allow(non_camel_case_types)]
+                    struct exportSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService>
+                        tonic::server::ServerStreamingService<super::ExportRequest>
+                        for exportSvc<T>
+                    {
+                        type Response = super::ExportResponse;
+                        type ResponseStream = T::exportStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).export(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = exportSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.ReservationService/import" => {
+                    #[allow(non_camel_case_types)]
+                    struct importSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::ImportRequest> for importSvc<T> {
+                        type Response = super::ImportResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ImportRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).import(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = importSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/reservation.ReservationService/filter" => {
                     #[allow(non_camel_case_types)]
                     struct filterSvc<T: ReservationService>(pub Arc<T>);
@@ -775,7 +1261,7 @@ pub mod reservation_service_server {
                         tonic::server::ServerStreamingService<super::ListenRequest>
                         for listenSvc<T>
                     {
-                        type Response = super::Reservation;
+                        type Response = super::ReservationEvent;
                         type ResponseStream = T::listenStream;
                         type Future =
                             BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
@@ -804,6 +1290,68 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/history" => {
+                    #[allow(non_camel_case_types)]
+                    struct historySvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::HistoryRequest> for historySvc<T> {
+                        type Response = super::HistoryResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HistoryRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).history(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = historySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.ReservationService/events" => {
+                    #[allow(non_camel_case_types)]
+                    struct eventsSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::EventsRequest> for eventsSvc<T> {
+                        type Response = super::EventsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EventsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).events(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = eventsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -839,3 +1387,210 @@ pub mod reservation_service_server {
         const NAME: &'static str = "reservation.ReservationService";
     }
 }
+/// Generated server implementations.
+pub mod webhook_service_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with WebhookServiceServer.
+    #[async_trait]
+    pub trait WebhookService: Send + Sync + 'static {
+        /// register a webhook
+        async fn subscribe(
+            &self,
+            request: tonic::Request<super::SubscribeRequest>,
+        ) -> Result<tonic::Response<super::SubscribeResponse>, tonic::Status>;
+        /// remove a webhook
+        async fn unsubscribe(
+            &self,
+            request: tonic::Request<super::UnsubscribeRequest>,
+        ) -> Result<tonic::Response<super::UnsubscribeResponse>, tonic::Status>;
+        /// list all webhooks
+        async fn list(
+            &self,
+            request: tonic::Request<super::ListWebhooksRequest>,
+        ) -> Result<tonic::Response<super::ListWebhooksResponse>, tonic::Status>;
+    }
+    /// Webhook service, manage the subscribers which receive reservation events by HTTP callbacks
+    #[derive(Debug)]
+    pub struct WebhookServiceServer<T: WebhookService> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: WebhookService> WebhookServiceServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+            }
+        }
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for WebhookServiceServer<T>
+    where
+        T: WebhookService,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/reservation.WebhookService/subscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct subscribeSvc<T: WebhookService>(pub Arc<T>);
+                    impl<T: WebhookService> tonic::server::UnaryService<super::SubscribeRequest> for subscribeSvc<T> {
+                        type Response = super::SubscribeResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SubscribeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).subscribe(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = subscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.WebhookService/unsubscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct unsubscribeSvc<T: WebhookService>(pub Arc<T>);
+                    impl<T: WebhookService> tonic::server::UnaryService<super::UnsubscribeRequest>
+                        for unsubscribeSvc<T>
+                    {
+                        type Response = super::UnsubscribeResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UnsubscribeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).unsubscribe(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = unsubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.WebhookService/list" => {
+                    #[allow(non_camel_case_types)]
+                    struct listSvc<T: WebhookService>(pub Arc<T>);
+                    impl<T: WebhookService> tonic::server::UnaryService<super::ListWebhooksRequest> for listSvc<T> {
+                        type Response = super::ListWebhooksResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListWebhooksRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).list(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = listSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: WebhookService> Clone for WebhookServiceServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+            }
+        }
+    }
+    impl<T: WebhookService> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: WebhookService> tonic::server::NamedService for WebhookServiceServer<T> {
+        const NAME: &'static str = "reservation.WebhookService";
+    }
+}
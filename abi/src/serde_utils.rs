@@ -0,0 +1,114 @@
+//! serde helpers for the generated protobuf types, so that they are readable in JSON
+
+/// serialize `Option<Timestamp>` as a RFC3339 string
+pub mod timestamp {
+    use chrono::{DateTime, Utc};
+    use prost_types::Timestamp;
+    use serde::{ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{convert_to_timestamp, convert_to_utc_time};
+
+    pub fn serialize<S: Serializer>(ts: &Option<Timestamp>, s: S) -> Result<S::Ok, S::Error> {
+        ts.as_ref()
+            .map(|ts| convert_to_utc_time(Some(ts), "timestamp").map(|t| t.to_rfc3339()))
+            .transpose()
+            .map_err(S::Error::custom)?
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Timestamp>, D::Error> {
+        let dt: Option<DateTime<Utc>> = Option::deserialize(d)?;
+        Ok(dt.map(convert_to_timestamp))
+    }
+}
+
+/// serialize `NaiveTime` as a HH:MM string, e.g. the check-in time of a resource
+pub mod local_time {
+    use chrono::NaiveTime;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%H:%M";
+
+    pub fn serialize<S: Serializer>(time: &NaiveTime, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&time.format(FORMAT).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveTime, D::Error> {
+        let time = String::deserialize(d)?;
+        NaiveTime::parse_from_str(&time, FORMAT)
+            .map_err(|_| D::Error::custom(format!("invalid time: {}, should be HH:MM", time)))
+    }
+}
+
+/// serialize the `ReservationStatus` field as its lowercase name
+pub mod reservation_status {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::ReservationStatus;
+
+    pub fn serialize<S: Serializer>(status: &i32, s: S) -> Result<S::Ok, S::Error> {
+        let status = ReservationStatus::from_i32(*status).unwrap_or(ReservationStatus::Unknown);
+        s.serialize_str(&status.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<i32, D::Error> {
+        let status = String::deserialize(d)?;
+        let status = match status.as_str() {
+            "unknown" => ReservationStatus::Unknown,
+            "pending" => ReservationStatus::Pending,
+            "confirmed" => ReservationStatus::Confirmed,
+            "blocked" => ReservationStatus::Blocked,
+            v => return Err(D::Error::custom(format!("invalid status: {}", v))),
+        };
+        Ok(status as i32)
+    }
+}
+
+/// serialize the `ReservationEventType` field as its lowercase name
+pub mod reservation_event_type {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::ReservationEventType;
+
+    pub fn serialize<S: Serializer>(event_type: &i32, s: S) -> Result<S::Ok, S::Error> {
+        let event_type =
+            ReservationEventType::from_i32(*event_type).unwrap_or(ReservationEventType::Unknown);
+        s.serialize_str(&event_type.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<i32, D::Error> {
+        let event_type = String::deserialize(d)?;
+        let event_type = match event_type.as_str() {
+            "unknown" => ReservationEventType::Unknown,
+            "create" => ReservationEventType::Created,
+            "update" => ReservationEventType::Updated,
+            "delete" => ReservationEventType::Deleted,
+            v => return Err(D::Error::custom(format!("invalid event type: {}", v))),
+        };
+        Ok(event_type as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Reservation, ReservationStatus};
+
+    #[test]
+    fn reservation_should_serialize_to_readable_json() {
+        let reservation = Reservation::new(
+            "alon",
+            "ocean-view-room-713",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "note",
+            ReservationStatus::Confirmed,
+        );
+        let json = serde_json::to_string(&reservation).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":0,"user_id":"alon","status":"confirmed","resource_id":"ocean-view-room-713","start":"2022-12-26T22:00:00+00:00","end":"2022-12-30T19:00:00+00:00","note":"note","start_exclusive":false,"end_inclusive":false,"timezone":"","start_date":"","end_date":""}"#
+        );
+        let result: Reservation = serde_json::from_str(&json).unwrap();
+        assert_eq!(result, reservation);
+    }
+}
@@ -1,34 +1,94 @@
 use std::ops::Bound;
 
-use chrono::{DateTime, Utc};
+use chrono::{
+    DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, Offset, SubsecRound, TimeZone, Utc,
+};
+use chrono_tz::Tz;
 use prost_types::Timestamp;
 use sqlx::postgres::types::PgRange;
 
 use crate::{convert_to_utc_time, Error};
 
 mod reservation;
+mod reservation_event;
+mod reservation_event_filter;
 mod reservation_filter;
 mod reservation_query;
 mod reservation_status;
+mod webhook;
 
+/// zone of the reservations and the resources without one
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// the start is required, the end is optional for the open-ended timespans. The times are
+/// compared in microseconds as postgres stores them, so the range is never empty once saved
 pub fn validate_range(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Result<(), Error> {
-    if start.is_none() || end.is_none() {
-        return Err(Error::InvalidTime);
-    }
-    let start = start.as_ref().unwrap();
-    let end = end.as_ref().unwrap();
-    if start.seconds >= end.seconds {
-        return Err(Error::InvalidTime);
+    let start = convert_to_utc_time(start, "start")?.trunc_subsecs(6);
+    if end.is_some() && convert_to_utc_time(end, "end")?.trunc_subsecs(6) <= start {
+        return Err(Error::invalid_time("end", "not after the start"));
     }
     Ok(())
 }
 
-pub fn get_timespan(start: Option<&Timestamp>, end: Option<&Timestamp>) -> PgRange<DateTime<Utc>> {
-    let start = convert_to_utc_time(start);
-    let end = convert_to_utc_time(end);
+/// the timespan with the given bounds, a missing time is unbounded
+pub fn get_timespan(
+    start: Option<&Timestamp>,
+    end: Option<&Timestamp>,
+    start_exclusive: bool,
+    end_inclusive: bool,
+) -> Result<PgRange<DateTime<Utc>>, Error> {
+    let bound = |ts: Option<&Timestamp>, field, inclusive| match ts {
+        Some(_) if inclusive => convert_to_utc_time(ts, field).map(Bound::Included),
+        Some(_) => convert_to_utc_time(ts, field).map(Bound::Excluded),
+        None => Ok(Bound::Unbounded),
+    };
+    Ok(PgRange {
+        start: bound(start, "start", !start_exclusive)?,
+        end: bound(end, "end", end_inclusive)?,
+    })
+}
+
+/// an IANA time zone, e.g. America/Los_Angeles
+pub fn parse_timezone(name: &str) -> Result<Tz, Error> {
+    name.parse()
+        .map_err(|_| Error::InvalidTimezone(name.to_string()))
+}
+
+/// a date in YYYY-MM-DD
+pub fn parse_date(s: &str, field: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| Error::invalid_time(field, format!("{} is not YYYY-MM-DD", s)))
+}
+
+/// the UTC range [start, end) of the day in the zone, a day could be 23 or 25 hours
+/// around the DST transitions
+pub fn local_day_range(day: &str, tz: Tz) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let out_of_range = || Error::invalid_time("local_day", "out of range");
+    let day = parse_date(day, "local_day")?;
+    let next = day.succ_opt().ok_or_else(out_of_range)?;
+    let start_of_day = |day: NaiveDate| {
+        day.and_hms_opt(0, 0, 0)
+            .and_then(|midnight| local_to_utc(midnight, tz))
+            .ok_or_else(out_of_range)
+    };
+    Ok((start_of_day(day)?, start_of_day(next)?))
+}
 
-    PgRange {
-        start: Bound::Included(start),
-        end: Bound::Excluded(end),
+/// the instant of the wall clock time in the zone, None if out of range. A time repeated by
+/// DST is the earlier one, a time skipped by DST is moved forward by the gap as postgres does,
+/// so a skipped midnight is the transition
+pub fn local_to_utc(local: NaiveDateTime, tz: Tz) -> Option<DateTime<Utc>> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(t) => Some(t.with_timezone(&Utc)),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest.with_timezone(&Utc)),
+        // in the gap the offset before the transition is used. The offset of a day ago is
+        // taken, zones don't change twice in a day
+        LocalResult::None => {
+            let before = local.checked_sub_signed(Duration::days(1))?;
+            let offset = tz.offset_from_utc_datetime(&before).fix();
+            let utc =
+                local.checked_sub_signed(Duration::seconds(offset.local_minus_utc() as i64))?;
+            Some(DateTime::from_utc(utc, Utc))
+        }
     }
 }
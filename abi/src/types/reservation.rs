@@ -1,14 +1,16 @@
 use std::ops::Bound;
 
 use crate::error::Error;
-use crate::{convert_to_timestamp, SqlxReservationStatus, Validator};
+use crate::{convert_to_timestamp, Normalizer, SqlxReservationStatus, Validator};
 use crate::{Reservation, ReservationStatus};
-use chrono::{DateTime, FixedOffset, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
 use sqlx::postgres::types::PgRange;
 use sqlx::postgres::PgRow;
 use sqlx::{FromRow, Row};
 
-use super::{get_timespan, validate_range};
+use super::{
+    get_timespan, local_to_utc, parse_date, parse_timezone, validate_range, DEFAULT_TIMEZONE,
+};
 
 impl Reservation {
     pub fn new(
@@ -27,11 +29,115 @@ impl Reservation {
             end: Some(convert_to_timestamp(end.with_timezone(&Utc))),
             note: note.into(),
             status: status as i32,
+            start_exclusive: false,
+            end_inclusive: false,
+            timezone: String::new(),
+            start_date: String::new(),
+            end_date: String::new(),
         }
     }
 
-    pub fn get_timespan(&self) -> PgRange<DateTime<Utc>> {
-        get_timespan(self.start.as_ref(), self.end.as_ref())
+    /// a date-based booking, the times are set by `resolve_dates` with the check-in and
+    /// check-out times of the resource. The end date is the day after if not set
+    pub fn new_for_dates(
+        uid: impl Into<String>,
+        rid: impl Into<String>,
+        start_date: NaiveDate,
+        end_date: Option<NaiveDate>,
+        note: impl Into<String>,
+        status: ReservationStatus,
+    ) -> Self {
+        Self {
+            id: 0,
+            user_id: uid.into(),
+            resource_id: rid.into(),
+            start: None,
+            end: None,
+            note: note.into(),
+            status: status as i32,
+            start_exclusive: false,
+            end_inclusive: false,
+            timezone: String::new(),
+            start_date: start_date.format("%Y-%m-%d").to_string(),
+            end_date: end_date
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// set the IANA time zone, the zone of the resource is used if not set
+    pub fn with_timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = timezone.into();
+        self
+    }
+
+    /// a reservation without an end, e.g. a permanent desk assignment
+    pub fn new_open_ended(
+        uid: impl Into<String>,
+        rid: impl Into<String>,
+        start: DateTime<FixedOffset>,
+        note: impl Into<String>,
+        status: ReservationStatus,
+    ) -> Self {
+        Self {
+            end: None,
+            ..Self::new(uid, rid, start, start, note, status)
+        }
+    }
+
+    /// set the start and end from the dates at the check-in and check-out times in the
+    /// timezone, the end date is the day after the start date if not set. Nothing is
+    /// changed if it's not a date-based booking
+    pub fn resolve_dates(
+        &mut self,
+        check_in: NaiveTime,
+        check_out: NaiveTime,
+    ) -> Result<(), Error> {
+        if self.start_date.is_empty() {
+            return Ok(());
+        }
+        if self.start.is_some() || self.end.is_some() {
+            return Err(Error::invalid_time(
+                "start_date",
+                "should not be set with start or end",
+            ));
+        }
+        let start_date = parse_date(&self.start_date, "start_date")?;
+        let end_date = match self.end_date.as_str() {
+            "" => start_date
+                .succ_opt()
+                .ok_or_else(|| Error::invalid_time("end_date", "out of range"))?,
+            end_date => parse_date(end_date, "end_date")?,
+        };
+        let tz = match self.timezone.as_str() {
+            "" => parse_timezone(DEFAULT_TIMEZONE)?,
+            timezone => parse_timezone(timezone)?,
+        };
+        let at = |date: NaiveDate, time, field| {
+            local_to_utc(date.and_time(time), tz)
+                .ok_or_else(|| Error::invalid_time(field, "out of range"))
+        };
+        let start = at(start_date, check_in, "start_date")?;
+        let end = at(end_date, check_out, "end_date")?;
+        if end <= start {
+            return Err(Error::invalid_time(
+                "end_date",
+                "check-out is not after check-in",
+            ));
+        }
+        self.start = Some(convert_to_timestamp(start));
+        self.end = Some(convert_to_timestamp(end));
+        self.end_date = end_date.format("%Y-%m-%d").to_string();
+        Ok(())
+    }
+
+    pub fn get_timespan(&self) -> Result<PgRange<DateTime<Utc>>, Error> {
+        get_timespan(
+            self.start.as_ref(),
+            self.end.as_ref(),
+            self.start_exclusive,
+            self.end_inclusive,
+        )
     }
 }
 
@@ -44,48 +150,261 @@ impl Validator for Reservation {
             return Err(Error::InvalidResourceId(self.resource_id.clone()));
         }
         validate_range(self.start.as_ref(), self.end.as_ref())?;
+        if !self.timezone.is_empty() {
+            parse_timezone(&self.timezone)?;
+        }
+        match (self.start_date.as_str(), self.end_date.as_str()) {
+            ("", "") => {}
+            ("", _) => return Err(Error::invalid_time("start_date", "missing")),
+            (_, "") => return Err(Error::invalid_time("end_date", "missing")),
+            (start_date, end_date) => {
+                if parse_date(end_date, "end_date")? < parse_date(start_date, "start_date")? {
+                    return Err(Error::invalid_time("end_date", "before the start_date"));
+                }
+            }
+        }
 
         Ok(())
     }
 }
 
+impl Normalizer for Reservation {
+    fn do_normalize(&mut self) {
+        // the status is optional, new reservations are pending by default
+        if self.status == ReservationStatus::Unknown as i32 {
+            self.status = ReservationStatus::Pending as i32;
+        }
+        if self.timezone.is_empty() {
+            self.timezone = DEFAULT_TIMEZONE.to_string();
+        }
+    }
+}
+
 impl FromRow<'_, PgRow> for Reservation {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
-        let range: PgRange<DateTime<Utc>> = row.get("timespan");
-        let range: NativeRange<DateTime<Utc>> = range.into();
-        assert!(range.start.is_some());
-        assert!(range.end.is_some());
-        let start = range.start.unwrap();
-        let end = range.end.unwrap();
-
-        let status: SqlxReservationStatus = row.get("status");
+        Self::from_prefixed_row(row, "")
+    }
+}
+
+impl Reservation {
+    /// build reservation from the columns named with the given prefix, e.g. "old_id", "old_user_id"
+    pub(crate) fn from_prefixed_row(row: &PgRow, prefix: &str) -> Result<Self, sqlx::Error> {
+        let column = |name: &str| format!("{}{}", prefix, name);
+
+        let range: PgRange<DateTime<Utc>> = row.try_get(column("timespan").as_str())?;
+        let (start, start_inclusive) = split_bound(range.start);
+        let (end, end_inclusive) = split_bound(range.end);
+
+        let status: SqlxReservationStatus = row.try_get(column("status").as_str())?;
+        let note: Option<String> = row.try_get(column("note").as_str())?;
+        // the snapshots of the events before the timezone column don't have it
+        let timezone: Option<String> = row.try_get(column("timezone").as_str())?;
+        let date = |name: &str| -> Result<String, sqlx::Error> {
+            let date: Option<NaiveDate> = row.try_get(column(name).as_str())?;
+            Ok(date
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default())
+        };
         Ok(Self {
-            id: row.get("id"),
-            user_id: row.get("user_id"),
-            resource_id: row.get("resource_id"),
-            start: Some(convert_to_timestamp(start)),
-            end: Some(convert_to_timestamp(end)),
-            note: row.get("note"),
+            id: row.try_get(column("id").as_str())?,
+            user_id: row.try_get(column("user_id").as_str())?,
+            resource_id: row.try_get(column("resource_id").as_str())?,
+            start: start.map(convert_to_timestamp),
+            end: end.map(convert_to_timestamp),
+            note: note.unwrap_or_default(),
             status: ReservationStatus::from(status) as i32,
+            start_exclusive: start.is_some() && !start_inclusive,
+            end_inclusive,
+            timezone: timezone.unwrap_or_else(|| DEFAULT_TIMEZONE.to_string()),
+            start_date: date("start_date")?,
+            end_date: date("end_date")?,
         })
     }
 }
 
-struct NativeRange<T> {
-    start: Option<T>,
-    end: Option<T>,
+/// the time of the bound, None if unbounded, and whether it's inclusive
+fn split_bound<T>(bound: Bound<T>) -> (Option<T>, bool) {
+    match bound {
+        Bound::Included(v) => (Some(v), true),
+        Bound::Excluded(v) => (Some(v), false),
+        Bound::Unbounded => (None, false),
+    }
 }
 
-impl<T> From<PgRange<T>> for NativeRange<T> {
-    fn from(range: PgRange<T>) -> Self {
-        let f = |b: Bound<T>| match b {
-            Bound::Included(v) => Some(v),
-            Bound::Excluded(v) => Some(v),
-            Bound::Unbounded => None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_ended_reservation_should_be_unbounded() {
+        let start: DateTime<FixedOffset> = "2022-12-26T15:00:00-0700".parse().unwrap();
+        let mut rsvp =
+            Reservation::new_open_ended("alon", "desk-42", start, "", ReservationStatus::Pending);
+        assert!(rsvp.validate().is_ok());
+        let timespan = rsvp.get_timespan().unwrap();
+        assert_eq!(timespan.start, Bound::Included(start.with_timezone(&Utc)));
+        assert_eq!(timespan.end, Bound::Unbounded);
+
+        rsvp.start_exclusive = true;
+        assert_eq!(
+            rsvp.get_timespan().unwrap().start,
+            Bound::Excluded(start.with_timezone(&Utc))
+        );
+
+        // the start is still required
+        rsvp.start = None;
+        assert_eq!(
+            rsvp.validate(),
+            Err(Error::invalid_time("start", "missing"))
+        );
+    }
+
+    #[test]
+    fn timezone_should_be_validated_and_defaulted() {
+        let start: DateTime<FixedOffset> = "2022-12-26T15:00:00-0700".parse().unwrap();
+        let end: DateTime<FixedOffset> = "2022-12-26T16:00:00-0700".parse().unwrap();
+        let rsvp = Reservation::new("alon", "room-1", start, end, "", ReservationStatus::Pending);
+
+        let mut normalized = rsvp.clone();
+        normalized.normalize().unwrap();
+        assert_eq!(normalized.timezone, "UTC");
+
+        let mut normalized = rsvp.clone().with_timezone("America/Los_Angeles");
+        normalized.normalize().unwrap();
+        assert_eq!(normalized.timezone, "America/Los_Angeles");
+
+        assert_eq!(
+            rsvp.with_timezone("PST8PDT7").validate(),
+            Err(Error::InvalidTimezone("PST8PDT7".into()))
+        );
+    }
+
+    #[test]
+    fn dates_should_resolve_to_check_in_and_check_out() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let times = |r: &Reservation| {
+            let timespan = r.get_timespan().unwrap();
+            match (timespan.start, timespan.end) {
+                (Bound::Included(start), Bound::Excluded(end)) => {
+                    (start.to_rfc3339(), end.to_rfc3339())
+                }
+                bounds => panic!("unexpected bounds: {:?}", bounds),
+            }
         };
-        Self {
-            start: f(range.start),
-            end: f(range.end),
+
+        // DST ends on 2022-11-06 in Los Angeles
+        let mut rsvp = Reservation::new_for_dates(
+            "alon",
+            "room-1",
+            date("2022-11-05"),
+            Some(date("2022-11-07")),
+            "",
+            ReservationStatus::Pending,
+        )
+        .with_timezone("America/Los_Angeles");
+        rsvp.resolve_dates(time("15:00"), time("11:00")).unwrap();
+        assert!(rsvp.validate().is_ok());
+        assert_eq!(
+            times(&rsvp),
+            (
+                "2022-11-05T22:00:00+00:00".into(),
+                "2022-11-07T19:00:00+00:00".into()
+            )
+        );
+        assert_eq!(
+            (rsvp.start_date.as_str(), rsvp.end_date.as_str()),
+            ("2022-11-05", "2022-11-07")
+        );
+
+        // one night by default, 02:30 is skipped by DST on 2023-03-12 and becomes 03:30
+        let mut rsvp = Reservation::new_for_dates(
+            "alon",
+            "room-1",
+            date("2023-03-12"),
+            None,
+            "",
+            ReservationStatus::Pending,
+        )
+        .with_timezone("America/Los_Angeles");
+        rsvp.resolve_dates(time("02:30"), time("02:00")).unwrap();
+        assert_eq!(rsvp.end_date, "2023-03-13");
+        assert_eq!(
+            times(&rsvp),
+            (
+                "2023-03-12T10:30:00+00:00".into(),
+                "2023-03-13T09:00:00+00:00".into()
+            )
+        );
+
+        // resolved only once, and the check-out should be after the check-in
+        assert_eq!(
+            rsvp.resolve_dates(time("15:00"), time("11:00")),
+            Err(Error::invalid_time(
+                "start_date",
+                "should not be set with start or end"
+            ))
+        );
+        let mut rsvp = Reservation::new_for_dates(
+            "alon",
+            "room-1",
+            date("2023-03-12"),
+            Some(date("2023-03-12")),
+            "",
+            ReservationStatus::Pending,
+        );
+        assert_eq!(
+            rsvp.resolve_dates(time("15:00"), time("11:00")),
+            Err(Error::invalid_time(
+                "end_date",
+                "check-out is not after check-in"
+            ))
+        );
+        rsvp.start_date = "12/03/2023".into();
+        assert_eq!(
+            rsvp.resolve_dates(time("15:00"), time("11:00")),
+            Err(Error::invalid_time(
+                "start_date",
+                "12/03/2023 is not YYYY-MM-DD"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_should_compare_full_precision() {
+        let start: DateTime<FixedOffset> = "2022-12-26T15:00:00.5-0700".parse().unwrap();
+        let end: DateTime<FixedOffset> = "2022-12-26T15:00:00.75-0700".parse().unwrap();
+        let rsvp = Reservation::new(
+            "alon",
+            "desk-42",
+            start,
+            end,
+            "",
+            ReservationStatus::Pending,
+        );
+        assert!(rsvp.validate().is_ok());
+        let rsvp = Reservation::new(
+            "alon",
+            "desk-42",
+            end,
+            start,
+            "",
+            ReservationStatus::Pending,
+        );
+        assert_eq!(
+            rsvp.validate(),
+            Err(Error::invalid_time("end", "not after the start"))
+        );
+
+        // out of the range values are rejected instead of panicking
+        for (seconds, nanos, reason) in [
+            (0, -1, "nanos -1 out of range"),
+            (0, 1_000_000_000, "nanos 1000000000 out of range"),
+            (i64::MAX, 0, "seconds 9223372036854775807 out of range"),
+        ] {
+            let mut rsvp = rsvp.clone();
+            rsvp.start = Some(prost_types::Timestamp { seconds, nanos });
+            assert_eq!(rsvp.validate(), Err(Error::invalid_time("start", reason)));
         }
     }
 }
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Row};
+
+use crate::{
+    convert_to_timestamp, Reservation, ReservationEvent, ReservationEventType,
+    SqlxReservationEventType,
+};
+
+impl ReservationEvent {
+    /// sql to select reservation events, old and new snapshots are expanded into prefixed columns
+    pub fn select_sql(condition: &str, direction: &str, limit: Option<i64>) -> String {
+        let limit = limit.map(|v| format!(" LIMIT {}", v)).unwrap_or_default();
+        format!(
+            "{}{} ORDER BY e.id {}{}",
+            Self::select_where_sql(),
+            condition,
+            direction,
+            limit
+        )
+    }
+
+    /// the beginning of select_sql till the condition, i.e. it ends with "WHERE "
+    pub fn select_where_sql() -> String {
+        format!(
+            "SELECT e.id, e.reservation_id, e.event, e.created_at, e.actor_id, e.request_id, {}, {} FROM reservation_events e LEFT JOIN LATERAL jsonb_populate_record(NULL::reservations, e.old) o ON TRUE LEFT JOIN LATERAL jsonb_populate_record(NULL::reservations, e.new) n ON TRUE WHERE ",
+            snapshot_columns("o", "old_"),
+            snapshot_columns("n", "new_"),
+        )
+    }
+
+    pub fn get_event_type(&self) -> ReservationEventType {
+        ReservationEventType::from_i32(self.event_type).unwrap_or(ReservationEventType::Unknown)
+    }
+}
+
+fn snapshot_columns(table: &str, prefix: &str) -> String {
+    [
+        "id",
+        "user_id",
+        "status",
+        "resource_id",
+        "timespan",
+        "note",
+        "timezone",
+        "start_date",
+        "end_date",
+    ]
+    .iter()
+    .map(|c| format!("{}.{} AS {}{}", table, c, prefix, c))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+impl FromRow<'_, PgRow> for ReservationEvent {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        let event_type: SqlxReservationEventType = row.try_get("event")?;
+        let created_at: DateTime<Utc> = row.try_get("created_at")?;
+        let actor_id: Option<String> = row.try_get("actor_id")?;
+        let request_id: Option<String> = row.try_get("request_id")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            reservation_id: row.try_get("reservation_id")?,
+            event_type: ReservationEventType::from(event_type) as i32,
+            old: snapshot(row, "old_")?,
+            new: snapshot(row, "new_")?,
+            created_at: Some(convert_to_timestamp(created_at)),
+            actor_id: actor_id.unwrap_or_default(),
+            request_id: request_id.unwrap_or_default(),
+        })
+    }
+}
+
+fn snapshot(row: &PgRow, prefix: &str) -> Result<Option<Reservation>, sqlx::Error> {
+    let id: Option<i64> = row.try_get(format!("{}id", prefix).as_str())?;
+    match id {
+        Some(_) => Ok(Some(Reservation::from_prefixed_row(row, prefix)?)),
+        None => Ok(None),
+    }
+}
+
+impl From<SqlxReservationEventType> for ReservationEventType {
+    fn from(event_type: SqlxReservationEventType) -> Self {
+        match event_type {
+            SqlxReservationEventType::Create => ReservationEventType::Created,
+            SqlxReservationEventType::Update => ReservationEventType::Updated,
+            SqlxReservationEventType::Delete => ReservationEventType::Deleted,
+            SqlxReservationEventType::Unknown => ReservationEventType::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ReservationEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReservationEventType::Created => write!(f, "create"),
+            ReservationEventType::Updated => write!(f, "update"),
+            ReservationEventType::Deleted => write!(f, "delete"),
+            ReservationEventType::Unknown => write!(f, "unknown"),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::{
+    Error, ReservationEvent, ReservationEventFilter, ReservationEventFilterBuilder,
+    ReservationEventType, Validator,
+};
+
+impl ReservationEventFilterBuilder {
+    pub fn build(&self) -> Result<ReservationEventFilter, Error> {
+        let filter = self
+            .private_build()
+            .expect("failed to build ReservationEventFilter");
+        filter.validate()?;
+        Ok(filter)
+    }
+}
+
+impl Validator for ReservationEventFilter {
+    fn validate(&self) -> Result<(), Error> {
+        if self.reservation_id < 0 {
+            return Err(Error::InvalidReservationId(self.reservation_id));
+        }
+        if self.page_size < 10 || self.page_size > 100 {
+            return Err(Error::InvalidPageSize(self.page_size));
+        }
+        if let Some(cursor) = self.cursor {
+            if cursor < 0 {
+                return Err(Error::InvalidCursor(cursor));
+            }
+        }
+        ReservationEventType::from_i32(self.event_type)
+            .ok_or(Error::InvalidEventType(self.event_type))?;
+        Ok(())
+    }
+}
+
+impl ReservationEventFilter {
+    pub fn get_cursor(&self) -> i64 {
+        self.cursor.unwrap_or(if self.desc { i64::MAX } else { 0 })
+    }
+
+    pub fn get_event_type(&self) -> ReservationEventType {
+        ReservationEventType::from_i32(self.event_type).unwrap()
+    }
+}
+
+impl ReservationEventFilter {
+    /// the query of the filter, the user and resource ids are bound as parameters
+    pub fn to_query(&self) -> QueryBuilder<'static, Postgres> {
+        let mut builder = QueryBuilder::new(ReservationEvent::select_where_sql());
+        builder
+            .push(if self.desc { "e.id <= " } else { "e.id >= " })
+            .push_bind(self.get_cursor());
+
+        if self.reservation_id > 0 {
+            builder
+                .push(" AND e.reservation_id = ")
+                .push_bind(self.reservation_id);
+        }
+        let event_type = self.get_event_type();
+        if event_type != ReservationEventType::Unknown {
+            builder.push(format!(
+                " AND e.event = '{}'::reservation_event",
+                event_type
+            ));
+        }
+        if !self.user_id.is_empty() {
+            builder
+                .push(" AND COALESCE(n.user_id, o.user_id) = ")
+                .push_bind(self.user_id.clone());
+        }
+        if !self.resource_id.is_empty() {
+            builder
+                .push(" AND COALESCE(n.resource_id, o.resource_id) = ")
+                .push_bind(self.resource_id.clone());
+        }
+
+        let direction = if self.desc { "DESC" } else { "ASC" };
+        builder
+            .push(format!(" ORDER BY e.id {} LIMIT ", direction))
+            .push_bind(self.page_size);
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_filter_should_bind_the_values() {
+        let filter = ReservationEventFilterBuilder::default()
+            .user_id("alon' OR 'a' = 'a")
+            .event_type(ReservationEventType::Deleted as i32)
+            .build()
+            .unwrap();
+        let query = filter.to_query();
+        assert!(query.sql().ends_with("WHERE e.id >= $1 AND e.event = 'delete'::reservation_event AND COALESCE(n.user_id, o.user_id) = $2 ORDER BY e.id ASC LIMIT $3"));
+
+        let filter = ReservationEventFilterBuilder::default()
+            .reservation_id(42)
+            .cursor(100)
+            .desc(true)
+            .build()
+            .unwrap();
+        let query = filter.to_query();
+        assert!(query
+            .sql()
+            .ends_with("WHERE e.id <= $1 AND e.reservation_id = $2 ORDER BY e.id DESC LIMIT $3"));
+    }
+
+    #[test]
+    fn event_filter_with_invalid_event_type_should_fail() {
+        let err = ReservationEventFilterBuilder::default()
+            .event_type(10)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidEventType(10));
+    }
+}
@@ -1,10 +1,14 @@
-use prost_types::Timestamp;
+use chrono::{DateTime, TimeZone, Utc};
 
+use super::{local_day_range, parse_timezone, DEFAULT_TIMEZONE};
 use crate::{
-    convert_to_utc_time, Error, Normalizer, ReservationQuery, ReservationQueryBuilder,
-    ReservationStatus, ToSql, Validator,
+    convert_to_timestamp, convert_to_utc_time, Error, Normalizer, ReservationQuery,
+    ReservationQueryBuilder, ReservationStatus, ToSql, Validator,
 };
 
+/// start and end of the range to query, None if unbounded
+type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
 impl ReservationQueryBuilder {
     pub fn build(&self) -> Result<ReservationQuery, Error> {
         let mut query = self
@@ -13,20 +17,59 @@ impl ReservationQueryBuilder {
         query.normalize()?;
         Ok(query)
     }
+
+    /// set the start time from a chrono datetime
+    pub fn start_time<Tz: TimeZone>(&mut self, start: DateTime<Tz>) -> &mut Self {
+        self.start(convert_to_timestamp(start.with_timezone(&Utc)))
+    }
+
+    /// set the end time from a chrono datetime
+    pub fn end_time<Tz: TimeZone>(&mut self, end: DateTime<Tz>) -> &mut Self {
+        self.end(convert_to_timestamp(end.with_timezone(&Utc)))
+    }
 }
 
 impl ReservationQuery {
     pub fn get_status(&self) -> ReservationStatus {
         ReservationStatus::from_i32(self.status).unwrap()
     }
+
+    /// the UTC range to query, None if unbounded. The local day is resolved in the
+    /// timezone, UTC if not set
+    pub fn get_range(&self) -> Result<TimeRange, Error> {
+        let timezone = match self.timezone.as_str() {
+            "" => DEFAULT_TIMEZONE,
+            timezone => timezone,
+        };
+        let tz = parse_timezone(timezone)?;
+        if !self.local_day.is_empty() {
+            if self.start.is_some() || self.end.is_some() {
+                return Err(Error::invalid_time(
+                    "local_day",
+                    "should not be set with start or end",
+                ));
+            }
+            let (start, end) = local_day_range(&self.local_day, tz)?;
+            return Ok((Some(start), Some(end)));
+        }
+        let start = self
+            .start
+            .as_ref()
+            .map(|ts| convert_to_utc_time(Some(ts), "start"));
+        let end = self
+            .end
+            .as_ref()
+            .map(|ts| convert_to_utc_time(Some(ts), "end"));
+        Ok((start.transpose()?, end.transpose()?))
+    }
 }
 
 impl Validator for ReservationQuery {
     fn validate(&self) -> Result<(), Error> {
         ReservationStatus::from_i32(self.status).ok_or(Error::InvalidStatus(self.status))?;
-        if let (Some(start), Some(end)) = (self.start.as_ref(), self.end.as_ref()) {
-            if start.seconds >= end.seconds {
-                return Err(Error::InvalidTime);
+        if let (Some(start), Some(end)) = self.get_range()? {
+            if start >= end {
+                return Err(Error::invalid_time("end", "not after the start"));
             }
         }
         Ok(())
@@ -45,10 +88,11 @@ impl ToSql for ReservationQuery {
     fn to_sql(&self) -> String {
         let status = self.get_status();
 
+        let (start, end) = self.get_range().unwrap_or_default();
         let timespan = format!(
-            "tstzrange('{}', '{}')",
-            get_time_string(self.start.as_ref(), true),
-            get_time_string(self.end.as_ref(), false)
+            "tstzrange({}, {})",
+            get_time_string(start),
+            get_time_string(end)
         );
 
         let condition = match (self.user_id.is_empty(), self.resource_id.is_empty()) {
@@ -67,10 +111,12 @@ impl ToSql for ReservationQuery {
     }
 }
 
-fn get_time_string(ts: Option<&Timestamp>, start: bool) -> String {
-    match ts {
-        Some(ts) => convert_to_utc_time(Some(ts)).to_rfc3339(),
-        None => (if start { "-infinity" } else { "infinity" }).into(),
+/// unbounded if not set, so the open-ended reservations are contained too. The query is
+/// validated, so the range is always valid
+fn get_time_string(time: Option<DateTime<Utc>>) -> String {
+    match time {
+        Some(time) => format!("'{}'", time.to_rfc3339()),
+        None => "NULL".into(),
     }
 }
 
@@ -78,6 +124,7 @@ fn get_time_string(ts: Option<&Timestamp>, start: bool) -> String {
 mod tests {
     use super::*;
     use crate::ReservationQueryBuilder;
+    use prost_types::Timestamp;
 
     #[test]
     fn query_should_generate_correct_sql() {
@@ -86,7 +133,7 @@ mod tests {
             .build()
             .unwrap();
         let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM reservations WHERE tstzrange('-infinity', 'infinity') @> timespan AND status = 'pending'::reservation_status AND user_id = 'alon' ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM reservations WHERE tstzrange(NULL, NULL) @> timespan AND status = 'pending'::reservation_status AND user_id = 'alon' ORDER BY lower(timespan) ASC");
 
         let query = ReservationQueryBuilder::default()
             .resource_id("test")
@@ -94,7 +141,7 @@ mod tests {
             .build()
             .unwrap();
         let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM reservations WHERE tstzrange('2021-11-01T22:00:00+00:00', 'infinity') @> timespan AND status = 'pending'::reservation_status AND resource_id = 'test' ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM reservations WHERE tstzrange('2021-11-01T22:00:00+00:00', NULL) @> timespan AND status = 'pending'::reservation_status AND resource_id = 'test' ORDER BY lower(timespan) ASC");
 
         let query = ReservationQueryBuilder::default()
             .resource_id("test")
@@ -102,6 +149,100 @@ mod tests {
             .build()
             .unwrap();
         let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM reservations WHERE tstzrange('-infinity', '2021-11-01T23:00:00+00:00') @> timespan AND status = 'pending'::reservation_status AND resource_id = 'test' ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM reservations WHERE tstzrange(NULL, '2021-11-01T23:00:00+00:00') @> timespan AND status = 'pending'::reservation_status AND resource_id = 'test' ORDER BY lower(timespan) ASC");
+    }
+
+    #[test]
+    fn local_day_should_follow_dst_transitions() {
+        let range = |day: &str, timezone: &str| {
+            let query = ReservationQueryBuilder::default()
+                .resource_id("test")
+                .local_day(day)
+                .timezone(timezone)
+                .build()
+                .unwrap();
+            let (start, end) = query.get_range().unwrap();
+            (start.unwrap().to_rfc3339(), end.unwrap().to_rfc3339())
+        };
+        let cases = [
+            // a normal day, then the 23 and 25 hours days
+            (
+                "2022-11-01",
+                "America/Los_Angeles",
+                "2022-11-01T07:00:00+00:00",
+                "2022-11-02T07:00:00+00:00",
+            ),
+            (
+                "2023-03-12",
+                "America/Los_Angeles",
+                "2023-03-12T08:00:00+00:00",
+                "2023-03-13T07:00:00+00:00",
+            ),
+            (
+                "2022-11-06",
+                "America/Los_Angeles",
+                "2022-11-06T07:00:00+00:00",
+                "2022-11-07T08:00:00+00:00",
+            ),
+            // the midnight is skipped, the day starts at 01:00
+            (
+                "2018-11-04",
+                "America/Sao_Paulo",
+                "2018-11-04T03:00:00+00:00",
+                "2018-11-05T02:00:00+00:00",
+            ),
+            (
+                "2022-11-01",
+                "",
+                "2022-11-01T00:00:00+00:00",
+                "2022-11-02T00:00:00+00:00",
+            ),
+        ];
+        for (day, timezone, start, end) in cases {
+            assert_eq!(
+                range(day, timezone),
+                (start.into(), end.into()),
+                "{} {}",
+                day,
+                timezone
+            );
+        }
+
+        let query = ReservationQueryBuilder::default()
+            .local_day("2022-11-01")
+            .timezone("Europe/Berlin")
+            .build()
+            .unwrap();
+        assert_eq!(query.to_sql(), "SELECT * FROM reservations WHERE tstzrange('2022-10-31T23:00:00+00:00', '2022-11-01T23:00:00+00:00') @> timespan AND status = 'pending'::reservation_status AND TRUE ORDER BY lower(timespan) ASC");
+    }
+
+    #[test]
+    fn local_day_should_be_validated() {
+        let mut builder = ReservationQueryBuilder::default();
+        builder
+            .local_day("2022-11-01")
+            .timezone("Mars/Olympus_Mons");
+        assert_eq!(
+            builder.build(),
+            Err(Error::InvalidTimezone("Mars/Olympus_Mons".into()))
+        );
+        builder.timezone("").local_day("11/01/2022");
+        assert_eq!(
+            builder.build(),
+            Err(Error::invalid_time(
+                "local_day",
+                "11/01/2022 is not YYYY-MM-DD"
+            ))
+        );
+        builder
+            .local_day("2022-11-01")
+            .start("2022-11-01T00:00:00Z".parse::<Timestamp>().unwrap());
+        assert_eq!(
+            builder.build(),
+            Err(Error::invalid_time(
+                "local_day",
+                "should not be set with start or end"
+            ))
+        );
     }
 }
@@ -0,0 +1,56 @@
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Row};
+
+use crate::{Error, Validator, Webhook};
+
+impl Webhook {
+    pub fn new(url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            id: 0,
+            url: url.into(),
+            secret: secret.into(),
+        }
+    }
+}
+
+impl Validator for Webhook {
+    fn validate(&self) -> Result<(), Error> {
+        if !(self.url.starts_with("http://") || self.url.starts_with("https://")) {
+            return Err(Error::InvalidWebhookUrl(self.url.clone()));
+        }
+        if self.secret.is_empty() {
+            return Err(Error::InvalidWebhookSecret);
+        }
+        Ok(())
+    }
+}
+
+impl FromRow<'_, PgRow> for Webhook {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            url: row.try_get("url")?,
+            secret: row.try_get("secret")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_should_be_validated() {
+        assert!(Webhook::new("https://example.com/hook", "secret")
+            .validate()
+            .is_ok());
+        assert_eq!(
+            Webhook::new("ftp://example.com", "secret").validate(),
+            Err(Error::InvalidWebhookUrl("ftp://example.com".to_string()))
+        );
+        assert_eq!(
+            Webhook::new("http://localhost:8080", "").validate(),
+            Err(Error::InvalidWebhookSecret)
+        );
+    }
+}
@@ -0,0 +1,330 @@
+mod retry;
+
+use abi::{
+    reservation_service_client::ReservationServiceClient, CancelRequest, ConfirmRequest, Error,
+    FilterRequest, GetRequest, ListenRequest, QueryRequest, Reservation, ReservationEvent,
+    ReservationFilter, ReservationQuery, ReservationStatus, ReserveRequest, ServerConfig,
+    UpdateRequest,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use futures::{Future, Stream, TryStreamExt};
+use std::pin::Pin;
+use tonic::transport::{Channel, Endpoint};
+
+pub use retry::RetryPolicy;
+
+pub type ReservationStream = Pin<Box<dyn Stream<Item = Result<Reservation, Error>> + Send>>;
+pub type ReservationEventStream =
+    Pin<Box<dyn Stream<Item = Result<ReservationEvent, Error>> + Send>>;
+
+/// async client of the reservation service, errors are mapped back to abi::Error
+#[derive(Debug, Clone)]
+pub struct ReservationClient {
+    inner: ReservationServiceClient<Channel>,
+    retry: RetryPolicy,
+}
+
+impl ReservationClient {
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            inner: ReservationServiceClient::new(channel),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub async fn connect(url: impl Into<String>) -> Result<Self, Error> {
+        let channel = Endpoint::from_shared(url.into())
+            .map_err(transport_error)?
+            .connect()
+            .await
+            .map_err(transport_error)?;
+        Ok(Self::new(channel))
+    }
+
+    /// connect over TLS if it's enabled in the config
+    pub async fn from_config(config: &ServerConfig) -> Result<Self, Error> {
+        let channel = config
+            .endpoint()?
+            .connect()
+            .await
+            .map_err(transport_error)?;
+        Ok(Self::new(channel))
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// make a pending reservation
+    pub async fn reserve<Tz: TimeZone>(
+        &self,
+        user_id: impl Into<String>,
+        resource_id: impl Into<String>,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+        note: impl Into<String>,
+    ) -> Result<Reservation, Error> {
+        let reservation = Reservation::new(
+            user_id,
+            resource_id,
+            start.with_timezone(&Utc).into(),
+            end.with_timezone(&Utc).into(),
+            note,
+            ReservationStatus::Pending,
+        );
+        let request = ReserveRequest {
+            reservation: Some(reservation),
+        };
+        // a retry might make the reservation twice
+        let rsp = self.call_once(self.inner.clone().reserve(request)).await?;
+        rsp.reservation.ok_or(Error::Unknown)
+    }
+
+    pub async fn confirm(&self, id: i64) -> Result<Reservation, Error> {
+        let rsp = self
+            .call_once(self.inner.clone().confirm(ConfirmRequest { id }))
+            .await?;
+        rsp.reservation.ok_or(Error::Unknown)
+    }
+
+    pub async fn update(&self, id: i64, note: impl Into<String>) -> Result<Reservation, Error> {
+        let note = note.into();
+        let rsp = self
+            .call(|mut client| {
+                let request = UpdateRequest {
+                    id,
+                    note: note.clone(),
+                };
+                async move { client.update(request).await }
+            })
+            .await?;
+        rsp.reservation.ok_or(Error::Unknown)
+    }
+
+    pub async fn cancel(&self, id: i64) -> Result<Reservation, Error> {
+        let rsp = self
+            .call_once(self.inner.clone().cancel(CancelRequest { id }))
+            .await?;
+        rsp.reservation.ok_or(Error::Unknown)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Reservation, Error> {
+        let rsp = self
+            .call(|mut client| async move { client.get(GetRequest { id }).await })
+            .await?;
+        rsp.reservation.ok_or(Error::Unknown)
+    }
+
+    /// query reservations, use ReservationQueryBuilder::start_time/end_time to set the range
+    pub async fn query(&self, query: ReservationQuery) -> Result<ReservationStream, Error> {
+        let stream = self
+            .call(|mut client| {
+                let request = QueryRequest {
+                    query: Some(query.clone()),
+                };
+                async move { client.query(request).await }
+            })
+            .await?;
+        Ok(Box::pin(stream.map_err(Error::from)))
+    }
+
+    /// filter a page of reservations, order by reservation id
+    pub async fn filter(&self, filter: ReservationFilter) -> Result<Vec<Reservation>, Error> {
+        let rsp = self
+            .call(|mut client| {
+                let request = FilterRequest {
+                    filter: Some(filter.clone()),
+                };
+                async move { client.filter(request).await }
+            })
+            .await?;
+        Ok(rsp.reservations)
+    }
+
+    /// receive reservation events as they happen
+    pub async fn listen(&self) -> Result<ReservationEventStream, Error> {
+        let stream = self
+            .call(|mut client| async move { client.listen(ListenRequest {}).await })
+            .await?;
+        Ok(Box::pin(stream.map_err(Error::from)))
+    }
+
+    /// send an idempotent request, retry with backoff if the error is transient
+    async fn call<T, F, Fut>(&self, f: F) -> Result<T, Error>
+    where
+        F: Fn(ReservationServiceClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+    {
+        let mut retry = 0;
+        loop {
+            match f(self.inner.clone()).await {
+                Ok(rsp) => return Ok(rsp.into_inner()),
+                Err(status) if retry < self.retry.max_retries && retry::is_transient(&status) => {
+                    tokio::time::sleep(self.retry.backoff(retry)).await;
+                    retry += 1;
+                }
+                Err(status) => return Err(status.into()),
+            }
+        }
+    }
+
+    /// send a request which is not safe to repeat, e.g. a reservation, it's never retried
+    async fn call_once<T>(
+        &self,
+        fut: impl Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+    ) -> Result<T, Error> {
+        Ok(fut.await?.into_inner())
+    }
+}
+
+fn transport_error(e: tonic::transport::Error) -> Error {
+    Error::RpcError(tonic::Code::Unavailable, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{Config, ReservationConflictInfo, ReservationQueryBuilder, ReservationWindow};
+    use futures::StreamExt;
+    use reservation_service::start_server;
+    use sqlx_db_tester::TestPg;
+    use std::{
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    struct TestServer {
+        #[allow(dead_code)]
+        db: TestPg,
+        config: Config,
+    }
+
+    /// a port nobody is listening on, so that the tests could run in parallel
+    fn free_port() -> u16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    impl TestServer {
+        async fn start() -> Self {
+            let mut config = Config::load("../service/fixtures/config.yml").unwrap();
+            let db = TestPg::new(config.db.server_url(), Path::new("../migrations"));
+            config.db.name = db.dbname.clone();
+            config.server.port = free_port();
+
+            let server_config = config.clone();
+            tokio::spawn(async move { start_server(&server_config).await.unwrap() });
+            Self { db, config }
+        }
+
+        async fn client(&self) -> ReservationClient {
+            for _ in 0..50 {
+                if let Ok(client) = ReservationClient::from_config(&self.config.server).await {
+                    return client;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            panic!("server is not started");
+        }
+    }
+
+    #[tokio::test]
+    async fn client_should_map_conflict_to_error() {
+        let server = TestServer::start().await;
+        let client = server.client().await;
+
+        let start: DateTime<Utc> = "2022-12-25T22:00:00Z".parse().unwrap();
+        let end: DateTime<Utc> = "2022-12-28T19:00:00Z".parse().unwrap();
+        let rsv = client
+            .reserve("alon", "ixia-3230", start, end, "hello")
+            .await
+            .unwrap();
+        assert!(rsv.id > 0);
+        // the caller is anonymous, the owner is hidden
+        let expected = Reservation {
+            user_id: "".to_string(),
+            ..rsv.clone()
+        };
+        assert_eq!(client.get(rsv.id).await.unwrap(), expected);
+
+        let err = client
+            .reserve(
+                "bob",
+                "ixia-3230",
+                "2022-12-26T22:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+                "2022-12-30T19:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+                "",
+            )
+            .await
+            .unwrap_err();
+        let conflict = match err {
+            Error::ConflictReservation(ReservationConflictInfo::Parsed(conflict)) => conflict,
+            err => panic!("unexpected error: {:?}", err),
+        };
+        assert_eq!(
+            conflict.old,
+            ReservationWindow::new("ixia-3230", start, end)
+        );
+
+        assert_eq!(client.get(rsv.id + 1).await.unwrap_err(), Error::NotFound);
+    }
+
+    #[tokio::test]
+    async fn client_query_should_return_stream() {
+        let server = TestServer::start().await;
+        let client = server.client().await;
+
+        for day in [1, 5] {
+            let start = Utc.with_ymd_and_hms(2023, 1, day, 15, 0, 0).unwrap();
+            client
+                .reserve(
+                    "alon",
+                    "ixia-3230",
+                    start,
+                    start + chrono::Duration::days(2),
+                    "",
+                )
+                .await
+                .unwrap();
+        }
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("alon")
+            .start_time(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap())
+            .end_time(Utc.with_ymd_and_hms(2023, 1, 4, 0, 0, 0).unwrap())
+            .build()
+            .unwrap();
+        let reservations: Vec<_> = client.query(query).await.unwrap().collect().await;
+        assert_eq!(reservations.len(), 1);
+        let reservation = reservations[0].as_ref().unwrap();
+        assert_eq!(reservation.resource_id, "ixia-3230");
+        assert_eq!(reservation.user_id, "");
+    }
+
+    #[tokio::test]
+    async fn client_should_retry_transient_errors() {
+        let url = format!("http://127.0.0.1:{}", free_port());
+        let channel = Endpoint::from_shared(url).unwrap().connect_lazy();
+        let client = ReservationClient::new(channel).with_retry(RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        });
+
+        let start = Instant::now();
+        let err = client.get(1).await.unwrap_err();
+        assert!(matches!(err, Error::RpcError(tonic::Code::Unavailable, _)));
+        // 50ms + 100ms of backoff
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        // the reservation might be made by the failed attempt, it's not sent again
+        let start = Instant::now();
+        let err = client
+            .reserve("alon", "ixia-3230", Utc::now(), Utc::now(), "")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::RpcError(tonic::Code::Unavailable, _)));
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
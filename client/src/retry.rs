@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+/// retry policy for transient errors, the backoff is doubled for each retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// retries after the first attempt, 0 to disable retry
+    pub max_retries: u32,
+    /// backoff before the first retry
+    pub initial_backoff: Duration,
+    /// upper limit of the backoff
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// backoff before the given retry (starts from 0)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+/// the server is unavailable or overloaded for a while. The request might have been processed
+/// already, so only the idempotent requests are sent again
+pub(crate) fn is_transient(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        tonic::Code::Unavailable | tonic::Code::ResourceExhausted | tonic::Code::Aborted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_should_be_doubled_and_capped() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(1600));
+        assert_eq!(policy.backoff(5), Duration::from_secs(2));
+        assert_eq!(policy.backoff(100), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn only_transient_status_should_be_retried() {
+        assert!(is_transient(&tonic::Status::unavailable("")));
+        assert!(!is_transient(&tonic::Status::failed_precondition("")));
+        assert!(!is_transient(&tonic::Status::deadline_exceeded("")));
+    }
+}
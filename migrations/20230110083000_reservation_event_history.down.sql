@@ -0,0 +1,2 @@
+ALTER TABLE reservation_events DROP COLUMN created_at;
+ALTER TABLE reservation_events ALTER COLUMN id TYPE INTEGER;
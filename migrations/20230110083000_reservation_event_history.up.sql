@@ -0,0 +1,4 @@
+-- event ids are exposed as int64 in the api
+ALTER TABLE reservation_events ALTER COLUMN id TYPE BIGINT;
+-- record when the reservation event happened
+ALTER TABLE reservation_events ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
@@ -0,0 +1,22 @@
+CREATE OR REPLACE FUNCTION reservations_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_events
+        INSERT INTO reservation_events (reservation_id, old, new, event) VALUES (NEW.id, null, to_jsonb(NEW), 'create');
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- if status changed, update reservation_events
+        IF OLD.status <> NEW.status THEN
+            INSERT INTO reservation_events (reservation_id, old, new, event) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'update');
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_events
+        INSERT INTO reservation_events (reservation_id, old, new, event) VALUES (OLD.id, to_jsonb(OLD), null, 'delete');
+    END IF;
+    -- notify a channel called reservation_event
+    NOTIFY reservation_event;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE reservation_events DROP COLUMN request_id;
+ALTER TABLE reservation_events DROP COLUMN actor_id;
@@ -0,0 +1,27 @@
+-- who made the change and in which request, set by the application in a transaction-local setting
+ALTER TABLE reservation_events ADD COLUMN actor_id VARCHAR(64);
+ALTER TABLE reservation_events ADD COLUMN request_id VARCHAR(64);
+
+-- trigger for add/update/delete a reservation, every column change is recorded
+CREATE OR REPLACE FUNCTION reservations_trigger() RETURNS TRIGGER AS $$
+DECLARE
+    actor VARCHAR(64) := NULLIF(current_setting('reservation.actor_id', true), '');
+    request VARCHAR(64) := NULLIF(current_setting('reservation.request_id', true), '');
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_events
+        INSERT INTO reservation_events (reservation_id, old, new, event, actor_id, request_id) VALUES (NEW.id, null, to_jsonb(NEW), 'create', actor, request);
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- if any column changed, update reservation_events
+        IF OLD IS DISTINCT FROM NEW THEN
+            INSERT INTO reservation_events (reservation_id, old, new, event, actor_id, request_id) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'update', actor, request);
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_events
+        INSERT INTO reservation_events (reservation_id, old, new, event, actor_id, request_id) VALUES (OLD.id, to_jsonb(OLD), null, 'delete', actor, request);
+    END IF;
+    -- notify a channel called reservation_event
+    NOTIFY reservation_event;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
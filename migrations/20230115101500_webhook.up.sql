@@ -0,0 +1,21 @@
+-- subscribers which receive reservation events by HTTP callbacks
+CREATE TABLE webhooks (
+    id BIGSERIAL NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT webhooks_pkey PRIMARY KEY (id)
+);
+
+-- events which could not be delivered after all the retries
+CREATE TABLE webhook_dead_letters (
+    id BIGSERIAL NOT NULL,
+    webhook_id BIGINT NOT NULL,
+    event_id BIGINT NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT webhook_dead_letters_pkey PRIMARY KEY (id)
+);
+CREATE INDEX webhook_dead_letters_webhook_id_idx ON webhook_dead_letters (webhook_id);
@@ -0,0 +1,2 @@
+-- IANA time zone of the reservation, the timespan is still stored in UTC
+ALTER TABLE reservations ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
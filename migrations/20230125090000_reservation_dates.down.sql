@@ -0,0 +1,2 @@
+ALTER TABLE reservations DROP COLUMN end_date;
+ALTER TABLE reservations DROP COLUMN start_date;
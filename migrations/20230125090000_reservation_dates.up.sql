@@ -0,0 +1,3 @@
+-- check-in and check-out dates of the date-based bookings, the timespan is derived from them
+ALTER TABLE reservations ADD COLUMN start_date DATE;
+ALTER TABLE reservations ADD COLUMN end_date DATE;
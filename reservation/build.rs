@@ -0,0 +1,4 @@
+fn main() {
+    // the migrations are embedded by sqlx::migrate!, rebuild if they're changed
+    println!("cargo:rerun-if-changed=../migrations");
+}
@@ -0,0 +1,709 @@
+//! the conformance suite of the Reservation implementations: the same scenarios and the
+//! same random operation sequences checked against a reference model run on every store.
+//! The stores don't have to be empty, each scenario or case only touches the users and
+//! resources under its own namespace.
+
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use abi::{
+    convert_to_timestamp, convert_to_utc_time, ReservationConflictInfo, ReservationFilterBuilder,
+    ReservationQueryBuilder, ReservationStatus,
+};
+use chrono::{DateTime, Duration, FixedOffset, SubsecRound, TimeZone, Utc};
+use proptest::{
+    option,
+    prelude::*,
+    test_runner::{TestCaseError, TestRunner},
+};
+use prost_types::Timestamp;
+use tokio::{runtime::Runtime, sync::mpsc};
+
+use crate::Reservation;
+
+const USERS: usize = 3;
+const RESOURCES: usize = 3;
+/// no store ever gives out this id
+const MISSING_ID: i64 = i64::MAX;
+
+/// run all the scenarios on the store, panic on the first failure
+pub async fn run<R: Reservation + Sync>(store: &R, namespace: &str) {
+    conflicts_should_be_rejected(store, &format!("{}-conflict", namespace)).await;
+    adjacent_windows_should_not_conflict(store, &format!("{}-adjacent", namespace)).await;
+    status_should_filter_results(store, &format!("{}-status", namespace)).await;
+    filter_should_paginate_in_id_order(store, &format!("{}-page", namespace)).await;
+    query_should_stream_in_start_order(store, &format!("{}-order", namespace)).await;
+    bounds_should_be_kept(store, &format!("{}-bounds", namespace)).await;
+    sub_microseconds_should_be_truncated(store, &format!("{}-precision", namespace)).await;
+}
+
+/// run random operation sequences on the store and the reference model, panic with the
+/// minimal failing sequence if they disagree. Must not be called within a tokio runtime
+pub fn check_against_model<R: Reservation + Sync>(
+    runtime: &Runtime,
+    store: &R,
+    config: ProptestConfig,
+) {
+    let cases = AtomicUsize::new(0);
+    let mut runner = TestRunner::new(config);
+    let result = runner.run(&operations(), |ops| {
+        let namespace = format!("model-{}", cases.fetch_add(1, Ordering::Relaxed));
+        runtime.block_on(check_operations(store, &namespace, &ops))
+    });
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
+}
+
+/// a step of the random sequences. Users, resources and times are indexes, the targets
+/// pick one of the reservations made so far, or an id which doesn't exist
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Reserve {
+        user: usize,
+        resource: usize,
+        start: i64,
+        /// None for an open-ended one
+        hours: Option<i64>,
+        start_exclusive: bool,
+        end_inclusive: bool,
+        status: ReservationStatus,
+    },
+    Confirm {
+        target: usize,
+    },
+    Update {
+        target: usize,
+        note: String,
+    },
+    Delete {
+        target: usize,
+    },
+    Query {
+        user: Option<usize>,
+        resource: usize,
+        window: Option<(i64, i64)>,
+        status: ReservationStatus,
+        desc: bool,
+    },
+    Filter {
+        user: usize,
+        resource: Option<usize>,
+        status: ReservationStatus,
+        desc: bool,
+    },
+}
+
+/// sequences of operations, reserving is more likely so there's something to work on
+pub fn operations() -> impl Strategy<Value = Vec<Operation>> {
+    let operation = prop_oneof![
+        4 => (
+            0..USERS,
+            0..RESOURCES,
+            0..72i64,
+            option::weighted(0.9, 1..12i64),
+            any::<(bool, bool)>(),
+            status()
+        )
+            .prop_map(
+                |(user, resource, start, hours, (start_exclusive, end_inclusive), status)| {
+                    Operation::Reserve {
+                        user,
+                        resource,
+                        start,
+                        hours,
+                        start_exclusive,
+                        end_inclusive,
+                        status,
+                    }
+                }
+            ),
+        1 => any::<usize>().prop_map(|target| Operation::Confirm { target }),
+        1 => (any::<usize>(), "[a-z]{0,3}")
+            .prop_map(|(target, note)| Operation::Update { target, note }),
+        1 => any::<usize>().prop_map(|target| Operation::Delete { target }),
+        1 => (
+            option::of(0..USERS),
+            0..RESOURCES,
+            option::of((0..72i64, 1..48i64)),
+            status(),
+            any::<bool>()
+        )
+            .prop_map(|(user, resource, window, status, desc)| Operation::Query {
+                user,
+                resource,
+                window,
+                status,
+                desc,
+            }),
+        1 => (0..USERS, option::of(0..RESOURCES), status(), any::<bool>()).prop_map(
+            |(user, resource, status, desc)| Operation::Filter {
+                user,
+                resource,
+                status,
+                desc,
+            }
+        ),
+    ];
+    proptest::collection::vec(operation, 1..40)
+}
+
+/// apply the operations to the store and the model, fail on the first difference
+pub async fn check_operations<R: Reservation + Sync>(
+    store: &R,
+    namespace: &str,
+    operations: &[Operation],
+) -> Result<(), TestCaseError> {
+    let user = |index| name(namespace, "user", index);
+    let resource = |index| name(namespace, "room", index);
+    let mut model = Model::default();
+
+    for operation in operations.iter().cloned() {
+        match operation {
+            Operation::Reserve {
+                user: uid,
+                resource: rid,
+                start,
+                hours,
+                start_exclusive,
+                end_inclusive,
+                status,
+            } => {
+                let end = at(start + hours.unwrap_or(1));
+                let mut rsvp = reservation(&user(uid), &resource(rid), at(start), end);
+                if hours.is_none() {
+                    rsvp.end = None;
+                }
+                rsvp.start_exclusive = start_exclusive;
+                rsvp.end_inclusive = end_inclusive && hours.is_some();
+                rsvp.status = status as i32;
+                let conflicts = model.conflicts(&rsvp);
+                match store.reserve(rsvp.clone()).await {
+                    Ok(saved) => {
+                        prop_assert!(conflicts.is_empty(), "{:?} should conflict", rsvp);
+                        prop_assert_eq!(
+                            &saved,
+                            &abi::Reservation {
+                                id: saved.id,
+                                timezone: abi::DEFAULT_TIMEZONE.to_string(),
+                                ..rsvp
+                            }
+                        );
+                        model.insert(saved);
+                    }
+                    Err(abi::Error::ConflictReservation(info)) => {
+                        prop_assert!(!conflicts.is_empty(), "{:?} should not conflict", rsvp);
+                        prop_assert_eq!(existing_ids(&info), conflicts);
+                    }
+                    Err(err) => return Err(fail(err)),
+                }
+            }
+            Operation::Confirm { target } => {
+                let id = model.target(target);
+                let expected = match model.reservations.get_mut(&id) {
+                    Some(r) if r.status == ReservationStatus::Pending as i32 => {
+                        r.status = ReservationStatus::Confirmed as i32;
+                        Ok(r.clone())
+                    }
+                    _ => Err(abi::Error::NotFound),
+                };
+                prop_assert_eq!(store.confirm(id).await, expected);
+            }
+            Operation::Update { target, note } => {
+                let id = model.target(target);
+                let expected = match model.reservations.get_mut(&id) {
+                    Some(r) => {
+                        r.note = note.clone();
+                        Ok(r.clone())
+                    }
+                    None => Err(abi::Error::NotFound),
+                };
+                prop_assert_eq!(store.update(id, note).await, expected);
+            }
+            Operation::Delete { target } => {
+                let id = model.target(target);
+                let expected = model.reservations.remove(&id).ok_or(abi::Error::NotFound);
+                prop_assert_eq!(store.delete(id).await, expected);
+            }
+            Operation::Query {
+                user: uid,
+                resource: rid,
+                window,
+                status,
+                desc,
+            } => {
+                let mut builder = ReservationQueryBuilder::default();
+                builder
+                    .resource_id(resource(rid))
+                    .status(status as i32)
+                    .desc(desc);
+                if let Some(uid) = uid {
+                    builder.user_id(user(uid));
+                }
+                if let Some((start, hours)) = window {
+                    builder.start_time(at(start)).end_time(at(start + hours));
+                }
+                let query = builder.build().map_err(fail)?;
+                let actual = collect(store.query(query.clone()).await)
+                    .await
+                    .map_err(fail)?;
+                prop_assert_eq!(actual, model.query(&query));
+            }
+            Operation::Filter {
+                user: uid,
+                resource: rid,
+                status,
+                desc,
+            } => {
+                let mut builder = ReservationFilterBuilder::default();
+                builder.user_id(user(uid)).status(status as i32).desc(desc);
+                if let Some(rid) = rid {
+                    builder.resource_id(resource(rid));
+                }
+                let filter = builder.build().map_err(fail)?;
+                let actual = filter_all(store, filter.clone()).await.map_err(fail)?;
+                prop_assert_eq!(actual, model.filter(&filter));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the reference model: everything is a scan over the saved reservations. The bounds are
+/// compared as points: `[t` and `t)` are (t, false), `(t` and `t]` are (t, true), so every
+/// timespan is [start, end) in the points
+#[derive(Debug, Default)]
+struct Model {
+    reservations: BTreeMap<i64, abi::Reservation>,
+    /// ids of all the reservations ever saved, deleted ones included
+    ids: Vec<i64>,
+}
+
+impl Model {
+    fn insert(&mut self, reservation: abi::Reservation) {
+        self.ids.push(reservation.id);
+        self.reservations.insert(reservation.id, reservation);
+    }
+
+    fn target(&self, target: usize) -> i64 {
+        let index = target % (self.ids.len() + 1);
+        self.ids.get(index).copied().unwrap_or(MISSING_ID)
+    }
+
+    /// timespans of the same resource must not overlap, the ids of the overlapping ones
+    /// ordered by start
+    fn conflicts(&self, reservation: &abi::Reservation) -> Vec<i64> {
+        let (start, end) = points(reservation);
+        let mut conflicts: Vec<_> = self
+            .reservations
+            .values()
+            .filter(|r| {
+                let (other_start, other_end) = points(r);
+                r.resource_id == reservation.resource_id && start < other_end && other_start < end
+            })
+            .collect();
+        conflicts.sort_by_key(|r| points(r).0);
+        conflicts.iter().map(|r| r.id).collect()
+    }
+
+    /// the query range is [start, end), unbounded if not set
+    fn query(&self, query: &abi::ReservationQuery) -> Vec<abi::Reservation> {
+        let (start, end) = points(&abi::Reservation {
+            start: query.start.clone(),
+            end: query.end.clone(),
+            ..Default::default()
+        });
+        let mut reservations: Vec<_> = self
+            .reservations
+            .values()
+            .filter(|r| {
+                let (r_start, r_end) = points(r);
+                r.status == query.status
+                    && r.resource_id == query.resource_id
+                    && (query.user_id.is_empty() || r.user_id == query.user_id)
+                    && start <= r_start
+                    && r_end <= end
+            })
+            .cloned()
+            .collect();
+        reservations.sort_by_key(|r| points(r).0);
+        if query.desc {
+            reservations.reverse();
+        }
+        reservations
+    }
+
+    fn filter(&self, filter: &abi::ReservationFilter) -> Vec<abi::Reservation> {
+        let mut reservations: Vec<_> = self
+            .reservations
+            .values()
+            .filter(|r| {
+                r.status == filter.status
+                    && r.user_id == filter.user_id
+                    && (filter.resource_id.is_empty() || r.resource_id == filter.resource_id)
+            })
+            .cloned()
+            .collect();
+        if filter.desc {
+            reservations.reverse();
+        }
+        reservations
+    }
+}
+
+async fn conflicts_should_be_rejected<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let room = name(namespace, "room", 0);
+    let saved = store
+        .reserve(reservation(&user, &room, at(10), at(20)))
+        .await
+        .unwrap();
+
+    for (start, end) in [(12, 14), (5, 11), (19, 30), (0, 40), (10, 20)] {
+        let existing = assert_conflict(store, reservation(&user, &room, at(start), at(end))).await;
+        assert_eq!(existing, vec![saved.id]);
+    }
+    // all the overlapping reservations are reported, ordered by start
+    let later = store
+        .reserve(reservation(&user, &room, at(25), at(30)))
+        .await
+        .unwrap();
+    let existing = assert_conflict(store, reservation(&user, &room, at(0), at(40))).await;
+    assert_eq!(existing, vec![saved.id, later.id]);
+    // a confirmed reservation blocks the timespan as well
+    store.confirm(saved.id).await.unwrap();
+    assert_conflict(store, reservation(&user, &room, at(12), at(14))).await;
+
+    // other resources are not affected
+    let other = name(namespace, "room", 1);
+    store
+        .reserve(reservation(&user, &other, at(10), at(20)))
+        .await
+        .unwrap();
+
+    // the timespan is free again after deleting
+    store.delete(saved.id).await.unwrap();
+    store
+        .reserve(reservation(&user, &room, at(12), at(14)))
+        .await
+        .unwrap();
+}
+
+async fn adjacent_windows_should_not_conflict<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let room = name(namespace, "room", 0);
+    store
+        .reserve(reservation(&user, &room, at(10), at(20)))
+        .await
+        .unwrap();
+
+    // the end is exclusive, the start is inclusive
+    store
+        .reserve(reservation(&user, &room, at(20), at(30)))
+        .await
+        .unwrap();
+    store
+        .reserve(reservation(&user, &room, at(0), at(10)))
+        .await
+        .unwrap();
+
+    // a microsecond of overlap is a conflict
+    let microsecond = Duration::microseconds(1);
+    let rsvp = reservation(&user, &room, at(30) - microsecond, at(40));
+    assert_conflict(store, rsvp).await;
+    let rsvp = reservation(&user, &room, at(-10), at(0) + microsecond);
+    assert_conflict(store, rsvp).await;
+}
+
+async fn status_should_filter_results<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let room = name(namespace, "room", 0);
+    let mut ids = vec![];
+    for (hour, status) in [
+        (0, ReservationStatus::Pending),
+        (10, ReservationStatus::Confirmed),
+        (20, ReservationStatus::Blocked),
+    ] {
+        let mut rsvp = reservation(&user, &room, at(hour), at(hour + 5));
+        rsvp.status = status as i32;
+        ids.push((status, store.reserve(rsvp).await.unwrap().id));
+    }
+    // unknown is the same as pending
+    ids.push((ReservationStatus::Unknown, ids[0].1));
+
+    for (status, id) in ids {
+        let query = ReservationQueryBuilder::default()
+            .user_id(&user)
+            .status(status as i32)
+            .build()
+            .unwrap();
+        let actual = collect(store.query(query).await).await.unwrap();
+        assert_eq!(ids_of(&actual), vec![id], "query {:?}", status);
+
+        let filter = ReservationFilterBuilder::default()
+            .user_id(&user)
+            .status(status as i32)
+            .build()
+            .unwrap();
+        let actual = store.filter(filter).await.unwrap();
+        assert_eq!(ids_of(&actual), vec![id], "filter {:?}", status);
+    }
+}
+
+async fn filter_should_paginate_in_id_order<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let mut ids = vec![];
+    for i in 0..25 {
+        // spread over resources so the ids and the timespans are in different orders
+        let room = name(namespace, "room", i % 3);
+        let rsvp = reservation(&user, &room, at(100 - i as i64), at(101 - i as i64));
+        ids.push(store.reserve(rsvp).await.unwrap().id);
+    }
+
+    let filter = ReservationFilterBuilder::default()
+        .user_id(&user)
+        .build()
+        .unwrap();
+    let page = store.filter(filter.clone()).await.unwrap();
+    assert_eq!(ids_of(&page), &ids[..10]);
+    let all = filter_all(store, filter).await.unwrap();
+    assert_eq!(ids_of(&all), ids);
+
+    let filter = ReservationFilterBuilder::default()
+        .user_id(&user)
+        .desc(true)
+        .build()
+        .unwrap();
+    let all = filter_all(store, filter).await.unwrap();
+    ids.reverse();
+    assert_eq!(ids_of(&all), ids);
+}
+
+async fn query_should_stream_in_start_order<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let room = name(namespace, "room", 0);
+    for hour in [30, 5, 17, 42, 11] {
+        store
+            .reserve(reservation(&user, &room, at(hour), at(hour + 2)))
+            .await
+            .unwrap();
+    }
+
+    let starts = |reservations: Vec<abi::Reservation>| -> Vec<_> {
+        reservations.iter().map(|r| points(r).0 .0).collect()
+    };
+    let mut builder = ReservationQueryBuilder::default();
+    builder.resource_id(&room);
+    let actual = collect(store.query(builder.build().unwrap()).await).await;
+    assert_eq!(starts(actual.unwrap()), [5, 11, 17, 30, 42].map(at));
+
+    // only the reservations within the range, latest first
+    builder.start_time(at(10)).end_time(at(32)).desc(true);
+    let actual = collect(store.query(builder.build().unwrap()).await).await;
+    assert_eq!(starts(actual.unwrap()), [30, 17, 11].map(at));
+}
+
+async fn bounds_should_be_kept<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let room = name(namespace, "room", 0);
+    // (10, 20] and [0, 10] touch without overlapping
+    let mut first = reservation(&user, &room, at(10), at(20));
+    first.start_exclusive = true;
+    first.end_inclusive = true;
+    let first = store.reserve(first).await.unwrap();
+    let mut second = reservation(&user, &room, at(0), at(10));
+    second.end_inclusive = true;
+    store.reserve(second).await.unwrap();
+    // 20 is taken by the first one
+    assert_conflict(store, reservation(&user, &room, at(20), at(30))).await;
+
+    // an open-ended one takes everything after its start
+    let desk = abi::Reservation {
+        end: None,
+        ..reservation(&user, &room, at(30), at(31))
+    };
+    let desk = store.reserve(desk).await.unwrap();
+    let existing = assert_conflict(store, reservation(&user, &room, at(1000), at(1001))).await;
+    assert_eq!(existing, vec![desk.id]);
+    let mut later = reservation(&user, &room, at(20), at(25));
+    later.start_exclusive = true;
+    store.reserve(later).await.unwrap();
+
+    // the bounds come back as they are, the open-ended one is only in unbounded queries
+    let mut builder = ReservationQueryBuilder::default();
+    builder.resource_id(&room);
+    let actual = collect(store.query(builder.build().unwrap()).await).await;
+    let actual = actual.unwrap();
+    assert_eq!(actual.len(), 4);
+    assert_eq!(actual[1], first);
+    assert_eq!(actual[3], desk);
+    builder.start_time(at(0)).end_time(at(1000));
+    let actual = collect(store.query(builder.build().unwrap()).await).await;
+    assert_eq!(actual.unwrap().len(), 3);
+}
+
+async fn sub_microseconds_should_be_truncated<R: Reservation + Sync>(store: &R, namespace: &str) {
+    let user = name(namespace, "user", 0);
+    let room = name(namespace, "room", 0);
+    let start = at(0) + Duration::nanoseconds(1_999);
+    let end = at(1) + Duration::nanoseconds(999);
+    let saved = store
+        .reserve(reservation(&user, &room, start, end))
+        .await
+        .unwrap();
+    let saved = store.get(saved.id).await.unwrap();
+    let micros = |dt: DateTime<Utc>| Some(convert_to_timestamp(dt.trunc_subsecs(6)));
+    assert_eq!(saved.start, micros(start));
+    assert_eq!(saved.end, micros(end));
+}
+
+/// the ids of the reservations in the way
+async fn assert_conflict<R: Reservation + Sync>(
+    store: &R,
+    reservation: abi::Reservation,
+) -> Vec<i64> {
+    match store.reserve(reservation.clone()).await {
+        Err(abi::Error::ConflictReservation(info)) => existing_ids(&info),
+        result => panic!("{:?} should conflict, got {:?}", reservation, result),
+    }
+}
+
+fn existing_ids(info: &ReservationConflictInfo) -> Vec<i64> {
+    match info {
+        ReservationConflictInfo::Parsed(conflict) => {
+            conflict.existing.iter().map(|r| r.id).collect()
+        }
+        ReservationConflictInfo::UnParsed(_) => vec![],
+    }
+}
+
+/// all the pages of the filter, following the cursor like the clients do
+async fn filter_all<R: Reservation + Sync>(
+    store: &R,
+    mut filter: abi::ReservationFilter,
+) -> Result<Vec<abi::Reservation>, abi::Error> {
+    let mut reservations = vec![];
+    loop {
+        let page = store.filter(filter.clone()).await?;
+        let done = page.len() < filter.page_size as usize;
+        if let Some(last) = page.last() {
+            filter.cursor = Some(if filter.desc {
+                last.id - 1
+            } else {
+                last.id + 1
+            });
+        }
+        reservations.extend(page);
+        if done {
+            return Ok(reservations);
+        }
+    }
+}
+
+async fn collect(
+    mut rx: mpsc::Receiver<Result<abi::Reservation, abi::Error>>,
+) -> Result<Vec<abi::Reservation>, abi::Error> {
+    let mut reservations = vec![];
+    while let Some(reservation) = rx.recv().await {
+        reservations.push(reservation?);
+    }
+    Ok(reservations)
+}
+
+fn status() -> impl Strategy<Value = ReservationStatus> {
+    prop_oneof![
+        Just(ReservationStatus::Pending),
+        Just(ReservationStatus::Confirmed),
+        Just(ReservationStatus::Blocked),
+    ]
+}
+
+fn name(namespace: &str, kind: &str, index: usize) -> String {
+    format!("{}-{}-{}", namespace, kind, index)
+}
+
+/// hours since the start of 2030
+fn at(hours: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap() + Duration::hours(hours)
+}
+
+fn reservation(
+    user: &str,
+    resource: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> abi::Reservation {
+    let utc = FixedOffset::east_opt(0).unwrap();
+    abi::Reservation::new(
+        user,
+        resource,
+        start.with_timezone(&utc),
+        end.with_timezone(&utc),
+        "",
+        ReservationStatus::Pending,
+    )
+}
+
+/// the bounds as points, see the model. The unbounded ones are the min and the max
+fn points(reservation: &abi::Reservation) -> ((DateTime<Utc>, bool), (DateTime<Utc>, bool)) {
+    let time = |ts: Option<&Timestamp>| convert_to_utc_time(ts, "").ok();
+    let start = match time(reservation.start.as_ref()) {
+        Some(start) => (start, reservation.start_exclusive),
+        None => (DateTime::<Utc>::MIN_UTC, false),
+    };
+    let end = match time(reservation.end.as_ref()) {
+        Some(end) => (end, reservation.end_inclusive),
+        None => (DateTime::<Utc>::MAX_UTC, true),
+    };
+    (start, end)
+}
+
+fn ids_of(reservations: &[abi::Reservation]) -> Vec<i64> {
+    reservations.iter().map(|r| r.id).collect()
+}
+
+fn fail(err: impl std::fmt::Debug) -> TestCaseError {
+    TestCaseError::fail(format!("{:?}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::init_db, InMemoryReservationStore, ReservationStore};
+
+    fn runtime() -> Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_should_conform() {
+        run(&InMemoryReservationStore::new(), "memory").await;
+    }
+
+    #[tokio::test]
+    async fn postgres_store_should_conform() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        run(&store, "postgres").await;
+    }
+
+    #[test]
+    fn in_memory_store_should_match_model() {
+        let runtime = runtime();
+        let store = InMemoryReservationStore::new();
+        check_against_model(&runtime, &store, ProptestConfig::with_cases(256));
+    }
+
+    #[test]
+    fn postgres_store_should_match_model() {
+        let runtime = runtime();
+        let db = init_db();
+        let store = ReservationStore::new(runtime.block_on(db.get_pool()));
+        check_against_model(&runtime, &store, ProptestConfig::with_cases(32));
+    }
+}
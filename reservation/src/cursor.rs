@@ -0,0 +1,122 @@
+use sqlx::PgPool;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// the missing ids are checked until they are this old, a transaction taking longer would be missed
+const GAP_TIMEOUT: Duration = Duration::from_secs(300);
+/// at most this many missing ids are tracked, the oldest ones are dropped first
+const MAX_GAPS: usize = 1000;
+
+/// Position in the reservation events. The ids are taken when an event is inserted but it's
+/// visible once the transaction commits, so an event with a lower id could show up after the
+/// higher ones. The skipped ids are kept and checked again, until they are too old to be in
+/// progress (e.g. the transaction was rolled back).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventCursor {
+    last_id: i64,
+    gaps: BTreeMap<i64, Instant>,
+}
+
+impl EventCursor {
+    /// start after the given id, the given ids before it are not seen yet
+    pub fn new(last_id: i64, missing: impl IntoIterator<Item = i64>) -> Self {
+        let now = Instant::now();
+        let mut cursor = Self {
+            last_id,
+            gaps: missing.into_iter().map(|id| (id, now)).collect(),
+        };
+        cursor.trim(now);
+        cursor
+    }
+
+    /// start after the latest event, the missing ids before it might be transactions in progress
+    pub async fn latest(pool: &PgPool) -> Result<Self, abi::Error> {
+        let last_id: i64 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM reservation_events")
+                .fetch_one(pool)
+                .await?;
+        let sql = "SELECT g.id FROM generate_series($1, $2) AS g(id) WHERE NOT EXISTS (SELECT 1 FROM reservation_events e WHERE e.id = g.id)";
+        let missing: Vec<i64> = sqlx::query_scalar(sql)
+            .bind((last_id - MAX_GAPS as i64).max(1))
+            .bind(last_id)
+            .fetch_all(pool)
+            .await?;
+        Ok(Self::new(last_id, missing))
+    }
+
+    /// the events after this id are not seen yet
+    pub fn last_id(&self) -> i64 {
+        self.last_id
+    }
+
+    /// the ids before last_id which are not seen yet, the expired ones are dropped
+    pub fn gaps(&mut self) -> Vec<i64> {
+        self.trim(Instant::now());
+        self.gaps.keys().copied().collect()
+    }
+
+    /// all the events up to this id are seen or expired, starting over from it misses none
+    pub fn checkpoint(&mut self) -> i64 {
+        self.trim(Instant::now());
+        self.gaps.keys().next().map_or(self.last_id, |id| id - 1)
+    }
+
+    /// mark the event as seen, returns false if it was seen already
+    pub fn advance(&mut self, id: i64) -> bool {
+        if id <= self.last_id {
+            return self.gaps.remove(&id).is_some();
+        }
+        let now = Instant::now();
+        let from = (self.last_id + 1).max(id - MAX_GAPS as i64);
+        self.gaps.extend((from..id).map(|id| (id, now)));
+        self.last_id = id;
+        self.trim(now);
+        true
+    }
+
+    fn trim(&mut self, now: Instant) {
+        self.gaps
+            .retain(|_, since| now.duration_since(*since) < GAP_TIMEOUT);
+        while self.gaps.len() > MAX_GAPS {
+            self.gaps.pop_first();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_should_track_skipped_ids() {
+        let mut cursor = EventCursor::new(3, [2]);
+        assert_eq!(cursor.gaps(), vec![2]);
+
+        assert!(cursor.advance(6));
+        assert_eq!(cursor.last_id(), 6);
+        assert_eq!(cursor.gaps(), vec![2, 4, 5]);
+
+        // committed late
+        assert!(cursor.advance(4));
+        assert!(!cursor.advance(4));
+        assert!(!cursor.advance(1));
+        assert_eq!(cursor.last_id(), 6);
+        assert_eq!(cursor.gaps(), vec![2, 5]);
+        assert_eq!(cursor.checkpoint(), 1);
+
+        assert!(cursor.advance(2));
+        assert!(cursor.advance(5));
+        assert_eq!(cursor.checkpoint(), 6);
+    }
+
+    #[test]
+    fn cursor_should_limit_tracked_ids() {
+        let mut cursor = EventCursor::new(0, []);
+        cursor.advance(5000);
+        let gaps = cursor.gaps();
+        assert_eq!(gaps.len(), MAX_GAPS);
+        assert_eq!(gaps[0], 5000 - MAX_GAPS as i64);
+    }
+}
@@ -1,21 +1,56 @@
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+mod cursor;
+#[cfg(any(test, feature = "in-memory"))]
+mod memory;
+mod metrics;
+mod migrate;
+mod outbox;
 mod store;
+#[cfg(test)]
+mod test_utils;
+mod webhook;
 
 use async_trait::async_trait;
 use sqlx::PgPool;
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
 
-#[derive(Debug)]
+#[cfg(any(test, feature = "in-memory"))]
+pub use memory::InMemoryReservationStore;
+pub use metrics::register_resources;
+pub use migrate::{MigrationStatus, MIGRATOR};
+pub use outbox::{sink_from_config, EventSink, JsonLinesSink, MemorySink, OutboxRelay, StdoutSink};
+pub use webhook::{
+    open_secret, seal_secret, sign, WebhookDispatcher, EVENT_ID_HEADER, SIGNATURE_HEADER,
+};
+
+#[derive(Debug, Clone)]
 pub struct ReservationStore {
     pool: PgPool,
+    context: abi::RequestContext,
+    shutdown: Arc<watch::Sender<bool>>,
 }
 
 #[async_trait]
 pub trait Reservation {
+    /// get a store which records the given caller in the reservation events
+    fn with_context(&self, context: abi::RequestContext) -> Self
+    where
+        Self: Sized;
     /// make a reservation
     async fn reserve(
         &self,
         mut reservation: abi::Reservation,
     ) -> Result<abi::Reservation, abi::Error>;
+    /// save the reservations in one transaction, return the saved ones and the conflicts.
+    /// Nothing is saved in dry run, or in atomic mode if any of them conflicts
+    async fn import(
+        &self,
+        reservations: Vec<abi::Reservation>,
+        dry_run: bool,
+        atomic: bool,
+    ) -> Result<(Vec<abi::Reservation>, Vec<abi::ImportConflict>), abi::Error>;
     /// change reservation status to confirmed if the current status is pending
     async fn confirm(&self, id: i64) -> Result<abi::Reservation, abi::Error>;
     /// update note
@@ -34,4 +69,23 @@ pub trait Reservation {
         &self,
         query: abi::ReservationFilter,
     ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// listen to reservation events happened from now on
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ReservationEvent, abi::Error>>;
+    /// get the change history of a reservation order by event id
+    async fn history(&self, id: i64) -> Result<Vec<abi::ReservationEvent>, abi::Error>;
+    /// query reservation events order by event id
+    async fn events(
+        &self,
+        filter: abi::ReservationEventFilter,
+    ) -> Result<Vec<abi::ReservationEvent>, abi::Error>;
+}
+
+#[async_trait]
+pub trait Webhook {
+    /// register a webhook to receive reservation events
+    async fn subscribe(&self, webhook: abi::Webhook) -> Result<abi::Webhook, abi::Error>;
+    /// remove a webhook
+    async fn unsubscribe(&self, id: i64) -> Result<abi::Webhook, abi::Error>;
+    /// list all webhooks, secrets are not returned
+    async fn list_webhooks(&self) -> Result<Vec<abi::Webhook>, abi::Error>;
 }
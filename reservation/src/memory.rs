@@ -0,0 +1,704 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
+
+use abi::{
+    convert_to_timestamp, convert_to_utc_time, Normalizer, ReservationConflict,
+    ReservationConflictInfo, ReservationEventType, ReservationStatus, ReservationWindow, Validator,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use prost_types::Timestamp;
+use sqlx::postgres::types::PgRange;
+use tokio::sync::{mpsc, watch};
+
+use crate::{metrics, Reservation};
+
+/// a store keeping everything in memory with the same semantics as ReservationStore:
+/// the conflicts, the query/filter results and the events are the same as with postgres
+#[derive(Debug, Clone)]
+pub struct InMemoryReservationStore {
+    state: Arc<Mutex<State>>,
+    /// id of the latest event, the listeners are woken up when it's changed
+    notify: Arc<watch::Sender<i64>>,
+    context: abi::RequestContext,
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    /// like BIGSERIAL the id is taken even if the insert fails
+    last_id: i64,
+    last_event_id: i64,
+    reservations: BTreeMap<i64, Row>,
+    /// start -> id of the reservations of each resource. Like the EXCLUDE constraint
+    /// guarantees, the timespans of a resource never overlap, so they're ordered by end too
+    resources: HashMap<String, BTreeMap<Point, i64>>,
+    events: Vec<abi::ReservationEvent>,
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    reservation: abi::Reservation,
+    start: Point,
+    end: Point,
+}
+
+/// a bound of a timespan as a point to compare. `[t` and `t)` are (t, false), `(t` and
+/// `t]` are (t, true), so every timespan is [start, end) in the points. The unbounded
+/// ones are the min and the max
+type Point = (DateTime<Utc>, bool);
+
+fn points(timespan: &PgRange<DateTime<Utc>>) -> (Point, Point) {
+    let start = match timespan.start {
+        Bound::Included(t) => (t, false),
+        Bound::Excluded(t) => (t, true),
+        Bound::Unbounded => (DateTime::<Utc>::MIN_UTC, false),
+    };
+    let end = match timespan.end {
+        Bound::Included(t) => (t, true),
+        Bound::Excluded(t) => (t, false),
+        Bound::Unbounded => (DateTime::<Utc>::MAX_UTC, true),
+    };
+    (start, end)
+}
+
+impl Default for InMemoryReservationStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryReservationStore {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State::default())),
+            notify: Arc::new(watch::channel(0).0),
+            context: abi::RequestContext::system(),
+        }
+    }
+
+    /// clear the owners the caller couldn't see
+    fn redact(&self, mut reservations: Vec<abi::Reservation>) -> Vec<abi::Reservation> {
+        reservations.iter_mut().for_each(|r| self.context.redact(r));
+        reservations
+    }
+
+    /// clear the owners in the event snapshots the caller couldn't see
+    fn redact_events(&self, mut events: Vec<abi::ReservationEvent>) -> Vec<abi::ReservationEvent> {
+        events.iter_mut().for_each(|e| self.context.redact_event(e));
+        events
+    }
+
+    /// run f on the state, wake up the listeners if any event is added
+    fn transact<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        let last_event_id = state.last_event_id;
+        let result = f(&mut state);
+        if state.last_event_id != last_event_id {
+            self.notify.send_replace(state.last_event_id);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl Reservation for InMemoryReservationStore {
+    fn with_context(&self, context: abi::RequestContext) -> Self {
+        Self {
+            state: self.state.clone(),
+            notify: self.notify.clone(),
+            context,
+        }
+    }
+
+    async fn reserve(
+        &self,
+        mut reservation: abi::Reservation,
+    ) -> Result<abi::Reservation, abi::Error> {
+        reservation.normalize()?;
+        reservation.id = self.transact(|state| state.insert(&reservation, &self.context))?;
+        Ok(reservation)
+    }
+
+    async fn import(
+        &self,
+        mut reservations: Vec<abi::Reservation>,
+        dry_run: bool,
+        atomic: bool,
+    ) -> Result<(Vec<abi::Reservation>, Vec<abi::ImportConflict>), abi::Error> {
+        for reservation in reservations.iter_mut() {
+            reservation.normalize()?;
+        }
+
+        self.transact(|state| {
+            // work on a copy as the transaction, the ids are taken even if it's rolled back
+            let mut tx = state.clone();
+            let mut saved = vec![];
+            let mut conflicts = vec![];
+            for mut reservation in reservations {
+                match tx.insert(&reservation, &self.context) {
+                    Ok(id) => {
+                        reservation.id = id;
+                        saved.push(reservation);
+                    }
+                    Err(abi::Error::ConflictReservation(info)) => {
+                        conflicts.push(abi::ImportConflict {
+                            reservation: Some(reservation),
+                            reason: info.to_string(),
+                        });
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if dry_run {
+                state.last_id = tx.last_id;
+                saved.iter_mut().for_each(|r| r.id = 0);
+            } else if atomic && !conflicts.is_empty() {
+                state.last_id = tx.last_id;
+                saved.clear();
+            } else {
+                *state = tx;
+            }
+            Ok((saved, conflicts))
+        })
+    }
+
+    async fn confirm(&self, id: i64) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        self.transact(|state| {
+            state.modify(id, &self.context, |r| {
+                if r.status != ReservationStatus::Pending as i32 {
+                    return Err(abi::Error::NotFound);
+                }
+                r.status = ReservationStatus::Confirmed as i32;
+                Ok(())
+            })
+        })
+    }
+
+    async fn update(&self, id: i64, note: String) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        self.transact(|state| {
+            state.modify(id, &self.context, |r| {
+                r.note = note;
+                Ok(())
+            })
+        })
+    }
+
+    async fn delete(&self, id: i64) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        self.transact(|state| state.delete(id, &self.context))
+    }
+
+    async fn get(&self, id: i64) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let state = self.state.lock().unwrap();
+        let mut reservation = state
+            .reservations
+            .get(&id)
+            .map(|row| row.reservation.clone())
+            .ok_or(abi::Error::NotFound)?;
+        self.context.redact(&mut reservation);
+        Ok(reservation)
+    }
+
+    async fn query(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, abi::Error>> {
+        let reservations = match query.validate() {
+            Ok(_) => Ok(self.redact(self.state.lock().unwrap().query(&query))),
+            Err(err) => Err(err),
+        };
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            let reservations = match reservations {
+                Ok(reservations) => reservations,
+                Err(err) => {
+                    let _ = tx.send(Err(err)).await;
+                    return;
+                }
+            };
+            for reservation in reservations {
+                if tx.send(Ok(reservation)).await.is_err() {
+                    // rx is dropped, stop the loop
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    async fn filter(
+        &self,
+        mut filter: abi::ReservationFilter,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        filter.normalize()?;
+        Ok(self.redact(self.state.lock().unwrap().filter(&filter)))
+    }
+
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ReservationEvent, abi::Error>> {
+        let (tx, rx) = mpsc::channel(64);
+        let mut notify = self.notify.subscribe();
+        let state = self.state.clone();
+        let context = self.context.clone();
+        // only the events happened after listening are sent
+        let mut last_id = *notify.borrow_and_update();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    changed = notify.changed() => {
+                        if changed.is_err() {
+                            // all the stores are dropped
+                            return;
+                        }
+                    }
+                    // rx is dropped, stop listening
+                    _ = tx.closed() => return,
+                }
+
+                let events: Vec<_> = state
+                    .lock()
+                    .unwrap()
+                    .events
+                    .iter()
+                    .filter(|e| e.id > last_id)
+                    .cloned()
+                    .collect();
+                for mut event in events {
+                    last_id = event.id;
+                    context.redact_event(&mut event);
+                    if tx.send(Ok(event)).await.is_err() {
+                        // rx is dropped, stop the loop
+                        return;
+                    }
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn history(&self, id: i64) -> Result<Vec<abi::ReservationEvent>, abi::Error> {
+        id.validate()?;
+        let state = self.state.lock().unwrap();
+        let events = state.events.iter().filter(|e| e.reservation_id == id);
+        Ok(self.redact_events(events.cloned().collect()))
+    }
+
+    async fn events(
+        &self,
+        filter: abi::ReservationEventFilter,
+    ) -> Result<Vec<abi::ReservationEvent>, abi::Error> {
+        filter.validate()?;
+        Ok(self.redact_events(self.state.lock().unwrap().events(&filter)))
+    }
+}
+
+impl State {
+    fn insert(
+        &mut self,
+        reservation: &abi::Reservation,
+        context: &abi::RequestContext,
+    ) -> Result<i64, abi::Error> {
+        self.last_id += 1;
+        let id = self.last_id;
+        // postgres keeps microseconds, sqlx drops the rest
+        let truncate = |ts: Option<&Timestamp>, field| match ts {
+            Some(_) => convert_to_utc_time(ts, field)
+                .map(|time| Some(convert_to_timestamp(time.trunc_subsecs(6)))),
+            None => Ok(None),
+        };
+        let reservation = abi::Reservation {
+            id,
+            start: truncate(reservation.start.as_ref(), "start")?,
+            end: truncate(reservation.end.as_ref(), "end")?,
+            ..reservation.clone()
+        };
+        let (start, end) = points(&reservation.get_timespan()?);
+
+        let existing = self.conflicts(&reservation.resource_id, start, end);
+        if let Some(old) = existing.first() {
+            metrics::record_conflict(&reservation.resource_id);
+            let conflict = ReservationConflict {
+                new: ReservationWindow::try_from(&reservation)?,
+                old: ReservationWindow::try_from(&old.reservation)?,
+                existing: vec![],
+            };
+            let existing = existing.iter().map(|row| row.reservation.clone()).collect();
+            let info = ReservationConflictInfo::from(conflict).with_existing(existing, context);
+            return Err(abi::Error::ConflictReservation(info));
+        }
+
+        self.resources
+            .entry(reservation.resource_id.clone())
+            .or_default()
+            .insert(start, id);
+        let status =
+            ReservationStatus::from_i32(reservation.status).unwrap_or(ReservationStatus::Pending);
+        let row = Row {
+            reservation: abi::Reservation {
+                status: status as i32,
+                ..reservation
+            },
+            start,
+            end,
+        };
+        self.add_event(
+            ReservationEventType::Created,
+            None,
+            Some(row.reservation.clone()),
+            context,
+        );
+        self.reservations.insert(id, row);
+        Ok(id)
+    }
+
+    /// the timespans are [start, end) in points, since the existing ones don't overlap each
+    /// other, the ones overlapping are the last ones starting before the new end, ordered by start
+    fn conflicts(&self, rid: &str, start: Point, end: Point) -> Vec<&Row> {
+        let Some(resource) = self.resources.get(rid) else {
+            return vec![];
+        };
+        let mut rows: Vec<_> = resource
+            .range(..end)
+            .rev()
+            .map(|(_, id)| &self.reservations[id])
+            .take_while(|row| row.end > start)
+            .collect();
+        rows.reverse();
+        rows
+    }
+
+    /// change the reservation, an event is added only if anything is changed
+    fn modify(
+        &mut self,
+        id: i64,
+        context: &abi::RequestContext,
+        f: impl FnOnce(&mut abi::Reservation) -> Result<(), abi::Error>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let row = self.reservations.get_mut(&id).ok_or(abi::Error::NotFound)?;
+        let old = row.reservation.clone();
+        f(&mut row.reservation)?;
+        let new = row.reservation.clone();
+        if old != new {
+            self.add_event(
+                ReservationEventType::Updated,
+                Some(old),
+                Some(new.clone()),
+                context,
+            );
+        }
+        Ok(new)
+    }
+
+    fn delete(
+        &mut self,
+        id: i64,
+        context: &abi::RequestContext,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let row = self.reservations.remove(&id).ok_or(abi::Error::NotFound)?;
+        if let Some(resource) = self.resources.get_mut(&row.reservation.resource_id) {
+            resource.remove(&row.start);
+        }
+        self.add_event(
+            ReservationEventType::Deleted,
+            Some(row.reservation.clone()),
+            None,
+            context,
+        );
+        Ok(row.reservation)
+    }
+
+    fn add_event(
+        &mut self,
+        event_type: ReservationEventType,
+        old: Option<abi::Reservation>,
+        new: Option<abi::Reservation>,
+        context: &abi::RequestContext,
+    ) {
+        self.last_event_id += 1;
+        let reservation_id = new.as_ref().or(old.as_ref()).map_or(0, |r| r.id);
+        self.events.push(abi::ReservationEvent {
+            id: self.last_event_id,
+            reservation_id,
+            event_type: event_type as i32,
+            old,
+            new,
+            created_at: Some(convert_to_timestamp(Utc::now())),
+            actor_id: context.actor_id.clone(),
+            request_id: context.request_id.clone(),
+        });
+    }
+
+    /// same as ReservationQuery::to_sql: the range of the query contains the timespan
+    fn query(&self, query: &abi::ReservationQuery) -> Vec<abi::Reservation> {
+        // the query is validated, only the missing times are None
+        let (start, end) = query.get_range().unwrap_or_default();
+        let (start, end) = points(&PgRange {
+            start: start.map_or(Bound::Unbounded, Bound::Included),
+            end: end.map_or(Bound::Unbounded, Bound::Excluded),
+        });
+        let mut rows: Vec<&Row> = self
+            .reservations
+            .values()
+            .filter(|row| {
+                let r = &row.reservation;
+                r.status == query.status
+                    && start <= row.start
+                    && row.end <= end
+                    && matches(&query.user_id, &r.user_id)
+                    && matches(&query.resource_id, &r.resource_id)
+            })
+            .collect();
+        rows.sort_by_key(|row| row.start);
+        if query.desc {
+            rows.reverse();
+        }
+        rows.into_iter()
+            .map(|row| row.reservation.clone())
+            .collect()
+    }
+
+    /// same as ReservationFilter::to_sql
+    fn filter(&self, filter: &abi::ReservationFilter) -> Vec<abi::Reservation> {
+        let cursor = filter.get_cursor();
+        let matched = |row: &&Row| {
+            let r = &row.reservation;
+            r.status == filter.status
+                && matches(&filter.user_id, &r.user_id)
+                && matches(&filter.resource_id, &r.resource_id)
+        };
+        let page_size = filter.page_size as usize;
+        let rows: Vec<&Row> = if filter.desc {
+            let rows = self.reservations.range(..=cursor).rev().map(|(_, row)| row);
+            rows.filter(matched).take(page_size).collect()
+        } else {
+            let rows = self.reservations.range(cursor..).map(|(_, row)| row);
+            rows.filter(matched).take(page_size).collect()
+        };
+        rows.into_iter()
+            .map(|row| row.reservation.clone())
+            .collect()
+    }
+
+    /// same as ReservationEventFilter::to_query, the user and resource are taken from the
+    /// new snapshot, or the old one if the reservation is deleted
+    fn events(&self, filter: &abi::ReservationEventFilter) -> Vec<abi::ReservationEvent> {
+        let cursor = filter.get_cursor();
+        let event_type = filter.get_event_type();
+        let matched = |e: &&abi::ReservationEvent| {
+            let snapshot = e.new.as_ref().or(e.old.as_ref());
+            let field = |f: fn(&abi::Reservation) -> &str| snapshot.map(f).unwrap_or_default();
+            (if filter.desc {
+                e.id <= cursor
+            } else {
+                e.id >= cursor
+            }) && (filter.reservation_id == 0 || e.reservation_id == filter.reservation_id)
+                && (event_type == ReservationEventType::Unknown
+                    || e.event_type == event_type as i32)
+                && matches(&filter.user_id, field(|r| &r.user_id))
+                && matches(&filter.resource_id, field(|r| &r.resource_id))
+        };
+        let page_size = filter.page_size as usize;
+        if filter.desc {
+            let events = self.events.iter().rev().filter(matched);
+            events.take(page_size).cloned().collect()
+        } else {
+            let events = self.events.iter().filter(matched);
+            events.take(page_size).cloned().collect()
+        }
+    }
+}
+
+/// an empty condition matches everything
+fn matches(condition: &str, value: &str) -> bool {
+    condition.is_empty() || condition == value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{ReservationFilterBuilder, ReservationQueryBuilder};
+
+    fn make_reservation(uid: &str, rid: &str, start: &str, end: &str) -> abi::Reservation {
+        abi::Reservation::new(
+            uid,
+            rid,
+            start.parse().unwrap(),
+            end.parse().unwrap(),
+            "",
+            ReservationStatus::Pending,
+        )
+    }
+
+    #[tokio::test]
+    async fn conflict_should_be_same_as_postgres() {
+        let store = InMemoryReservationStore::new();
+        let r1 = make_reservation(
+            "alon",
+            "ocean-view-room-711",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T15:00:00-0700",
+        );
+        let r1 = store.reserve(r1).await.unwrap();
+        // adjacent ranges don't overlap
+        let r2 = make_reservation(
+            "tyr",
+            "ocean-view-room-711",
+            "2022-12-28T15:00:00-0700",
+            "2022-12-30T15:00:00-0700",
+        );
+        store.reserve(r2).await.unwrap();
+
+        let r3 = make_reservation(
+            "alon",
+            "ocean-view-room-711",
+            "2022-12-26T15:00:00-0700",
+            "2022-12-27T15:00:00-0700",
+        );
+        let err = store.reserve(r3).await.unwrap_err();
+        // the detail postgres reports for the same conflict
+        let detail = "Key (resource_id, timespan)=(ocean-view-room-711, [\"2022-12-26 22:00:00+00\",\"2022-12-27 22:00:00+00\")) conflicts with existing key (resource_id, timespan)=(ocean-view-room-711, [\"2022-12-25 22:00:00+00\",\"2022-12-28 22:00:00+00\")).";
+        let info = detail
+            .parse::<ReservationConflictInfo>()
+            .unwrap()
+            .with_existing(vec![r1], &abi::RequestContext::system());
+        assert_eq!(err, abi::Error::ConflictReservation(info));
+
+        // the failed insert takes an id, like the postgres sequence
+        let r4 = make_reservation(
+            "alon",
+            "ocean-view-room-712",
+            "2022-12-26T15:00:00-0700",
+            "2022-12-27T15:00:00-0700",
+        );
+        assert_eq!(store.reserve(r4).await.unwrap().id, 4);
+    }
+
+    #[tokio::test]
+    async fn query_and_filter_should_be_same_as_postgres() {
+        let store = InMemoryReservationStore::new();
+        for (uid, start, end) in [
+            ("alon", "2023-01-05T00:00:00Z", "2023-01-06T00:00:00Z"),
+            ("alon", "2023-01-01T00:00:00Z", "2023-01-02T00:00:00Z"),
+            ("tyr", "2023-01-03T00:00:00Z", "2023-01-04T00:00:00Z"),
+            ("alon", "2023-01-10T00:00:00Z", "2023-01-20T00:00:00Z"),
+        ] {
+            store
+                .reserve(make_reservation(uid, "room", start, end))
+                .await
+                .unwrap();
+        }
+        store.confirm(4).await.unwrap();
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("alon")
+            .start_time("2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
+            .end_time("2023-01-15T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
+            .desc(true)
+            .build()
+            .unwrap();
+        let mut rx = store.query(query).await;
+        let mut ids = vec![];
+        while let Some(r) = rx.recv().await {
+            ids.push(r.unwrap().id);
+        }
+        // 4 is confirmed and not contained in the range
+        assert_eq!(ids, vec![1, 2]);
+
+        let filter = ReservationFilterBuilder::default()
+            .cursor(2)
+            .build()
+            .unwrap();
+        let ids: Vec<_> = store
+            .filter(filter)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn import_should_keep_sequence_after_rollback() {
+        let store = InMemoryReservationStore::new();
+        let existing = make_reservation(
+            "alon",
+            "room",
+            "2023-01-01T00:00:00Z",
+            "2023-01-05T00:00:00Z",
+        );
+        store.reserve(existing).await.unwrap();
+        let reservations = vec![
+            make_reservation(
+                "alice",
+                "room",
+                "2023-01-10T00:00:00Z",
+                "2023-01-12T00:00:00Z",
+            ),
+            make_reservation(
+                "alice",
+                "room",
+                "2023-01-02T00:00:00Z",
+                "2023-01-03T00:00:00Z",
+            ),
+        ];
+
+        let (saved, conflicts) = store
+            .import(reservations.clone(), false, true)
+            .await
+            .unwrap();
+        assert!(saved.is_empty());
+        assert_eq!(conflicts.len(), 1);
+
+        let (saved, conflicts) = store.import(reservations, false, false).await.unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(saved[0].id, 4);
+        assert_eq!(store.get(4).await.unwrap().user_id, "alice");
+    }
+
+    #[tokio::test]
+    async fn events_should_be_recorded_and_sent() {
+        let store = InMemoryReservationStore::new()
+            .with_context(abi::RequestContext::new("support", "req-1"));
+        let mut rx = store.listen().await;
+
+        let r = make_reservation(
+            "alon",
+            "room",
+            "2023-01-01T00:00:00Z",
+            "2023-01-05T00:00:00Z",
+        );
+        let r = store.reserve(r).await.unwrap();
+        // nothing changed, no event
+        store.update(r.id, "".to_string()).await.unwrap();
+        store.update(r.id, "late".to_string()).await.unwrap();
+        store.delete(r.id).await.unwrap();
+        assert_eq!(store.get(r.id).await.unwrap_err(), abi::Error::NotFound);
+
+        let history = store.history(r.id).await.unwrap();
+        let types: Vec<_> = history.iter().map(|e| e.get_event_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                ReservationEventType::Created,
+                ReservationEventType::Updated,
+                ReservationEventType::Deleted
+            ]
+        );
+        assert_eq!(history[2].actor_id, "support");
+        assert_eq!(history[1].new.as_ref().unwrap().note, "late");
+
+        for event in history {
+            assert_eq!(rx.recv().await.unwrap().unwrap(), event);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use once_cell::sync::Lazy;
+use std::{collections::HashSet, sync::RwLock};
+
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    HistogramTimer, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+};
+
+/// latency of the store methods, including the database round trips
+static STORE_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "reservation_store_duration_seconds",
+        "Latency of the reservation store methods",
+        &["method"]
+    )
+    .unwrap()
+});
+
+/// rejected reservations because of an overlapping one
+static CONFLICTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "reservation_conflicts_total",
+        "Reservations rejected because of conflicts",
+        &["resource_id"]
+    )
+    .unwrap()
+});
+
+/// resources counted by their id in the conflicts, the others are counted as "other" so
+/// that the callers can't make up label values
+static CONFLICT_RESOURCES: Lazy<RwLock<HashSet<String>>> = Lazy::new(Default::default);
+
+/// listen subscribers which are still receiving events
+static LISTEN_SUBSCRIBERS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "reservation_listen_subscribers",
+        "Active subscribers of the reservation events"
+    )
+    .unwrap()
+});
+
+/// connections of the PgPool, updated on each scrape
+static DB_POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "reservation_db_pool_connections",
+        "Connections of the database pool",
+        &["state"]
+    )
+    .unwrap()
+});
+
+/// the latency is recorded when the timer is dropped
+pub(crate) fn observe(method: &str) -> HistogramTimer {
+    STORE_DURATION.with_label_values(&[method]).start_timer()
+}
+
+/// count the conflicts of these resources by their id, usually the configured resources
+pub fn register_resources(resource_ids: impl IntoIterator<Item = String>) {
+    CONFLICT_RESOURCES.write().unwrap().extend(resource_ids);
+}
+
+fn resource_label(resource_id: &str) -> &str {
+    if CONFLICT_RESOURCES.read().unwrap().contains(resource_id) {
+        resource_id
+    } else {
+        "other"
+    }
+}
+
+pub(crate) fn record_conflict(resource_id: &str) {
+    CONFLICTS
+        .with_label_values(&[resource_label(resource_id)])
+        .inc();
+}
+
+/// counts the listen subscriber as long as the guard is alive
+pub(crate) struct SubscriberGuard;
+
+impl SubscriberGuard {
+    pub(crate) fn new() -> Self {
+        LISTEN_SUBSCRIBERS.inc();
+        Self
+    }
+}
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        LISTEN_SUBSCRIBERS.dec();
+    }
+}
+
+pub(crate) fn record_pool(size: u32, idle: usize) {
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["idle"])
+        .set(idle as i64);
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["active"])
+        .set(size as i64 - idle as i64);
+}
+
+#[cfg(test)]
+pub(crate) fn conflicts(resource_id: &str) -> u64 {
+    CONFLICTS.with_label_values(&[resource_id]).get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts_of_unknown_resources_should_be_other() {
+        register_resources(["metrics-room-1".to_string()]);
+        let (known, other) = (conflicts("metrics-room-1"), conflicts("other"));
+        record_conflict("metrics-room-1");
+        record_conflict("metrics-room-unknown");
+        assert_eq!(conflicts("metrics-room-1"), known + 1);
+        assert!(conflicts("other") > other);
+        assert_eq!(conflicts("metrics-room-unknown"), 0);
+    }
+}
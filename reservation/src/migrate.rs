@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use sqlx::migrate::{AppliedMigration, Migrate, Migrator};
+
+use crate::ReservationStore;
+
+/// the migrations in /migrations, embedded in the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+/// an embedded migration and whether it's applied to the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+impl ReservationStore {
+    /// apply the pending migrations
+    pub async fn migrate_up(&self) -> Result<(), abi::Error> {
+        MIGRATOR.run(&self.pool).await.map_err(sqlx::Error::from)?;
+        Ok(())
+    }
+
+    /// revert the latest applied migration, return its version if there's any
+    pub async fn migrate_down(&self) -> Result<Option<i64>, abi::Error> {
+        let mut versions: Vec<i64> = self.applied_migrations().await?.into_keys().collect();
+        versions.sort_unstable();
+        let latest = match versions.pop() {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+        let target = versions.pop().unwrap_or_default();
+        MIGRATOR
+            .undo(&self.pool, target)
+            .await
+            .map_err(sqlx::Error::from)?;
+        Ok(Some(latest))
+    }
+
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>, abi::Error> {
+        let applied = self.applied_migrations().await?;
+        Ok(MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                applied: applied.contains_key(&m.version),
+            })
+            .collect())
+    }
+
+    /// fail unless exactly the embedded migrations are applied, with the same content
+    pub async fn check_schema(&self) -> Result<(), abi::Error> {
+        let applied = self.applied_migrations().await?;
+        let embedded: Vec<_> = MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .collect();
+        let matched = applied.len() == embedded.len()
+            && embedded.iter().all(|m| {
+                applied
+                    .get(&m.version)
+                    .is_some_and(|a| a.checksum == m.checksum)
+            });
+        if matched {
+            return Ok(());
+        }
+        Err(abi::Error::SchemaMismatch {
+            expected: embedded.iter().map(|m| m.version).max().unwrap_or_default(),
+            actual: applied.keys().copied().max().unwrap_or_default(),
+        })
+    }
+
+    /// the migrations table is not created if it doesn't exist
+    async fn applied_migrations(&self) -> Result<HashMap<i64, AppliedMigration>, abi::Error> {
+        let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        if !exists {
+            return Ok(HashMap::new());
+        }
+        let mut conn = self.pool.acquire().await?;
+        let applied = conn
+            .list_applied_migrations()
+            .await
+            .map_err(sqlx::Error::from)?;
+        Ok(applied.into_iter().map(|m| (m.version, m)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::init_db;
+
+    #[tokio::test]
+    async fn migrations_should_be_reverted_and_applied() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        store.check_schema().await.unwrap();
+        let status = store.migration_status().await.unwrap();
+        assert!(status.iter().all(|m| m.applied));
+        let latest = status.last().unwrap().version;
+
+        assert_eq!(store.migrate_down().await.unwrap(), Some(latest));
+        let status = store.migration_status().await.unwrap();
+        assert!(!status.last().unwrap().applied);
+        assert!(status[..status.len() - 1].iter().all(|m| m.applied));
+        match store.check_schema().await.unwrap_err() {
+            abi::Error::SchemaMismatch { expected, actual } => {
+                assert_eq!(expected, latest);
+                assert!(actual < latest);
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+
+        store.migrate_up().await.unwrap();
+        store.check_schema().await.unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use crate::{cursor::EventCursor, ReservationStore};
+use abi::SinkConfig;
+use async_trait::async_trait;
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+use tracing::warn;
+
+/// max number of events relayed in one batch
+const BATCH_SIZE: i64 = 100;
+/// backoff before retrying on database errors, doubled for each retry
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// upper limit of the backoff on database errors
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Destination of the reservation events. Implement it to publish events to a message broker.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// publish an event, the event is marked as delivered once it returns Ok
+    async fn publish(&self, event: &abi::ReservationEvent) -> Result<(), abi::Error>;
+}
+
+/// print events to stdout as JSON lines
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+/// append events to a file as JSON lines
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    file: Mutex<File>,
+}
+
+/// keep events in memory, useful for tests
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    events: std::sync::Mutex<Vec<abi::ReservationEvent>>,
+}
+
+/// Relays the reservation events to an event sink in order of the event id. An event is
+/// marked as delivered by moving the relay's read cursor after it is published, so events
+/// are delivered at least once. The events committed late are published once they show up,
+/// the stored cursor stays before them so a restarted relay doesn't miss them.
+pub struct OutboxRelay {
+    pool: PgPool,
+    name: String,
+    sink: Arc<dyn EventSink>,
+    cursor: Mutex<Option<EventCursor>>,
+}
+
+#[async_trait]
+impl EventSink for StdoutSink {
+    async fn publish(&self, event: &abi::ReservationEvent) -> Result<(), abi::Error> {
+        println!("{}", to_json(event)?);
+        Ok(())
+    }
+}
+
+impl JsonLinesSink {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, abi::Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|e| abi::Error::EventSinkError(e.to_string()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for JsonLinesSink {
+    async fn publish(&self, event: &abi::ReservationEvent) -> Result<(), abi::Error> {
+        let line = format!("{}\n", to_json(event)?);
+        let mut file = self.file.lock().await;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| abi::Error::EventSinkError(e.to_string()))?;
+        file.flush()
+            .await
+            .map_err(|e| abi::Error::EventSinkError(e.to_string()))
+    }
+}
+
+impl MemorySink {
+    /// all the published events
+    pub fn events(&self) -> Vec<abi::ReservationEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl EventSink for MemorySink {
+    async fn publish(&self, event: &abi::ReservationEvent) -> Result<(), abi::Error> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+fn to_json(event: &abi::ReservationEvent) -> Result<String, abi::Error> {
+    serde_json::to_string(event).map_err(|e| abi::Error::EventSinkError(e.to_string()))
+}
+
+/// build the event sink from config
+pub async fn sink_from_config(config: &SinkConfig) -> Result<Arc<dyn EventSink>, abi::Error> {
+    Ok(match config {
+        SinkConfig::Stdout => Arc::new(StdoutSink),
+        SinkConfig::File { path } => Arc::new(JsonLinesSink::open(path).await?),
+    })
+}
+
+impl ReservationStore {
+    /// get a relay which publishes the reservation events to the sink, the name identifies
+    /// the read cursor of the relay
+    pub fn outbox_relay(&self, name: impl Into<String>, sink: Arc<dyn EventSink>) -> OutboxRelay {
+        OutboxRelay {
+            pool: self.pool.clone(),
+            name: name.into(),
+            sink,
+            cursor: Mutex::new(None),
+        }
+    }
+}
+
+impl OutboxRelay {
+    /// keep relaying the events, the database errors are retried with backoff so it never returns
+    pub async fn run(self, poll_interval: Duration) {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            if let Err(err) = self.relay_notified(poll_interval, &mut backoff).await {
+                warn!(
+                    "Outbox relay {} failed, retry in {:?}: {}",
+                    self.name, backoff, err
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    /// relay the events on notification, only returns on database errors. The backoff is reset
+    /// once the database is reachable again
+    async fn relay_notified(
+        &self,
+        poll_interval: Duration,
+        backoff: &mut Duration,
+    ) -> Result<(), abi::Error> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen("reservation_event").await?;
+        self.init().await?;
+
+        loop {
+            loop {
+                match self.relay().await {
+                    Ok(0) => break,
+                    Ok(_) => continue,
+                    Err(abi::Error::EventSinkError(err)) => {
+                        // the undelivered events will be retried
+                        warn!("Outbox relay {} failed to publish: {}", self.name, err);
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            *backoff = INITIAL_BACKOFF;
+            // wait for the notification of new events, poll anyway in case of missed ones
+            if let Ok(notification) = tokio::time::timeout(poll_interval, listener.recv()).await {
+                notification?;
+            }
+        }
+    }
+
+    /// create the read cursor if not exists, all the recorded events will be relayed
+    pub async fn init(&self) -> Result<(), abi::Error> {
+        let sql = "INSERT INTO server_read_cursor (server_id, last_change_id) VALUES ($1, 0) ON CONFLICT (server_id) DO NOTHING";
+        sqlx::query(sql)
+            .bind(&self.name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// relay a batch of events, return the number of events delivered. No transaction is kept
+    /// open while publishing the events
+    pub async fn relay(&self) -> Result<usize, abi::Error> {
+        let mut cursor = self.cursor.lock().await;
+        let cursor = match cursor.as_mut() {
+            Some(cursor) => cursor,
+            None => {
+                let sql = "SELECT last_change_id FROM server_read_cursor WHERE server_id = $1";
+                let last_id: i64 = sqlx::query_scalar(sql)
+                    .bind(&self.name)
+                    .fetch_one(&self.pool)
+                    .await?;
+                cursor.insert(EventCursor::new(last_id, []))
+            }
+        };
+
+        let sql = abi::ReservationEvent::select_sql(
+            "e.id > $1 OR e.id = ANY($2)",
+            "ASC",
+            Some(BATCH_SIZE),
+        );
+        let events: Vec<abi::ReservationEvent> = sqlx::query_as(&sql)
+            .bind(cursor.last_id())
+            .bind(cursor.gaps())
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut delivered = 0;
+        let mut result = Ok(());
+        for event in events.iter() {
+            if let Err(err) = self.sink.publish(event).await {
+                result = Err(err);
+                break;
+            }
+            cursor.advance(event.id);
+            delivered += 1;
+        }
+
+        // mark the published events as delivered
+        if delivered > 0 {
+            let sql = "UPDATE server_read_cursor SET last_change_id = $1 WHERE server_id = $2";
+            sqlx::query(sql)
+                .bind(cursor.checkpoint())
+                .bind(&self.name)
+                .execute(&self.pool)
+                .await?;
+        }
+        result.map(|_| delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{
+        test_utils::{alon_reservation, init_db, insert_reservation},
+        Reservation,
+    };
+    use abi::ReservationEventType;
+
+    /// fails once, then delegates to the memory sink
+    #[derive(Default)]
+    struct FlakySink {
+        failed: AtomicBool,
+        inner: MemorySink,
+    }
+
+    #[async_trait]
+    impl EventSink for FlakySink {
+        async fn publish(&self, event: &abi::ReservationEvent) -> Result<(), abi::Error> {
+            if event.get_event_type() == ReservationEventType::Deleted
+                && !self.failed.swap(true, Ordering::SeqCst)
+            {
+                return Err(abi::Error::EventSinkError("broker is down".into()));
+            }
+            self.inner.publish(event).await
+        }
+    }
+
+    #[tokio::test]
+    async fn relay_should_publish_events_in_order() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        let reservation = store.reserve(alon_reservation()).await.unwrap();
+        store.confirm(reservation.id).await.unwrap();
+
+        let sink = Arc::new(MemorySink::default());
+        let relay = store.outbox_relay("test-relay", sink.clone());
+        relay.init().await.unwrap();
+        assert_eq!(relay.relay().await.unwrap(), 2);
+        assert_eq!(relay.relay().await.unwrap(), 0);
+
+        let events = sink.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].get_event_type(), ReservationEventType::Created);
+        assert_eq!(events[1].get_event_type(), ReservationEventType::Updated);
+        assert!(events[0].id < events[1].id);
+    }
+
+    #[tokio::test]
+    async fn relay_should_retry_undelivered_events() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        let reservation = store.reserve(alon_reservation()).await.unwrap();
+        store.delete(reservation.id).await.unwrap();
+
+        let sink = Arc::new(FlakySink::default());
+        let relay = store.outbox_relay("test-relay", sink.clone());
+        relay.init().await.unwrap();
+        let err = relay.relay().await.unwrap_err();
+        assert_eq!(err, abi::Error::EventSinkError("broker is down".into()));
+        assert_eq!(sink.inner.events().len(), 1);
+
+        // the created event is delivered, only the deleted event is published again
+        assert_eq!(relay.relay().await.unwrap(), 1);
+        let events = sink.inner.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].get_event_type(), ReservationEventType::Deleted);
+    }
+
+    #[tokio::test]
+    async fn relay_should_publish_events_committed_late() {
+        let db = init_db();
+        let pool = db.get_pool().await;
+        let store = ReservationStore::new(pool.clone());
+        let sink = Arc::new(MemorySink::default());
+        let relay = store.outbox_relay("test-relay", sink.clone());
+        relay.init().await.unwrap();
+
+        // the first event id is taken by a transaction committing after the second one
+        let mut late = pool.begin().await.unwrap();
+        insert_reservation(&mut late, "late", 1).await;
+        insert_reservation(&pool, "early", 2).await;
+        assert_eq!(relay.relay().await.unwrap(), 1);
+
+        late.commit().await.unwrap();
+        assert_eq!(relay.relay().await.unwrap(), 1);
+        let events = sink.events();
+        assert_eq!(events[0].new.as_ref().unwrap().user_id, "early");
+        assert_eq!(events[1].new.as_ref().unwrap().user_id, "late");
+        assert!(events[1].id < events[0].id);
+
+        // a restarted relay starts before the late event until it's published
+        let sink = Arc::new(MemorySink::default());
+        let relay = store.outbox_relay("test-relay", sink.clone());
+        assert_eq!(relay.relay().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn json_lines_sink_should_append_events() {
+        let path = std::env::temp_dir().join(format!("events-{}.jsonl", std::process::id()));
+        let sink = JsonLinesSink::open(&path).await.unwrap();
+        let event = abi::ReservationEvent {
+            id: 1,
+            reservation_id: 42,
+            event_type: ReservationEventType::Created as i32,
+            new: Some(alon_reservation()),
+            ..Default::default()
+        };
+        sink.publish(&event).await.unwrap();
+        sink.publish(&event).await.unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let result: abi::ReservationEvent = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(result, event);
+    }
+}
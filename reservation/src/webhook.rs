@@ -0,0 +1,617 @@
+use std::time::Duration;
+
+use crate::{cursor::EventCursor, ReservationStore, Webhook};
+use abi::{Validator, WebhookConfig};
+use async_trait::async_trait;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use sha2::Sha256;
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// http header of the payload signature, in the form of `sha256=<hex digest>`
+pub const SIGNATURE_HEADER: &str = "x-reservation-signature";
+/// http header of the reservation event id
+pub const EVENT_ID_HEADER: &str = "x-reservation-event-id";
+
+/// prefix of the webhook secrets encrypted with the secret key
+const SEALED_PREFIX: &str = "sealed:";
+
+/// server id of the dispatcher in server_read_cursor
+const DISPATCHER_ID: &str = "webhook-dispatcher";
+/// max number of events delivered in one batch
+const BATCH_SIZE: i64 = 100;
+/// backoff before retrying on database errors, doubled for each retry
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// upper limit of the backoff on database errors
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Delivers reservation events to the webhooks, events are delivered in order of the event id
+pub struct WebhookDispatcher {
+    pool: PgPool,
+    client: reqwest::Client,
+    config: WebhookConfig,
+    /// the queued events and the stored cursor it was saved as
+    cursor: Mutex<Option<(EventCursor, i64)>>,
+}
+
+/// an event queued for a webhook
+struct Delivery {
+    id: i64,
+    event_id: i64,
+    payload: String,
+    webhook: abi::Webhook,
+}
+
+#[async_trait]
+impl Webhook for ReservationStore {
+    async fn subscribe(&self, mut webhook: abi::Webhook) -> Result<abi::Webhook, abi::Error> {
+        webhook.validate()?;
+
+        let sql = "INSERT INTO webhooks (url, secret) VALUES ($1, $2) RETURNING id";
+        let id = sqlx::query_scalar(sql)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .fetch_one(&self.pool)
+            .await?;
+        webhook.id = id;
+        Ok(webhook)
+    }
+
+    async fn unsubscribe(&self, id: i64) -> Result<abi::Webhook, abi::Error> {
+        id.validate()?;
+
+        let sql = "DELETE FROM webhooks WHERE id = $1 RETURNING id, url, '' AS secret";
+        let webhook = sqlx::query_as(sql).bind(id).fetch_one(&self.pool).await?;
+        Ok(webhook)
+    }
+
+    async fn list_webhooks(&self) -> Result<Vec<abi::Webhook>, abi::Error> {
+        let sql = "SELECT id, url, '' AS secret FROM webhooks ORDER BY id";
+        let webhooks = sqlx::query_as(sql).fetch_all(&self.pool).await?;
+        Ok(webhooks)
+    }
+}
+
+impl ReservationStore {
+    pub fn webhook_dispatcher(&self, config: &WebhookConfig) -> WebhookDispatcher {
+        WebhookDispatcher::new(self.pool.clone(), config.clone())
+    }
+}
+
+impl WebhookDispatcher {
+    pub fn new(pool: PgPool, config: WebhookConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .expect("failed to build http client");
+        Self {
+            pool,
+            client,
+            config,
+            cursor: Mutex::new(None),
+        }
+    }
+
+    /// keep delivering the new events, the database errors are retried with backoff so it never
+    /// returns
+    pub async fn run(self) {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            if let Err(err) = self.dispatch_notified(&mut backoff).await {
+                warn!("Webhook dispatcher failed, retry in {:?}: {}", backoff, err);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    /// deliver the events on notification, only returns on database errors. The backoff is reset
+    /// once the database is reachable again
+    async fn dispatch_notified(&self, backoff: &mut Duration) -> Result<(), abi::Error> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen("reservation_event").await?;
+        self.init().await?;
+
+        let interval = Duration::from_millis(self.config.poll_interval_ms);
+        loop {
+            while self.dispatch().await? > 0 {}
+            *backoff = INITIAL_BACKOFF;
+            // wait for the notification of new events, poll anyway in case of missed ones
+            if let Ok(notification) = tokio::time::timeout(interval, listener.recv()).await {
+                notification?;
+            }
+        }
+    }
+
+    /// create the read cursor if not exists, only the events happened from now on are delivered
+    pub async fn init(&self) -> Result<(), abi::Error> {
+        let sql = "INSERT INTO server_read_cursor (server_id, last_change_id) SELECT $1, COALESCE(MAX(id), 0) FROM reservation_events ON CONFLICT (server_id) DO NOTHING";
+        sqlx::query(sql)
+            .bind(DISPATCHER_ID)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// queue a batch of events for all the webhooks and deliver the queued ones, return the
+    /// number of events in the batch. No transaction is kept open while posting the events
+    pub async fn dispatch(&self) -> Result<usize, abi::Error> {
+        let count = self.enqueue().await?;
+        loop {
+            let deliveries = self.claim().await?;
+            if deliveries.is_empty() {
+                return Ok(count);
+            }
+            let results = join_all(deliveries.iter().map(|d| self.deliver(d))).await;
+            for (delivery, result) in deliveries.iter().zip(results) {
+                self.finish(delivery, result).await?;
+            }
+        }
+    }
+
+    /// move the cursor after a batch of events and queue them for all the webhooks. The events
+    /// committed late are queued once they show up, the stored cursor stays before them
+    async fn enqueue(&self) -> Result<usize, abi::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // lock the cursor, so that the events are queued once
+        let sql = "SELECT last_change_id FROM server_read_cursor WHERE server_id = $1 FOR UPDATE";
+        let stored: i64 = sqlx::query_scalar(sql)
+            .bind(DISPATCHER_ID)
+            .fetch_one(&mut tx)
+            .await?;
+
+        let mut state = self.cursor.lock().await;
+        // start over from the stored cursor if another dispatcher moved it
+        if !matches!(*state, Some((_, saved)) if saved == stored) {
+            *state = Some((EventCursor::new(stored, []), stored));
+        }
+        let (cursor, saved) = state.as_mut().unwrap();
+
+        let sql =
+            "SELECT id FROM reservation_events WHERE id > $1 OR id = ANY($2) ORDER BY id LIMIT $3";
+        let events: Vec<i64> = sqlx::query_scalar(sql)
+            .bind(cursor.last_id())
+            .bind(cursor.gaps())
+            .bind(BATCH_SIZE)
+            .fetch_all(&mut tx)
+            .await?;
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        let sql = "INSERT INTO webhook_deliveries (webhook_id, event_id, payload) SELECT w.id, e.id, jsonb_build_object('id', e.id, 'reservation_id', e.reservation_id, 'event', e.event, 'old', e.old, 'new', e.new, 'actor_id', e.actor_id, 'request_id', e.request_id, 'created_at', e.created_at) FROM webhooks w CROSS JOIN reservation_events e WHERE e.id = ANY($1) ON CONFLICT (webhook_id, event_id) DO NOTHING";
+        sqlx::query(sql).bind(&events).execute(&mut tx).await?;
+
+        for id in events.iter() {
+            cursor.advance(*id);
+        }
+        let checkpoint = cursor.checkpoint();
+        let sql = "UPDATE server_read_cursor SET last_change_id = $1 WHERE server_id = $2";
+        sqlx::query(sql)
+            .bind(checkpoint)
+            .bind(DISPATCHER_ID)
+            .execute(&mut tx)
+            .await?;
+        // the cursor in memory is ahead if it fails, it starts over from the stored one then
+        tx.commit().await?;
+        *saved = checkpoint;
+        Ok(events.len())
+    }
+
+    /// lease the oldest queued event of each webhook, so the events of a webhook are delivered
+    /// in order. A lease expires after all the attempts could have been made
+    async fn claim(&self) -> Result<Vec<Delivery>, abi::Error> {
+        let config = &self.config;
+        let lease = config.max_attempts as u64 * (config.timeout_ms + config.max_backoff_ms);
+        let sql = "UPDATE webhook_deliveries d SET locked_until = now() + $1 * interval '1 millisecond' FROM webhooks w WHERE d.webhook_id = w.id AND d.id IN (SELECT DISTINCT ON (webhook_id) id FROM webhook_deliveries ORDER BY webhook_id, event_id) AND (d.locked_until IS NULL OR d.locked_until < now()) RETURNING d.id, d.event_id, d.payload::text, w.id, w.url, w.secret";
+        let rows: Vec<(i64, i64, String, i64, String, String)> = sqlx::query_as(sql)
+            .bind(lease as f64)
+            .fetch_all(&self.pool)
+            .await?;
+        let deliveries = rows
+            .into_iter()
+            .map(
+                |(id, event_id, payload, webhook_id, url, secret)| Delivery {
+                    id,
+                    event_id,
+                    payload,
+                    webhook: abi::Webhook {
+                        id: webhook_id,
+                        url,
+                        secret,
+                    },
+                },
+            )
+            .collect();
+        Ok(deliveries)
+    }
+
+    /// remove the delivered event from the queue, keep a dead letter if all the attempts failed
+    async fn finish(
+        &self,
+        delivery: &Delivery,
+        result: Result<(), (u32, String)>,
+    ) -> Result<(), abi::Error> {
+        let mut tx = self.pool.begin().await?;
+        if let Err((attempts, err)) = result {
+            warn!(
+                "Failed to deliver event {} to webhook {}: {}",
+                delivery.event_id, delivery.webhook.id, err
+            );
+            let sql = "INSERT INTO webhook_dead_letters (webhook_id, event_id, payload, attempts, last_error) VALUES ($1, $2, $3::jsonb, $4, $5)";
+            sqlx::query(sql)
+                .bind(delivery.webhook.id)
+                .bind(delivery.event_id)
+                .bind(&delivery.payload)
+                .bind(attempts as i32)
+                .bind(err)
+                .execute(&mut tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM webhook_deliveries WHERE id = $1")
+            .bind(delivery.id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// post the payload with exponential backoff, return the attempts and the last error on failure
+    async fn deliver(&self, delivery: &Delivery) -> Result<(), (u32, String)> {
+        let Delivery {
+            webhook,
+            event_id,
+            payload,
+            ..
+        } = delivery;
+        let secret = open_secret(self.config.secret_key.as_deref(), &webhook.secret)
+            .map_err(|err| (0, err.to_string()))?;
+        let signature = sign(&secret, payload.as_bytes());
+        let mut backoff = self.config.initial_backoff_ms;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let result = self
+                .client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .header(EVENT_ID_HEADER, *event_id)
+                .body(payload.to_string())
+                .send()
+                .await
+                .and_then(|res| res.error_for_status());
+            match result {
+                Ok(_) => return Ok(()),
+                Err(err) if attempts >= self.config.max_attempts => {
+                    return Err((attempts, err.to_string()))
+                }
+                Err(err) => {
+                    warn!(
+                        "Attempt {} to deliver event {} to webhook {} failed: {}",
+                        attempts, event_id, webhook.id, err
+                    );
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                    backoff = (backoff * 2).min(self.config.max_backoff_ms);
+                }
+            }
+        }
+    }
+}
+
+/// sign the payload with HMAC-SHA256, subscribers should verify it with the shared secret
+pub fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes key of any size");
+    mac.update(payload);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// encrypt the webhook secret with the hex encoded key, it's stored as `sealed:<hex nonce and
+/// ciphertext>`
+pub fn seal_secret(key: &str, secret: &str) -> Result<String, abi::Error> {
+    let key = secret_key(key)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| secret_key_error("failed to generate a nonce"))?;
+    let mut sealed = secret.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut sealed,
+    )
+    .map_err(|_| secret_key_error("failed to encrypt the secret"))?;
+    Ok(format!(
+        "{}{}{}",
+        SEALED_PREFIX,
+        hex::encode(nonce),
+        hex::encode(sealed)
+    ))
+}
+
+/// decrypt the stored webhook secret, the ones stored without a key are returned as they are
+pub fn open_secret(key: Option<&str>, stored: &str) -> Result<String, abi::Error> {
+    let sealed = match stored.strip_prefix(SEALED_PREFIX) {
+        Some(sealed) => sealed,
+        None => return Ok(stored.to_string()),
+    };
+    let key = secret_key(key.ok_or_else(|| secret_key_error("should be set to decrypt"))?)?;
+    let mut sealed = hex::decode(sealed).map_err(|_| secret_key_error("malformed secret"))?;
+    if sealed.len() < NONCE_LEN {
+        return Err(secret_key_error("malformed secret"));
+    }
+    let mut ciphertext = sealed.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&sealed)
+        .map_err(|_| secret_key_error("malformed secret"))?;
+    let secret = key
+        .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+        .map_err(|_| secret_key_error("doesn't match the secret"))?;
+    String::from_utf8(secret.to_vec()).map_err(|_| secret_key_error("malformed secret"))
+}
+
+fn secret_key(key: &str) -> Result<LessSafeKey, abi::Error> {
+    let key = hex::decode(key).map_err(|_| secret_key_error("should be 32 bytes in hex"))?;
+    let key = UnboundKey::new(&AES_256_GCM, &key)
+        .map_err(|_| secret_key_error("should be 32 bytes in hex"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn secret_key_error(reason: &str) -> abi::Error {
+    abi::Error::InvalidConfig {
+        key: "webhook.secret_key".to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{
+        test_utils::{alon_reservation, init_db, insert_reservation},
+        Reservation,
+    };
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server, StatusCode,
+    };
+
+    #[test]
+    fn sign_should_work() {
+        // echo -n 'hello' | openssl dgst -sha256 -hmac 'secret'
+        assert_eq!(
+            sign("secret", b"hello"),
+            "sha256=88aab3ede8d3adf94d26ab90d3bafd4a2083070c3bcce9c014ee04a443847c0b"
+        );
+    }
+
+    #[test]
+    fn sealed_secret_should_be_opened_with_the_key() {
+        let key = "ab".repeat(32);
+        let sealed = seal_secret(&key, "secret").unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("secret"));
+        assert_ne!(seal_secret(&key, "secret").unwrap(), sealed);
+        assert_eq!(open_secret(Some(&key), &sealed).unwrap(), "secret");
+
+        // stored before the key is set
+        assert_eq!(open_secret(Some(&key), "secret").unwrap(), "secret");
+        assert_eq!(open_secret(None, "secret").unwrap(), "secret");
+
+        assert!(open_secret(None, &sealed).is_err());
+        assert!(open_secret(Some(&"cd".repeat(32)), &sealed).is_err());
+    }
+
+    #[tokio::test]
+    async fn webhook_subscribe_and_unsubscribe_should_work() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        let webhook = store
+            .subscribe(abi::Webhook::new("http://localhost:8080/hook", "secret"))
+            .await
+            .unwrap();
+        assert!(webhook.id > 0);
+
+        let webhooks = store.list_webhooks().await.unwrap();
+        assert_eq!(webhooks.len(), 1);
+        assert_eq!(webhooks[0].url, webhook.url);
+        assert_eq!(webhooks[0].secret, "");
+
+        store.unsubscribe(webhook.id).await.unwrap();
+        assert!(store.list_webhooks().await.unwrap().is_empty());
+        let err = store.unsubscribe(webhook.id).await.unwrap_err();
+        assert_eq!(err, abi::Error::NotFound);
+    }
+
+    #[tokio::test]
+    async fn dispatch_should_deliver_signed_events_with_retries() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        // the first request fails, the retry succeeds
+        let (addr, received) = start_standin(1).await;
+        let key = "ab".repeat(32);
+        let secret = seal_secret(&key, "secret").unwrap();
+        store
+            .subscribe(abi::Webhook::new(format!("http://{}/hook", addr), secret))
+            .await
+            .unwrap();
+        let config = WebhookConfig {
+            secret_key: Some(key),
+            ..test_config()
+        };
+        let dispatcher = store.webhook_dispatcher(&config);
+        dispatcher.init().await.unwrap();
+
+        let reservation = store.reserve(alon_reservation()).await.unwrap();
+        assert_eq!(dispatcher.dispatch().await.unwrap(), 1);
+        assert_eq!(dispatcher.dispatch().await.unwrap(), 0);
+
+        let (signature, body) = {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            received[1].clone()
+        };
+        assert_eq!(signature, sign("secret", body.as_bytes()));
+        assert!(body.contains(&format!("\"reservation_id\": {}", reservation.id)));
+        assert!(body.contains("\"event\": \"create\""));
+
+        let dead_letters: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM webhook_dead_letters")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(dead_letters, 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_should_keep_dead_letter_after_all_attempts_failed() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        let (addr, received) = start_standin(usize::MAX).await;
+        let webhook = store
+            .subscribe(abi::Webhook::new(format!("http://{}/hook", addr), "secret"))
+            .await
+            .unwrap();
+        let dispatcher = store.webhook_dispatcher(&test_config());
+        dispatcher.init().await.unwrap();
+
+        store.reserve(alon_reservation()).await.unwrap();
+        assert_eq!(dispatcher.dispatch().await.unwrap(), 1);
+        assert_eq!(received.lock().unwrap().len(), 3);
+
+        let (webhook_id, attempts): (i64, i32) =
+            sqlx::query_as("SELECT webhook_id, attempts FROM webhook_dead_letters")
+                .fetch_one(&store.pool)
+                .await
+                .unwrap();
+        assert_eq!(webhook_id, webhook.id);
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn dispatch_should_deliver_events_committed_late() {
+        let db = init_db();
+        let pool = db.get_pool().await;
+        let store = ReservationStore::new(pool.clone());
+        let (addr, received) = start_standin(0).await;
+        store
+            .subscribe(abi::Webhook::new(format!("http://{}/hook", addr), "secret"))
+            .await
+            .unwrap();
+        let dispatcher = store.webhook_dispatcher(&test_config());
+        dispatcher.init().await.unwrap();
+
+        // the first event id is taken by a transaction committing after the second one
+        let mut late = pool.begin().await.unwrap();
+        insert_reservation(&mut late, "late", 1).await;
+        insert_reservation(&pool, "early", 2).await;
+        assert_eq!(dispatcher.dispatch().await.unwrap(), 1);
+
+        late.commit().await.unwrap();
+        assert_eq!(dispatcher.dispatch().await.unwrap(), 1);
+        assert_eq!(dispatcher.dispatch().await.unwrap(), 0);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received[0].1.contains("\"user_id\": \"early\""));
+        assert!(received[1].1.contains("\"user_id\": \"late\""));
+    }
+
+    #[tokio::test]
+    async fn dispatch_should_not_lock_the_cursor_while_posting() {
+        let db = init_db();
+        let store = ReservationStore::new(db.get_pool().await);
+        let (addr, received) = start_standin(usize::MAX).await;
+        store
+            .subscribe(abi::Webhook::new(format!("http://{}/hook", addr), "secret"))
+            .await
+            .unwrap();
+        let config = WebhookConfig {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 500,
+            ..test_config()
+        };
+        let dispatcher = store.webhook_dispatcher(&config);
+        dispatcher.init().await.unwrap();
+        store.reserve(alon_reservation()).await.unwrap();
+
+        let handle = tokio::spawn(async move { dispatcher.dispatch().await });
+        // wait for the first attempt, the retries are still pending
+        while received.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut tx = store.pool.begin().await.unwrap();
+        let sql =
+            "SELECT last_change_id FROM server_read_cursor WHERE server_id = $1 FOR UPDATE NOWAIT";
+        let cursor: i64 = sqlx::query_scalar(sql)
+            .bind(DISPATCHER_ID)
+            .fetch_one(&mut tx)
+            .await
+            .unwrap();
+        assert!(cursor > 0);
+        tx.rollback().await.unwrap();
+
+        // another dispatcher doesn't post the leased event again
+        let other = store.webhook_dispatcher(&config);
+        assert_eq!(other.dispatch().await.unwrap(), 0);
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// local http stand-in for the subscriber, responds 500 for the first `failures` requests
+    async fn start_standin(failures: usize) -> (SocketAddr, Received) {
+        let received: Received = Arc::new(Mutex::new(vec![]));
+        let data = received.clone();
+        let make_svc = make_service_fn(move |_| {
+            let data = data.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let data = data.clone();
+                    async move {
+                        let signature = req.headers()[SIGNATURE_HEADER]
+                            .to_str()
+                            .unwrap()
+                            .to_string();
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let mut data = data.lock().unwrap();
+                        data.push((signature, String::from_utf8(body.to_vec()).unwrap()));
+                        let status = if data.len() <= failures {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::OK
+                        };
+                        let mut res = Response::new(Body::empty());
+                        *res.status_mut() = status;
+                        Ok::<_, Infallible>(res)
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        (addr, received)
+    }
+
+    fn test_config() -> WebhookConfig {
+        WebhookConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 20,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use anyhow::Result;
+use clap::Parser;
+use reservation_service::Cli;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    Cli::parse().run().await
+}
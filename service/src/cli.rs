@@ -0,0 +1,442 @@
+use abi::{
+    convert_to_timestamp, convert_to_utc_time,
+    reservation_service_client::ReservationServiceClient, CancelRequest, ConfirmRequest,
+    FilterRequest, GetRequest, ListenRequest, QueryRequest, Reservation, ReservationEvent,
+    ReservationFilterBuilder, ReservationQueryBuilder, ReservationStatus, ReserveRequest,
+    UpdateRequest,
+};
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use futures::StreamExt;
+use tonic::transport::Channel;
+
+use crate::load_config;
+
+/// command line client of the reservation service
+#[derive(Debug, Parser)]
+#[command(name = "reservation-cli", version)]
+pub struct Cli {
+    /// config file to read the server address from, same lookup as the server if not set
+    #[arg(short, long)]
+    config: Option<String>,
+    /// output format
+    #[arg(short, long, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Status {
+    Pending,
+    Confirmed,
+    Blocked,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// make a reservation
+    Reserve {
+        #[arg(short, long)]
+        user: String,
+        #[arg(short, long)]
+        resource: String,
+        /// start time in RFC 3339, e.g. 2022-12-25T15:00:00-07:00
+        #[arg(short, long, required_unless_present = "start_date")]
+        start: Option<DateTime<FixedOffset>>,
+        /// end time in RFC 3339, e.g. 2022-12-28T12:00:00-07:00, open-ended if not set
+        #[arg(short, long)]
+        end: Option<DateTime<FixedOffset>>,
+        /// check-in date in YYYY-MM-DD instead of start and end, at the check-in time of the resource
+        #[arg(long, conflicts_with_all = ["start", "end"])]
+        start_date: Option<NaiveDate>,
+        /// check-out date in YYYY-MM-DD, the day after the check-in date if not set
+        #[arg(long, requires = "start_date")]
+        end_date: Option<NaiveDate>,
+        #[arg(short, long, default_value = "")]
+        note: String,
+        /// IANA time zone, e.g. America/Los_Angeles, the zone of the resource if not set
+        #[arg(long, default_value = "")]
+        timezone: String,
+    },
+    /// confirm a pending reservation
+    Confirm { id: i64 },
+    /// update the note of a reservation
+    Update { id: i64, note: String },
+    /// cancel a reservation
+    Cancel { id: i64 },
+    /// get a reservation by id
+    Get { id: i64 },
+    /// query reservations in a time range
+    Query {
+        #[arg(short, long, default_value = "")]
+        user: String,
+        #[arg(short, long, default_value = "")]
+        resource: String,
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+        #[arg(short, long)]
+        start: Option<DateTime<FixedOffset>>,
+        #[arg(short, long)]
+        end: Option<DateTime<FixedOffset>>,
+        /// a whole day in YYYY-MM-DD instead of start and end
+        #[arg(long, conflicts_with_all = ["start", "end"])]
+        local_day: Option<String>,
+        /// IANA time zone of the local day, the zone of the resource if not set
+        #[arg(long, default_value = "")]
+        timezone: String,
+        #[arg(long)]
+        desc: bool,
+    },
+    /// filter reservations by page, order by reservation id
+    Filter {
+        #[arg(short, long, default_value = "")]
+        user: String,
+        #[arg(short, long, default_value = "")]
+        resource: String,
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+        #[arg(long)]
+        cursor: Option<i64>,
+        #[arg(long, default_value_t = 10)]
+        page_size: i64,
+        #[arg(long)]
+        desc: bool,
+    },
+    /// print reservation events as they happen
+    Listen,
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        let config = load_config(self.config.clone(), &[])?;
+        let channel = config.server.endpoint()?.connect().await?;
+        let mut client = ReservationServiceClient::new(channel);
+        self.execute(&mut client).await
+    }
+
+    async fn execute(self, client: &mut ReservationServiceClient<Channel>) -> Result<()> {
+        let output = self.output;
+        match self.command {
+            Command::Reserve {
+                user,
+                resource,
+                start,
+                end,
+                start_date,
+                end_date,
+                note,
+                timezone,
+            } => {
+                let status = ReservationStatus::Pending;
+                let reservation = match (start, end, start_date) {
+                    (Some(start), Some(end), _) => {
+                        Reservation::new(user, resource, start, end, note, status)
+                    }
+                    (Some(start), None, _) => {
+                        Reservation::new_open_ended(user, resource, start, note, status)
+                    }
+                    (None, _, Some(start_date)) => Reservation::new_for_dates(
+                        user, resource, start_date, end_date, note, status,
+                    ),
+                    (None, ..) => unreachable!("start or start date is required"),
+                }
+                .with_timezone(timezone);
+                let rsp = client
+                    .reserve(ReserveRequest {
+                        reservation: Some(reservation),
+                    })
+                    .await?;
+                print_reservations(rsp.into_inner().reservation, output)?;
+            }
+            Command::Confirm { id } => {
+                let rsp = client.confirm(ConfirmRequest { id }).await?;
+                print_reservations(rsp.into_inner().reservation, output)?;
+            }
+            Command::Update { id, note } => {
+                let rsp = client.update(UpdateRequest { id, note }).await?;
+                print_reservations(rsp.into_inner().reservation, output)?;
+            }
+            Command::Cancel { id } => {
+                let rsp = client.cancel(CancelRequest { id }).await?;
+                print_reservations(rsp.into_inner().reservation, output)?;
+            }
+            Command::Get { id } => {
+                let rsp = client.get(GetRequest { id }).await?;
+                print_reservations(rsp.into_inner().reservation, output)?;
+            }
+            Command::Query {
+                user,
+                resource,
+                status,
+                start,
+                end,
+                local_day,
+                timezone,
+                desc,
+            } => {
+                let mut builder = ReservationQueryBuilder::default();
+                builder
+                    .user_id(user)
+                    .resource_id(resource)
+                    .status(to_status(status) as i32)
+                    .local_day(local_day.unwrap_or_default())
+                    .timezone(timezone)
+                    .desc(desc);
+                if let Some(start) = start {
+                    builder.start(convert_to_timestamp(start.with_timezone(&Utc)));
+                }
+                if let Some(end) = end {
+                    builder.end(convert_to_timestamp(end.with_timezone(&Utc)));
+                }
+                let query = builder.build()?;
+                let mut stream = client
+                    .query(QueryRequest { query: Some(query) })
+                    .await?
+                    .into_inner();
+                let mut reservations = vec![];
+                while let Some(reservation) = stream.next().await {
+                    reservations.push(reservation?);
+                }
+                print_reservations(reservations, output)?;
+            }
+            Command::Filter {
+                user,
+                resource,
+                status,
+                cursor,
+                page_size,
+                desc,
+            } => {
+                let mut builder = ReservationFilterBuilder::default();
+                builder
+                    .user_id(user)
+                    .resource_id(resource)
+                    .status(to_status(status) as i32)
+                    .page_size(page_size)
+                    .desc(desc);
+                if let Some(cursor) = cursor {
+                    builder.cursor(cursor);
+                }
+                let filter = builder.build()?;
+                let rsp = client
+                    .filter(FilterRequest {
+                        filter: Some(filter),
+                    })
+                    .await?
+                    .into_inner();
+                print_reservations(rsp.reservations, output)?;
+            }
+            Command::Listen => {
+                let mut stream = client.listen(ListenRequest {}).await?.into_inner();
+                if output == Output::Table {
+                    println!("{}", render_event_header());
+                }
+                while let Some(event) = stream.next().await {
+                    println!("{}", render_event(&event?, output)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_status(status: Option<Status>) -> ReservationStatus {
+    match status {
+        None => ReservationStatus::Unknown,
+        Some(Status::Pending) => ReservationStatus::Pending,
+        Some(Status::Confirmed) => ReservationStatus::Confirmed,
+        Some(Status::Blocked) => ReservationStatus::Blocked,
+    }
+}
+
+fn print_reservations(
+    reservations: impl IntoIterator<Item = Reservation>,
+    output: Output,
+) -> Result<()> {
+    let reservations: Vec<_> = reservations.into_iter().collect();
+    println!("{}", render_reservations(&reservations, output)?);
+    Ok(())
+}
+
+const HEADER: [&str; 7] = ["ID", "USER", "RESOURCE", "STATUS", "START", "END", "NOTE"];
+
+fn render_reservations(reservations: &[Reservation], output: Output) -> Result<String> {
+    match output {
+        Output::Json => Ok(serde_json::to_string_pretty(reservations)?),
+        Output::Yaml => Ok(serde_yaml::to_string(reservations)?),
+        Output::Table => {
+            let rows: Vec<_> = reservations.iter().map(to_row).collect();
+            Ok(render_table(&HEADER, &rows))
+        }
+    }
+}
+
+fn render_event_header() -> String {
+    let mut header = vec!["EVENT", "EVENT_ID"];
+    header.extend(HEADER);
+    header.join("\t")
+}
+
+/// events are rendered one by one as they come, so the table form is tab separated
+fn render_event(event: &ReservationEvent, output: Output) -> Result<String> {
+    match output {
+        Output::Json => Ok(serde_json::to_string(event)?),
+        Output::Yaml => Ok(format!("---\n{}", serde_yaml::to_string(event)?)),
+        Output::Table => {
+            let mut row = vec![event.get_event_type().to_string(), event.id.to_string()];
+            match event.new.as_ref().or(event.old.as_ref()) {
+                Some(reservation) => row.extend(to_row(reservation)),
+                None => row.push(event.reservation_id.to_string()),
+            }
+            Ok(row.join("\t"))
+        }
+    }
+}
+
+fn to_row(r: &Reservation) -> Vec<String> {
+    vec![
+        r.id.to_string(),
+        r.user_id.clone(),
+        r.resource_id.clone(),
+        ReservationStatus::from_i32(r.status)
+            .unwrap_or(ReservationStatus::Unknown)
+            .to_string(),
+        format_time(r.start.as_ref()),
+        format_time(r.end.as_ref()),
+        r.note.clone(),
+    ]
+}
+
+fn format_time(ts: Option<&prost_types::Timestamp>) -> String {
+    match convert_to_utc_time(ts, "") {
+        Ok(time) => time.to_rfc3339(),
+        Err(_) => "-".to_string(),
+    }
+}
+
+/// render rows as a table with left aligned columns
+fn render_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<_> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.trim_end().to_string()
+    };
+
+    let mut lines = vec![format_row(header.to_vec())];
+    lines.extend(
+        rows.iter()
+            .map(|row| format_row(row.iter().map(|c| c.as_str()).collect())),
+    );
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_reservation() -> Reservation {
+        let mut reservation = Reservation::new(
+            "alon",
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test device reservation",
+            ReservationStatus::Pending,
+        );
+        reservation.id = 1;
+        reservation
+    }
+
+    #[test]
+    fn cli_should_parse_reserve_command() {
+        let cli = Cli::try_parse_from([
+            "reservation-cli",
+            "-o",
+            "json",
+            "reserve",
+            "--user",
+            "alon",
+            "--resource",
+            "ixia-3230",
+            "--start",
+            "2022-12-26T15:00:00-07:00",
+            "--end",
+            "2022-12-30T12:00:00-07:00",
+        ])
+        .unwrap();
+        assert_eq!(cli.output, Output::Json);
+        assert!(matches!(cli.command, Command::Reserve { ref user, .. } if user == "alon"));
+    }
+
+    #[test]
+    fn cli_should_parse_date_based_reserve_command() {
+        let args = [
+            "reservation-cli",
+            "reserve",
+            "--user",
+            "alon",
+            "--resource",
+            "ocean-view-room-713",
+            "--start-date",
+            "2022-12-26",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Reserve { start: None, start_date: Some(d), end_date: None, .. }
+                if d == NaiveDate::from_ymd_opt(2022, 12, 26).unwrap()
+        ));
+        // either the start or the start date
+        assert!(Cli::try_parse_from(&args[..6]).is_err());
+        let args = [&args[..], &["--start", "2022-12-26T15:00:00-07:00"]].concat();
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn cli_should_not_mix_local_day_and_start() {
+        let args = ["reservation-cli", "query", "--local-day", "2022-12-26"];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert!(
+            matches!(cli.command, Command::Query { ref local_day, .. } if local_day.as_deref() == Some("2022-12-26"))
+        );
+        let args = [&args[..], &["--start", "2022-12-26T15:00:00-07:00"]].concat();
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn reservations_should_render_as_table() {
+        let table = render_reservations(&[make_reservation()], Output::Table).unwrap();
+        assert_eq!(
+            table,
+            "ID  USER  RESOURCE   STATUS   START                      END                        NOTE\n\
+             1   alon  ixia-3230  pending  2022-12-26T22:00:00+00:00  2022-12-30T19:00:00+00:00  test device reservation"
+        );
+    }
+
+    #[test]
+    fn reservations_should_render_as_yaml() {
+        let yaml = render_reservations(&[make_reservation()], Output::Yaml).unwrap();
+        let reservations: Vec<Reservation> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(reservations, vec![make_reservation()]);
+    }
+}
@@ -0,0 +1,185 @@
+use abi::Config;
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use reservation::{MigrationStatus, ReservationStore};
+
+use crate::{init_tracing, load_config, shutdown_tracing, start_server};
+
+/// reservation server, the config is layered: defaults, the file,
+/// RESERVATION__* env vars (e.g. RESERVATION__DB__PASSWORD) and --set flags
+#[derive(Debug, Parser)]
+#[command(name = "reservation-service", version, about)]
+pub struct ServerCli {
+    /// config file, $RESERVATION_CONFIG or the default locations if not set
+    #[arg(short, long, global = true)]
+    config: Option<String>,
+    /// override a config key, e.g. --set server.port=50052
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_key_value, global = true)]
+    overrides: Vec<(String, String)>,
+    /// serve if not set
+    #[command(subcommand)]
+    command: Option<ServerCommand>,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+enum ServerCommand {
+    /// start the server, it refuses to start if the database schema is not up to date
+    Serve {
+        /// apply the pending migrations before starting
+        #[arg(long)]
+        migrate: bool,
+    },
+    /// manage the database schema with the embedded migrations
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+    /// validate the config and print it with the secrets masked
+    CheckConfig,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+enum MigrateCommand {
+    /// apply the pending migrations
+    Up,
+    /// revert the latest applied migration
+    Down,
+    /// list the migrations and whether they're applied
+    Status,
+}
+
+impl ServerCli {
+    pub async fn run(self) -> Result<()> {
+        let config = load_config(self.config, &self.overrides)?;
+        let command = self
+            .command
+            .unwrap_or(ServerCommand::Serve { migrate: false });
+        match command {
+            ServerCommand::Serve { migrate } => {
+                if migrate {
+                    let store = ReservationStore::from_config(&config.db).await?;
+                    store.migrate_up().await?;
+                    store.close().await;
+                }
+                init_tracing(&config.tracing)?;
+                let result = start_server(&config).await;
+                shutdown_tracing();
+                result
+            }
+            ServerCommand::Migrate { command } => {
+                let store = ReservationStore::from_config(&config.db).await?;
+                match command {
+                    MigrateCommand::Up => {
+                        store.migrate_up().await?;
+                        println!("All migrations are applied");
+                    }
+                    MigrateCommand::Down => match store.migrate_down().await? {
+                        Some(version) => println!("Reverted migration {}", version),
+                        None => println!("No migration to revert"),
+                    },
+                    MigrateCommand::Status => {
+                        print!("{}", render_status(&store.migration_status().await?));
+                    }
+                }
+                store.close().await;
+                Ok(())
+            }
+            ServerCommand::CheckConfig => {
+                print!("{}", render_config(&config)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expect KEY=VALUE, got {}", s))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn render_status(status: &[MigrationStatus]) -> String {
+    status
+        .iter()
+        .map(|m| {
+            let state = if m.applied { "applied" } else { "pending" };
+            format!("{}  {:<8} {}\n", m.version, state, m.description)
+        })
+        .collect()
+}
+
+/// the effective config as yaml, the password is masked
+fn render_config(config: &Config) -> Result<String> {
+    let mut config = config.clone();
+    if !config.db.password.is_empty() {
+        config.db.password = "********".to_string();
+    }
+    Ok(serde_yaml::to_string(&config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_cli_should_parse_subcommands() {
+        let cli = ServerCli::try_parse_from(["reservation-service"]).unwrap();
+        assert_eq!(cli.command, None);
+
+        let cli = ServerCli::try_parse_from([
+            "reservation-service",
+            "serve",
+            "--migrate",
+            "--set",
+            "server.port=50052",
+        ])
+        .unwrap();
+        assert_eq!(cli.command, Some(ServerCommand::Serve { migrate: true }));
+        assert_eq!(
+            cli.overrides,
+            vec![("server.port".to_string(), "50052".to_string())]
+        );
+
+        let cli =
+            ServerCli::try_parse_from(["reservation-service", "-c", "a.yml", "migrate", "down"])
+                .unwrap();
+        assert_eq!(cli.config.as_deref(), Some("a.yml"));
+        assert_eq!(
+            cli.command,
+            Some(ServerCommand::Migrate {
+                command: MigrateCommand::Down
+            })
+        );
+
+        assert!(ServerCli::try_parse_from(["reservation-service", "--set", "port"]).is_err());
+    }
+
+    #[test]
+    fn check_config_should_mask_password() {
+        let config = Config::load("fixtures/config.yml").unwrap();
+        let yaml = render_config(&config).unwrap();
+        assert!(yaml.contains("password: '********'"), "{}", yaml);
+        assert!(!yaml.contains("123456"));
+    }
+
+    #[test]
+    fn migration_status_should_be_rendered() {
+        let status = vec![
+            MigrationStatus {
+                version: 20221220090838,
+                description: "init".to_string(),
+                applied: true,
+            },
+            MigrationStatus {
+                version: 20230115101500,
+                description: "webhook".to_string(),
+                applied: false,
+            },
+        ];
+        assert_eq!(
+            render_status(&status),
+            "20221220090838  applied  init\n20230115101500  pending  webhook\n"
+        );
+    }
+}
@@ -0,0 +1,572 @@
+use std::{convert::Infallible, sync::Arc};
+
+use abi::{
+    ical_footer, ical_header, parse_ical, ImportConflict, RequestContext, ReservationConflictInfo,
+    ReservationEventFilter, ReservationEventFilterBuilder, ReservationFilterBuilder,
+    ReservationQuery, ReservationQueryBuilder, ResourcesConfig,
+};
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, StreamExt};
+use reservation::Reservation;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::{
+    metrics::MetricsLayer,
+    service::{require_admin, ACTOR_ID_KEY, REQUEST_ID_KEY},
+    telemetry::TraceLayer,
+};
+
+/// REST/JSON routes of the reservation service, served by the same store as gRPC. The
+/// requests are recorded in the metrics and traced like the RPCs
+pub fn router<R>(store: R, resources: ResourcesConfig) -> Router
+where
+    R: Reservation + Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/v1/reservations", post(reserve::<R>).get(filter::<R>))
+        .route("/v1/reservations/query", get(query::<R>))
+        .route("/v1/reservations/listen", get(listen::<R>))
+        .route("/v1/reservations/events", get(events::<R>))
+        .route("/v1/reservations/export", get(export::<R>))
+        .route("/v1/reservations/import", post(import::<R>))
+        .route(
+            "/v1/reservations/:id",
+            get(get_reservation::<R>)
+                .patch(update::<R>)
+                .delete(cancel::<R>),
+        )
+        .route("/v1/reservations/:id/confirm", post(confirm::<R>))
+        .route("/v1/reservations/:id/history", get(history::<R>))
+        // route layers see the matched route, it's the label instead of the path
+        .route_layer(TraceLayer)
+        .route_layer(MetricsLayer)
+        .layer(Extension(Arc::new(resources)))
+        .with_state(store)
+}
+
+/// abi::Error as a JSON response with the matching HTTP status
+pub struct ApiError(abi::Error);
+
+impl From<abi::Error> for ApiError {
+    fn from(e: abi::Error) -> Self {
+        Self(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = match &self.0 {
+            abi::Error::ConflictReservation(info) => {
+                let conflicts: Vec<_> = match info {
+                    ReservationConflictInfo::Parsed(conflict) => conflict
+                        .existing
+                        .iter()
+                        .map(|r| {
+                            json!({
+                                "id": r.id,
+                                "user_id": r.user_id,
+                                "status": r.status.to_string(),
+                                "start": r.window.start_time().map(|t| t.to_rfc3339()),
+                                "end": r.window.end_time().map(|t| t.to_rfc3339()),
+                            })
+                        })
+                        .collect(),
+                    ReservationConflictInfo::UnParsed(_) => vec![],
+                };
+                json!({
+                    "error": self.0.to_string(),
+                    "detail": info.to_string(),
+                    "conflicts": conflicts,
+                })
+            }
+            e => json!({ "error": e.to_string() }),
+        };
+        (status_code(&self.0), Json(body)).into_response()
+    }
+}
+
+fn status_code(e: &abi::Error) -> StatusCode {
+    match e {
+        abi::Error::DbError(_)
+        | abi::Error::ConfigReadError(..)
+        | abi::Error::ConfigParseError(_)
+        | abi::Error::InvalidConfig { .. }
+        | abi::Error::SchemaMismatch { .. }
+        | abi::Error::EventSinkError(_)
+        | abi::Error::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+        abi::Error::InvalidTime { .. }
+        | abi::Error::InvalidTimezone(_)
+        | abi::Error::InvalidReservationId(_)
+        | abi::Error::InvalidUserId(_)
+        | abi::Error::InvalidResourceId(_)
+        | abi::Error::InvalidPageSize(_)
+        | abi::Error::InvalidCursor(_)
+        | abi::Error::InvalidStatus(_)
+        | abi::Error::InvalidEventType(_)
+        | abi::Error::InvalidWebhookUrl(_)
+        | abi::Error::InvalidWebhookSecret
+        | abi::Error::InvalidIcal(_) => StatusCode::BAD_REQUEST,
+        abi::Error::ConflictReservation(_) => StatusCode::CONFLICT,
+        abi::Error::NotFound => StatusCode::NOT_FOUND,
+        abi::Error::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+        abi::Error::PermissionDenied => StatusCode::FORBIDDEN,
+        abi::Error::RpcError(code, _) => match code {
+            tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+            tonic::Code::NotFound => StatusCode::NOT_FOUND,
+            tonic::Code::FailedPrecondition | tonic::Code::AlreadyExists => StatusCode::CONFLICT,
+            tonic::Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+            tonic::Code::PermissionDenied => StatusCode::FORBIDDEN,
+            tonic::Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        },
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// same as the gRPC metadata, the caller identity is taken from the headers. It's not verified,
+/// so the REST callers are never admins
+fn request_context(headers: &HeaderMap) -> RequestContext {
+    let get = |key: &str| {
+        headers
+            .get(key)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    RequestContext::new(get(ACTOR_ID_KEY), get(REQUEST_ID_KEY))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct QueryParams {
+    user_id: String,
+    resource_id: String,
+    #[serde(deserialize_with = "abi::serde_utils::reservation_status::deserialize")]
+    status: i32,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    local_day: String,
+    timezone: String,
+    desc: bool,
+}
+
+impl TryFrom<QueryParams> for ReservationQuery {
+    type Error = abi::Error;
+
+    fn try_from(params: QueryParams) -> Result<Self, Self::Error> {
+        let mut builder = ReservationQueryBuilder::default();
+        builder
+            .user_id(params.user_id)
+            .resource_id(params.resource_id)
+            .status(params.status)
+            .local_day(params.local_day)
+            .timezone(params.timezone)
+            .desc(params.desc);
+        if let Some(start) = params.start {
+            builder.start_time(start);
+        }
+        if let Some(end) = params.end {
+            builder.end_time(end);
+        }
+        builder.build()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FilterParams {
+    user_id: String,
+    resource_id: String,
+    #[serde(deserialize_with = "abi::serde_utils::reservation_status::deserialize")]
+    status: i32,
+    cursor: Option<i64>,
+    page_size: Option<i64>,
+    desc: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct EventsParams {
+    reservation_id: i64,
+    user_id: String,
+    resource_id: String,
+    #[serde(deserialize_with = "abi::serde_utils::reservation_event_type::deserialize")]
+    event_type: i32,
+    cursor: Option<i64>,
+    page_size: Option<i64>,
+    desc: bool,
+}
+
+impl TryFrom<EventsParams> for ReservationEventFilter {
+    type Error = abi::Error;
+
+    fn try_from(params: EventsParams) -> Result<Self, Self::Error> {
+        let mut builder = ReservationEventFilterBuilder::default();
+        builder
+            .reservation_id(params.reservation_id)
+            .user_id(params.user_id)
+            .resource_id(params.resource_id)
+            .event_type(params.event_type)
+            .desc(params.desc);
+        if let Some(cursor) = params.cursor {
+            builder.cursor(cursor);
+        }
+        if let Some(page_size) = params.page_size {
+            builder.page_size(page_size);
+        }
+        builder.build()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct UpdateBody {
+    note: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ImportParams {
+    resource_id: String,
+    user_id: String,
+    dry_run: bool,
+    atomic: bool,
+}
+
+/// POST /v1/reservations
+async fn reserve<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    Extension(resources): Extension<Arc<ResourcesConfig>>,
+    headers: HeaderMap,
+    Json(mut reservation): Json<abi::Reservation>,
+) -> ApiResult<(StatusCode, Json<abi::Reservation>)> {
+    resources.fill_reservation(&mut reservation)?;
+    let store = store.with_context(request_context(&headers));
+    let reservation = store.reserve(reservation).await?;
+    Ok((StatusCode::CREATED, Json(reservation)))
+}
+
+/// GET /v1/reservations/:id
+async fn get_reservation<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<abi::Reservation>> {
+    let store = store.with_context(request_context(&headers));
+    Ok(Json(store.get(id).await?))
+}
+
+/// PATCH /v1/reservations/:id
+async fn update<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(body): Json<UpdateBody>,
+) -> ApiResult<Json<abi::Reservation>> {
+    let store = store.with_context(request_context(&headers));
+    Ok(Json(store.update(id, body.note).await?))
+}
+
+/// POST /v1/reservations/:id/confirm
+async fn confirm<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<abi::Reservation>> {
+    let store = store.with_context(request_context(&headers));
+    Ok(Json(store.confirm(id).await?))
+}
+
+/// DELETE /v1/reservations/:id
+async fn cancel<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<abi::Reservation>> {
+    let store = store.with_context(request_context(&headers));
+    Ok(Json(store.delete(id).await?))
+}
+
+/// GET /v1/reservations
+async fn filter<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Query(params): Query<FilterParams>,
+) -> ApiResult<Json<Vec<abi::Reservation>>> {
+    let store = store.with_context(request_context(&headers));
+    let mut builder = ReservationFilterBuilder::default();
+    builder
+        .user_id(params.user_id)
+        .resource_id(params.resource_id)
+        .status(params.status)
+        .desc(params.desc);
+    if let Some(cursor) = params.cursor {
+        builder.cursor(cursor);
+    }
+    if let Some(page_size) = params.page_size {
+        builder.page_size(page_size);
+    }
+    Ok(Json(store.filter(builder.build()?).await?))
+}
+
+/// GET /v1/reservations/query, the reservations are sent as server-sent events
+async fn query<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    Extension(resources): Extension<Arc<ResourcesConfig>>,
+    headers: HeaderMap,
+    Query(params): Query<QueryParams>,
+) -> ApiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let store = store.with_context(request_context(&headers));
+    let mut query = ReservationQuery::try_from(params)?;
+    resources.fill_query(&mut query);
+    let reservations = store.query(query).await;
+    Ok(Sse::new(receiver_stream(reservations).map(to_event)))
+}
+
+/// GET /v1/reservations/listen, the reservation events are sent as server-sent events
+async fn listen<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let store = store.with_context(request_context(&headers));
+    let events = store.listen().await;
+    Sse::new(receiver_stream(events).map(to_event)).keep_alive(KeepAlive::default())
+}
+
+/// GET /v1/reservations/:id/history
+async fn history<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<Vec<abi::ReservationEvent>>> {
+    let store = store.with_context(request_context(&headers));
+    Ok(Json(store.history(id).await?))
+}
+
+/// GET /v1/reservations/events (admin). The callers of the gateway are not authenticated, so
+/// it's always forbidden
+async fn events<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    headers: HeaderMap,
+    Query(params): Query<EventsParams>,
+) -> ApiResult<Json<Vec<abi::ReservationEvent>>> {
+    let context = request_context(&headers);
+    require_admin(&context)?;
+    let store = store.with_context(context);
+    let filter = ReservationEventFilter::try_from(params)?;
+    Ok(Json(store.events(filter).await?))
+}
+
+/// GET /v1/reservations/export, the reservations as an iCalendar (.ics) document
+async fn export<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    Extension(resources): Extension<Arc<ResourcesConfig>>,
+    headers: HeaderMap,
+    Query(params): Query<QueryParams>,
+) -> ApiResult<impl IntoResponse> {
+    let store = store.with_context(request_context(&headers));
+    if params.user_id.is_empty() && params.resource_id.is_empty() {
+        return Err(abi::Error::InvalidResourceId(params.resource_id).into());
+    }
+    let mut query = ReservationQuery::try_from(params)?;
+    resources.fill_query(&mut query);
+    let mut reservations = store.query(query).await;
+
+    let stamp = Utc::now();
+    let mut data = ical_header();
+    while let Some(reservation) = reservations.recv().await {
+        data.push_str(&reservation?.to_vevent(stamp));
+    }
+    data.push_str(&ical_footer());
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        data,
+    ))
+}
+
+/// POST /v1/reservations/import, the body is an iCalendar (.ics) document
+async fn import<R: Reservation + Send + Sync>(
+    State(store): State<R>,
+    Extension(resources): Extension<Arc<ResourcesConfig>>,
+    headers: HeaderMap,
+    Query(params): Query<ImportParams>,
+    data: String,
+) -> ApiResult<Json<serde_json::Value>> {
+    if params.resource_id.is_empty() {
+        return Err(abi::Error::InvalidResourceId(params.resource_id).into());
+    }
+    let store = store.with_context(request_context(&headers));
+    let timezone = resources.timezone(&params.resource_id);
+    let mut reservations = parse_ical(&data, &params.resource_id, &params.user_id, timezone)?;
+    for reservation in reservations.iter_mut() {
+        resources.fill_reservation(reservation)?;
+    }
+    let (reservations, conflicts) = store
+        .import(reservations, params.dry_run, params.atomic)
+        .await?;
+    let conflicts: Vec<_> = conflicts
+        .into_iter()
+        .map(
+            |ImportConflict {
+                 reservation,
+                 reason,
+             }| { json!({ "reservation": reservation, "reason": reason }) },
+        )
+        .collect();
+    Ok(Json(
+        json!({ "reservations": reservations, "conflicts": conflicts }),
+    ))
+}
+
+fn receiver_stream<T>(
+    rx: mpsc::Receiver<Result<T, abi::Error>>,
+) -> impl Stream<Item = Result<T, abi::Error>> {
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })
+}
+
+/// items are sent as JSON data, errors are sent as "error" events
+fn to_event<T: serde::Serialize>(item: Result<T, abi::Error>) -> Result<Event, Infallible> {
+    let event = match item {
+        Ok(item) => Event::default()
+            .json_data(item)
+            .unwrap_or_else(|e| Event::default().event("error").data(e.to_string())),
+        Err(e) => Event::default().event("error").data(e.to_string()),
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics, test_utils::TestConfig};
+    use axum::{body::Body, http::Request};
+    use reservation::{InMemoryReservationStore, ReservationStore};
+    use tower::ServiceExt;
+
+    async fn send(router: &Router, request: Request<Body>) -> (StatusCode, String) {
+        let rsp = router.clone().oneshot(request).await.unwrap();
+        let status = rsp.status();
+        let body = hyper::body::to_bytes(rsp.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn reserve_request(start: &str, end: &str) -> Request<Body> {
+        let body = json!({
+            "user_id": "alon",
+            "resource_id": "ixia-3230",
+            "start": start,
+            "end": end,
+            "note": "test device reservation",
+        });
+        Request::post("/v1/reservations")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(ACTOR_ID_KEY, "web")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rest_reserve_should_map_errors_to_http_status() {
+        let config = TestConfig::default();
+        let store = ReservationStore::from_config(&config.db).await.unwrap();
+        let router = router(store, ResourcesConfig::default());
+
+        let request = reserve_request("2022-12-26T22:00:00Z", "2022-12-30T19:00:00Z");
+        let (status, body) = send(&router, request).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let reservation: abi::Reservation = serde_json::from_str(&body).unwrap();
+        assert!(reservation.id > 0);
+
+        let request = reserve_request("2022-12-28T22:00:00Z", "2022-12-31T19:00:00Z");
+        let (status, body) = send(&router, request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("conflicts with the reservation of ixia-3230"));
+        // the caller doesn't own the reservation in the way
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["conflicts"][0]["id"], reservation.id);
+        assert_eq!(body["conflicts"][0]["user_id"], serde_json::Value::Null);
+
+        let uri = format!("/v1/reservations/{}/confirm", reservation.id);
+        let request = Request::post(uri).body(Body::empty()).unwrap();
+        let (status, body) = send(&router, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""status":"confirmed""#));
+
+        let request = Request::get("/v1/reservations/10000")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&router, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let request = Request::get("/v1/reservations?page_size=1000")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&router, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let request = Request::get("/v1/reservations/events")
+            .header(ACTOR_ID_KEY, "support")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&router, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rest_should_work_with_in_memory_store() {
+        let router = router(InMemoryReservationStore::new(), ResourcesConfig::default());
+        let created = metrics::rpc_requests("POST /v1/reservations", "201");
+
+        let request = reserve_request("2022-12-26T22:00:00Z", "2022-12-30T19:00:00Z");
+        let (status, body) = send(&router, request).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let reservation: abi::Reservation = serde_json::from_str(&body).unwrap();
+
+        let request = Request::get(format!("/v1/reservations/{}", reservation.id))
+            .header(ACTOR_ID_KEY, "alon")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send(&router, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let found: abi::Reservation = serde_json::from_str(&body).unwrap();
+        assert_eq!(found, reservation);
+
+        // the routes are the labels, not the paths
+        assert!(metrics::rpc_requests("POST /v1/reservations", "201") > created);
+        assert!(metrics::rpc_requests("GET /v1/reservations/:id", "200") > 0);
+    }
+
+    #[tokio::test]
+    async fn rest_query_should_send_events() {
+        let config = TestConfig::default();
+        let store = ReservationStore::from_config(&config.db).await.unwrap();
+        let router = router(store, ResourcesConfig::default());
+
+        let request = reserve_request("2022-12-26T22:00:00Z", "2022-12-30T19:00:00Z");
+        send(&router, request).await;
+
+        let request = Request::get("/v1/reservations/query?user_id=alon&status=pending")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send(&router, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let events: Vec<_> = body.lines().filter(|l| l.starts_with("data:")).collect();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains(r#""resource_id":"ixia-3230""#));
+    }
+}
@@ -0,0 +1,146 @@
+use abi::{
+    reservation_service_server::ReservationServiceServer,
+    webhook_service_server::WebhookServiceServer,
+};
+use reservation::ReservationStore;
+use std::time::Duration;
+use tonic::transport::NamedService;
+use tonic_health::{server::HealthReporter, ServingStatus};
+
+use crate::{ReservationService, WebhookService};
+
+/// services reported to the health service, "" is the overall status of the server
+const SERVICES: [&str; 3] = [
+    "",
+    <ReservationServiceServer<ReservationService> as NamedService>::NAME,
+    <WebhookServiceServer<WebhookService> as NamedService>::NAME,
+];
+
+/// ping the database periodically, the services are serving only if the database is reachable.
+/// It stops once the store is shut down, so that the status is not reported serving again
+pub async fn report_health(
+    store: ReservationStore,
+    mut reporter: HealthReporter,
+    interval: Duration,
+) {
+    let report = async {
+        loop {
+            let status = match store.ping().await {
+                Ok(_) => ServingStatus::Serving,
+                Err(e) => {
+                    eprintln!("Database is not reachable: {:?}", e);
+                    ServingStatus::NotServing
+                }
+            };
+            set_status(&mut reporter, status).await;
+            tokio::time::sleep(interval).await;
+        }
+    };
+    tokio::select! {
+        biased;
+        _ = store.shutdown_requested() => {}
+        _ = report => {}
+    }
+}
+
+/// report all the services not serving, e.g. when the server is shutting down so that the
+/// load balancers stop sending requests while draining
+pub async fn set_not_serving(reporter: &mut HealthReporter) {
+    set_status(reporter, ServingStatus::NotServing).await;
+}
+
+async fn set_status(reporter: &mut HealthReporter, status: ServingStatus) {
+    for service in SERVICES {
+        reporter.set_service_status(service, status).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{start_server, test_utils::TestConfig};
+    use tonic::transport::Channel;
+    use tonic_health::proto::{
+        health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+    };
+    use tonic_reflection::proto::{
+        server_reflection_client::ServerReflectionClient,
+        server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+        ServerReflectionRequest,
+    };
+
+    async fn connect(config: &TestConfig) -> Channel {
+        let url = config.server.url(false);
+        for _ in 0..50 {
+            if let Ok(channel) = Channel::from_shared(url.clone()).unwrap().connect().await {
+                return channel;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("server is not started");
+    }
+
+    #[tokio::test]
+    async fn health_and_reflection_should_work() {
+        let config = TestConfig::default();
+        let server_config = config.config.clone();
+        tokio::spawn(async move { start_server(&server_config).await.unwrap() });
+        let channel = connect(&config).await;
+
+        let mut health = HealthClient::new(channel.clone());
+        let rsp = health
+            .check(HealthCheckRequest {
+                service: "reservation.ReservationService".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(rsp.status, ServingStatus::Serving as i32);
+
+        let mut reflection = ServerReflectionClient::new(channel);
+        let request = ServerReflectionRequest {
+            host: "".to_string(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut stream = reflection
+            .server_reflection_info(futures::stream::iter(vec![request]))
+            .await
+            .unwrap()
+            .into_inner();
+        let rsp = stream.message().await.unwrap().unwrap();
+        let services = match rsp.message_response {
+            Some(MessageResponse::ListServicesResponse(rsp)) => rsp.service,
+            _ => panic!("unexpected response"),
+        };
+        let names: Vec<_> = services.into_iter().map(|s| s.name).collect();
+        assert!(names.contains(&"reservation.ReservationService".to_string()));
+        assert!(names.contains(&"reservation.WebhookService".to_string()));
+    }
+
+    #[tokio::test]
+    async fn health_should_not_be_serving_after_shutdown() {
+        let config = TestConfig::default();
+        let store = ReservationStore::from_config(&config.db).await.unwrap();
+        let (mut reporter, health) = tonic_health::server::health_reporter();
+        let task = tokio::spawn(report_health(
+            store.clone(),
+            reporter.clone(),
+            Duration::from_millis(10),
+        ));
+        let check = || async {
+            let request = HealthCheckRequest {
+                service: "reservation.ReservationService".to_string(),
+            };
+            let mut client = HealthClient::new(health.clone());
+            client.check(request).await.unwrap().into_inner().status
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(check().await, ServingStatus::Serving as i32);
+
+        store.shutdown();
+        set_not_serving(&mut reporter).await;
+        task.await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(check().await, ServingStatus::NotServing as i32);
+    }
+}
@@ -1,15 +1,49 @@
+mod cli;
+mod command;
+mod gateway;
+mod health;
+mod metrics;
 mod service;
+mod shutdown;
+mod telemetry;
+#[cfg(test)]
 mod test_utils;
+mod tls;
+mod webhook;
 
-use abi::{reservation_service_server::ReservationServiceServer, Config, Reservation};
-use futures::Stream;
-use reservation::ReservationStore;
-use std::pin::Pin;
-use tokio::sync::mpsc;
+use abi::{
+    reservation_service_server::ReservationServiceServer,
+    webhook_service_server::WebhookServiceServer, Config, ExportResponse, Reservation,
+    ReservationEvent, ResourcesConfig, Validator,
+};
+use anyhow::Context;
+use futures::{Future, Stream};
+use reservation::{register_resources, sink_from_config, ReservationStore};
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    pin::Pin,
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
 use tonic::{transport::Server, Status};
 
-pub struct ReservationService {
+pub use cli::Cli;
+pub use command::ServerCli;
+pub use telemetry::{init_tracing, shutdown_tracing};
+
+/// the gRPC service, generic over the store so it could be tested without a database
+pub struct ReservationService<R = ReservationStore> {
+    store: R,
+    admins: Vec<String>,
+    resources: ResourcesConfig,
+}
+
+/// the webhook management, only the admins could call it
+pub struct WebhookService {
     store: ReservationStore,
+    admins: Vec<String>,
+    secret_key: Option<String>,
 }
 
 pub struct TonicReceiverStream<T> {
@@ -17,13 +51,171 @@ pub struct TonicReceiverStream<T> {
 }
 
 type ReservationStream = Pin<Box<dyn Stream<Item = Result<Reservation, Status>> + Send>>;
+type ExportStream = Pin<Box<dyn Stream<Item = Result<ExportResponse, Status>> + Send>>;
+type ReservationEventStream = Pin<Box<dyn Stream<Item = Result<ReservationEvent, Status>> + Send>>;
+
+/// find the config file: $RESERVATION_CONFIG, ./reservation.yml, ~/.config/reservation.yml or /etc/reservation.yml
+pub fn config_filename() -> Option<String> {
+    if let Ok(filename) = std::env::var("RESERVATION_CONFIG") {
+        return Some(filename);
+    }
+    let home = shellexpand::tilde("~/.config/reservation.yml");
+    let found = ["./reservation.yml", home.as_ref(), "/etc/reservation.yml"]
+        .into_iter()
+        .find(|p| Path::new(p).exists())
+        .map(|p| p.to_string());
+    found
+}
 
+/// load the config from the defaults, the file (or the one found by config_filename),
+/// the env vars and the `key=value` overrides, in this order
+pub fn load_config(
+    filename: Option<String>,
+    overrides: &[(String, String)],
+) -> Result<Config, abi::Error> {
+    let mut loader = abi::ConfigLoader::new().env();
+    if let Some(filename) = filename.or_else(config_filename) {
+        loader = loader.file(filename);
+    }
+    for (key, value) in overrides {
+        loader = loader.set(key, value);
+    }
+    loader.load()
+}
+
+/// start the server, it's shut down gracefully on SIGTERM or SIGINT
 pub async fn start_server(config: &Config) -> Result<(), anyhow::Error> {
+    start_server_with_shutdown(config, shutdown::signal()).await
+}
+
+/// start the server, once the signal is resolved new connections are refused, listen
+/// subscribers are notified and the in-flight requests are drained within the timeout
+pub async fn start_server_with_shutdown(
+    config: &Config,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), anyhow::Error> {
+    // the config might not come from the loader, e.g. the REST gateway with mutual TLS
+    config.validate()?;
     let addr = format!("{}:{}", config.server.host, config.server.port).parse()?;
     let service = ReservationService::from_config(config).await?;
+    let store = service.store.clone();
+    register_resources(config.resources.0.keys().cloned());
+    store
+        .check_schema()
+        .await
+        .context("refuse to start, run `migrate up` or `serve --migrate` first")?;
+    let webhook = WebhookService::new(store.clone())
+        .with_admins(config.server.admins.clone())
+        .with_secret_key(config.webhook.secret_key.clone());
+    if config.webhook.secret_key.is_none() {
+        eprintln!("webhook.secret_key is not set, the webhook secrets are stored in plaintext");
+    }
+    let mut tasks = vec![];
+
+    let dispatcher = store.webhook_dispatcher(&config.webhook);
+    tasks.push(tokio::spawn(dispatcher.run()));
+
+    if let Some(sink) = &config.outbox {
+        let relay = store.outbox_relay("outbox-relay", sink_from_config(sink).await?);
+        tasks.push(tokio::spawn(relay.run(Duration::from_secs(5))));
+    }
+
+    let (reporter, health) = tonic_health::server::health_reporter();
+    tasks.push(tokio::spawn(health::report_health(
+        store.clone(),
+        reporter.clone(),
+        Duration::from_secs(5),
+    )));
+
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(abi::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(
+            tonic_health::proto::GRPC_HEALTH_V1_FILE_DESCRIPTOR_SET,
+        )
+        .build()?;
+
+    // the gateway is drained with the gRPC server instead of aborted with the tasks
+    let mut gateway = None;
+    if let Some(port) = config.server.http_port {
+        let http_addr = format!("{}:{}", config.server.host, port).parse()?;
+        let router = gateway::router(store.clone(), config.resources.clone());
+        let store = store.clone();
+        // bind before spawning, so that a taken port fails the start
+        let server = axum::Server::try_bind(&http_addr)?
+            .serve(router.into_make_service())
+            .with_graceful_shutdown(async move { store.shutdown_requested().await });
+        println!("REST gateway listening on {}", http_addr);
+        gateway = Some(tokio::spawn(async move {
+            if let Err(err) = server.await {
+                eprintln!("REST gateway stopped: {:?}", err);
+            }
+        }));
+    }
+
+    if let Some(port) = config.server.metrics_port {
+        let metrics_addr: SocketAddr = format!("{}:{}", config.server.host, port).parse()?;
+        let listener = TcpListener::bind(metrics_addr)
+            .with_context(|| format!("failed to bind the metrics port {}", metrics_addr))?;
+        let store = store.clone();
+        println!("Metrics listening on {}", metrics_addr);
+        tasks.push(tokio::spawn(async move {
+            if let Err(err) = metrics::serve_metrics(listener, store).await {
+                eprintln!("Metrics server stopped: {:?}", err);
+            }
+        }));
+    }
+
     let service = ReservationServiceServer::new(service);
+    let webhook = WebhookServiceServer::new(webhook);
+
+    // the drain timeout starts when the signal is received
+    let (draining_tx, draining_rx) = oneshot::channel();
+    let shutdown = {
+        let store = store.clone();
+        let mut reporter = reporter;
+        async move {
+            signal.await;
+            println!("Shutting down, draining in-flight requests");
+            // the listen streams never end by themselves, the REST gateway is shut down too
+            store.shutdown();
+            health::set_not_serving(&mut reporter).await;
+            let _ = draining_tx.send(());
+        }
+    };
 
     println!("Listening on {}", addr);
-    Server::builder().add_service(service).serve(addr).await?;
-    Ok(())
+    let mut builder = Server::builder();
+    if let Some(tls) = &config.server.tls {
+        builder = builder.tls_config(tls::server_tls_config(tls)?)?;
+    }
+    let server = builder
+        .layer(metrics::MetricsLayer)
+        .layer(telemetry::TraceLayer)
+        .add_service(health)
+        .add_service(reflection)
+        .add_service(service)
+        .add_service(webhook)
+        .serve_with_shutdown(addr, shutdown);
+    let drain = async {
+        let result = server.await;
+        if let Some(gateway) = gateway.as_mut() {
+            let _ = gateway.await;
+        }
+        result
+    };
+
+    let drain_timeout = Duration::from_millis(config.server.drain_timeout_ms);
+    let result = tokio::select! {
+        result = drain => result.map_err(anyhow::Error::from),
+        _ = shutdown::drain_deadline(draining_rx, drain_timeout) => {
+            eprintln!("Drain timeout, in-flight requests are dropped");
+            Ok(())
+        }
+    };
+
+    for task in tasks.into_iter().chain(gateway) {
+        task.abort();
+    }
+    store.close().await;
+    result
 }
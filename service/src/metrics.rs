@@ -0,0 +1,231 @@
+use std::{
+    collections::HashSet,
+    net::TcpListener,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, State},
+    routing::get,
+    Router,
+};
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    TextEncoder,
+};
+use prost::Message;
+use prost_types::FileDescriptorSet;
+use reservation::ReservationStore;
+use tonic::codegen::http;
+use tower::{Layer, Service};
+
+static RPC_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "reservation_rpc_requests_total",
+        "gRPC and REST requests by method and result code",
+        &["method", "code"]
+    )
+    .unwrap()
+});
+
+static RPC_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "reservation_rpc_duration_seconds",
+        "Latency of the gRPC and REST requests by method and result code",
+        &["method", "code"]
+    )
+    .unwrap()
+});
+
+/// paths of the gRPC methods served, e.g. `/reservation.ReservationService/reserve`
+static METHODS: Lazy<HashSet<String>> = Lazy::new(|| {
+    [
+        abi::FILE_DESCRIPTOR_SET,
+        tonic_health::proto::GRPC_HEALTH_V1_FILE_DESCRIPTOR_SET,
+        tonic_reflection::proto::FILE_DESCRIPTOR_SET,
+    ]
+    .into_iter()
+    .flat_map(|set| FileDescriptorSet::decode(set).unwrap().file)
+    .flat_map(|file| {
+        let package = file.package().to_string();
+        file.service.into_iter().flat_map(move |service| {
+            let prefix = format!("/{}.{}/", package, service.name());
+            service
+                .method
+                .into_iter()
+                .map(move |method| format!("{}{}", prefix, method.name()))
+        })
+    })
+    .collect()
+});
+
+/// the method label of the request path, anything else is "unknown" so that clients
+/// can't make up label values
+fn method_label(path: &str) -> &'static str {
+    METHODS.get(path).map(|m| m.as_str()).unwrap_or("unknown")
+}
+
+/// the method label of the REST request, e.g. `GET /v1/reservations/:id`, None for gRPC
+pub(crate) fn route_label<B>(req: &http::Request<B>) -> Option<String> {
+    let route = req.extensions().get::<MatchedPath>()?;
+    Some(format!("{} {}", req.method(), route.as_str()))
+}
+
+/// record requests and latency of every gRPC method, and of every REST route if it's a route
+/// layer. For streaming methods the latency is the time until the stream is started. The code
+/// of the REST requests is the HTTP status
+#[derive(Debug, Clone, Default)]
+pub struct MetricsLayer;
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        // the service which is ready is taken, leave the clone for the next call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let method = method_label(req.uri().path());
+        let route = route_label(&req);
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let rsp = inner.call(req).await?;
+            let (method, code) = match &route {
+                Some(route) => (route.as_str(), rsp.status().as_str().to_string()),
+                None => (method, format!("{:?}", grpc_code(&rsp))),
+            };
+            RPC_REQUESTS.with_label_values(&[method, &code]).inc();
+            RPC_DURATION
+                .with_label_values(&[method, &code])
+                .observe(start.elapsed().as_secs_f64());
+            Ok(rsp)
+        })
+    }
+}
+
+/// errors are returned as trailers-only responses, so the status is in the headers
+pub(crate) fn grpc_code<B>(rsp: &http::Response<B>) -> tonic::Code {
+    rsp.headers()
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i32>().ok())
+        .map(tonic::Code::from_i32)
+        .unwrap_or(tonic::Code::Ok)
+}
+
+/// serve the metrics in the prometheus text format at /metrics, the listener is bound by the
+/// caller so that a taken port fails the start
+pub async fn serve_metrics(
+    listener: TcpListener,
+    store: ReservationStore,
+) -> Result<(), anyhow::Error> {
+    let router = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(store);
+    axum::Server::from_tcp(listener)?
+        .serve(router.into_make_service())
+        .await?;
+    Ok(())
+}
+
+async fn metrics(State(store): State<ReservationStore>) -> String {
+    store.record_pool_stats();
+    let mut buf = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buf)
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[cfg(test)]
+pub(crate) fn rpc_requests(method: &str, code: &str) -> u64 {
+    RPC_REQUESTS.with_label_values(&[method, code]).get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::method_label;
+    use crate::{
+        start_server,
+        test_utils::{free_port, TestConfig},
+    };
+    use abi::{reservation_service_client::ReservationServiceClient, GetRequest};
+    use std::time::Duration;
+
+    #[test]
+    fn unknown_paths_should_share_one_label() {
+        for path in [
+            "/reservation.ReservationService/reserve",
+            "/reservation.WebhookService/subscribe",
+            "/grpc.health.v1.Health/Check",
+            "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+        ] {
+            assert_eq!(method_label(path), path);
+        }
+        assert_eq!(
+            method_label("/reservation.ReservationService/x1"),
+            "unknown"
+        );
+        assert_eq!(method_label("/"), "unknown");
+    }
+
+    #[tokio::test]
+    async fn metrics_should_count_rpc_requests() {
+        let mut config = TestConfig::default();
+        let metrics_port = free_port();
+        config.config.server.metrics_port = Some(metrics_port);
+        let server_config = config.config.clone();
+        tokio::spawn(async move { start_server(&server_config).await.unwrap() });
+
+        let mut client = None;
+        for _ in 0..50 {
+            if let Ok(c) = ReservationServiceClient::connect(config.server.url(false)).await {
+                client = Some(c);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let mut client = client.expect("server is not started");
+        let err = client.get(GetRequest { id: 10000 }).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let uri = format!("http://127.0.0.1:{}/metrics", metrics_port)
+            .parse()
+            .unwrap();
+        let rsp = hyper::Client::new().get(uri).await.unwrap();
+        let body = hyper::body::to_bytes(rsp.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(
+            r#"reservation_rpc_requests_total{code="NotFound",method="/reservation.ReservationService/get"} 1"#
+        ));
+        assert!(body.contains(r#"reservation_store_duration_seconds_count{method="get"}"#));
+        assert!(body.contains(r#"reservation_db_pool_connections{state="idle"}"#));
+    }
+}
@@ -3,22 +3,86 @@ use std::task::Poll;
 
 use abi::reservation_service_server::ReservationService as ReservationServiceTrait;
 use abi::{
-    CancelRequest, CancelResponse, Config, ConfirmRequest, ConfirmResponse, FilterRequest,
-    FilterResponse, GetRequest, GetResponse, ListenRequest, QueryRequest, ReserveRequest,
-    ReserveResponse, UpdateRequest, UpdateResponse,
+    ical_footer, ical_header, parse_ical, CancelRequest, CancelResponse, Config, ConfirmRequest,
+    ConfirmResponse, EventsRequest, EventsResponse, ExportRequest, ExportResponse, FilterRequest,
+    FilterResponse, GetRequest, GetResponse, HistoryRequest, HistoryResponse, ImportRequest,
+    ImportResponse, ListenRequest, QueryRequest, RequestContext, ReserveRequest, ReserveResponse,
+    ResourcesConfig, UpdateRequest, UpdateResponse,
 };
-use futures::Stream;
+use chrono::Utc;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 use reservation::{Reservation, ReservationStore};
 use tokio::sync::mpsc;
 use tonic::{Response, Status};
 
-use crate::{ReservationService, ReservationStream, TonicReceiverStream};
+use crate::{
+    tls, ExportStream, ReservationEventStream, ReservationService, ReservationStream,
+    TonicReceiverStream,
+};
+
+/// metadata key of the caller id. It's not verified, so it's only advisory: any client could set
+/// it unless an authenticating proxy in front of the service overwrites it
+pub(crate) const ACTOR_ID_KEY: &str = "x-actor-id";
+/// metadata key of the request id
+pub(crate) const REQUEST_ID_KEY: &str = "x-request-id";
 
 impl ReservationService {
     pub async fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
-        Ok(Self {
-            store: ReservationStore::from_config(&config.db).await?,
-        })
+        let store = ReservationStore::from_config(&config.db).await?;
+        Ok(Self::new(store)
+            .with_admins(config.server.admins.clone())
+            .with_resources(config.resources.clone()))
+    }
+}
+
+impl<R: Reservation> ReservationService<R> {
+    pub fn new(store: R) -> Self {
+        Self {
+            store,
+            admins: vec![],
+            resources: ResourcesConfig::default(),
+        }
+    }
+
+    /// callers allowed to see the owners of others' reservations, matched against the client
+    /// certificate subjects
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    /// settings of the resources, e.g. the default time zone of their reservations
+    pub fn with_resources(mut self, resources: ResourcesConfig) -> Self {
+        self.resources = resources;
+        self
+    }
+}
+
+/// get the caller identity of the request, it is recorded in the reservation events.
+/// With mutual TLS it's the subject of the client certificate, the metadata is ignored.
+/// Only the callers with a certificate could be admins
+pub(crate) fn request_context<T>(request: &tonic::Request<T>, admins: &[String]) -> RequestContext {
+    let get = |key: &str| {
+        request
+            .metadata()
+            .get(key)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let context = match tls::peer_identity(request) {
+        Some(actor_id) => RequestContext::authenticated(actor_id, get(REQUEST_ID_KEY)),
+        None => RequestContext::new(get(ACTOR_ID_KEY), get(REQUEST_ID_KEY)),
+    };
+    context.with_admins(admins)
+}
+
+/// reject the callers which are not admins
+pub(crate) fn require_admin(context: &RequestContext) -> Result<(), abi::Error> {
+    if context.admin {
+        Ok(())
+    } else {
+        Err(abi::Error::PermissionDenied)
     }
 }
 
@@ -45,17 +109,25 @@ impl<T> Stream for TonicReceiverStream<T> {
 }
 
 #[tonic::async_trait]
-impl ReservationServiceTrait for ReservationService {
+impl<R> ReservationServiceTrait for ReservationService<R>
+where
+    R: Reservation + Send + Sync + 'static,
+{
     /// make a reservation
     async fn reserve(
         &self,
         request: tonic::Request<ReserveRequest>,
     ) -> Result<tonic::Response<ReserveResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
-        if request.reservation.is_none() {
-            return Err(Status::invalid_argument("missing reservation"));
-        }
-        let reservation = self.store.reserve(request.reservation.unwrap()).await?;
+        let mut reservation = match request.reservation {
+            Some(reservation) => reservation,
+            None => return Err(Status::invalid_argument("missing reservation")),
+        };
+        self.resources.fill_reservation(&mut reservation)?;
+        let reservation = store.reserve(reservation).await?;
         Ok(Response::new(ReserveResponse {
             reservation: Some(reservation),
         }))
@@ -66,8 +138,11 @@ impl ReservationServiceTrait for ReservationService {
         &self,
         request: tonic::Request<ConfirmRequest>,
     ) -> Result<tonic::Response<ConfirmResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
-        let reservation = self.store.confirm(request.id).await?;
+        let reservation = store.confirm(request.id).await?;
         Ok(Response::new(ConfirmResponse {
             reservation: Some(reservation),
         }))
@@ -78,8 +153,11 @@ impl ReservationServiceTrait for ReservationService {
         &self,
         request: tonic::Request<UpdateRequest>,
     ) -> Result<tonic::Response<UpdateResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
-        let reservation = self.store.update(request.id, request.note).await?;
+        let reservation = store.update(request.id, request.note).await?;
         Ok(Response::new(UpdateResponse {
             reservation: Some(reservation),
         }))
@@ -90,8 +168,11 @@ impl ReservationServiceTrait for ReservationService {
         &self,
         request: tonic::Request<CancelRequest>,
     ) -> Result<tonic::Response<CancelResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
-        let reservation = self.store.delete(request.id).await?;
+        let reservation = store.delete(request.id).await?;
         Ok(Response::new(CancelResponse {
             reservation: Some(reservation),
         }))
@@ -102,8 +183,11 @@ impl ReservationServiceTrait for ReservationService {
         &self,
         request: tonic::Request<GetRequest>,
     ) -> Result<tonic::Response<GetResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
-        let reservation = self.store.get(request.id).await?;
+        let reservation = store.get(request.id).await?;
         Ok(Response::new(GetResponse {
             reservation: Some(reservation),
         }))
@@ -116,49 +200,185 @@ impl ReservationServiceTrait for ReservationService {
         &self,
         request: tonic::Request<QueryRequest>,
     ) -> Result<tonic::Response<Self::queryStream>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
-        if request.query.is_none() {
-            return Err(Status::invalid_argument("missing query"));
-        }
-        let reservations = self.store.query(request.query.unwrap()).await;
+        let mut query = match request.query {
+            Some(query) => query,
+            None => return Err(Status::invalid_argument("missing query")),
+        };
+        self.resources.fill_query(&mut query);
+        let reservations = store.query(query).await;
         let stream = TonicReceiverStream::new(reservations);
         Ok(Response::new(Box::pin(stream)))
     }
 
+    /// Server streaming response type for the export method.
+    type exportStream = ExportStream;
+    /// export reservations of a user or resource as an iCalendar (.ics) document
+    async fn export(
+        &self,
+        request: tonic::Request<ExportRequest>,
+    ) -> Result<tonic::Response<Self::exportStream>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
+        let request = request.into_inner();
+        let mut query = match request.query {
+            Some(query) => query,
+            None => return Err(Status::invalid_argument("missing query")),
+        };
+        if query.user_id.is_empty() && query.resource_id.is_empty() {
+            return Err(Status::invalid_argument("missing user id or resource id"));
+        }
+        self.resources.fill_query(&mut query);
+
+        let stamp = Utc::now();
+        let reservations = TonicReceiverStream::new(store.query(query).await);
+        let events = reservations.map_ok(move |r| ExportResponse {
+            data: r.to_vevent(stamp),
+        });
+        let stream = stream::once(async {
+            Ok(ExportResponse {
+                data: ical_header(),
+            })
+        })
+        .chain(events)
+        .chain(stream::once(async {
+            Ok(ExportResponse {
+                data: ical_footer(),
+            })
+        }));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    /// import reservations of a resource from an iCalendar (.ics) document
+    async fn import(
+        &self,
+        request: tonic::Request<ImportRequest>,
+    ) -> Result<tonic::Response<ImportResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
+        let request = request.into_inner();
+        if request.resource_id.is_empty() {
+            return Err(Status::invalid_argument("missing resource id"));
+        }
+        let timezone = self.resources.timezone(&request.resource_id);
+        let mut reservations = parse_ical(
+            &request.data,
+            &request.resource_id,
+            &request.user_id,
+            timezone,
+        )?;
+        for reservation in reservations.iter_mut() {
+            self.resources.fill_reservation(reservation)?;
+        }
+        let (reservations, conflicts) = store
+            .import(reservations, request.dry_run, request.atomic)
+            .await?;
+        Ok(Response::new(ImportResponse {
+            reservations,
+            conflicts,
+        }))
+    }
+
     /// filter reservations, order by reservation id
     async fn filter(
         &self,
         request: tonic::Request<FilterRequest>,
     ) -> Result<tonic::Response<FilterResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
         let request = request.into_inner();
         if request.filter.is_none() {
             return Err(Status::invalid_argument("missing filter"));
         }
-        let reservations = self.store.filter(request.filter.unwrap()).await?;
-        Ok(Response::new(FilterResponse {
-            reservations: reservations,
-        }))
+        let reservations = store.filter(request.filter.unwrap()).await?;
+        Ok(Response::new(FilterResponse { reservations }))
     }
 
     /// Server streaming response type for the listen method.
-    type listenStream = ReservationStream;
-    /// another system could monitor reservation events: added/confirmed/cancelled
+    type listenStream = ReservationEventStream;
+    /// another system could monitor reservation events: added/confirmed/updated/cancelled
     async fn listen(
         &self,
-        _request: tonic::Request<ListenRequest>,
+        request: tonic::Request<ListenRequest>,
     ) -> Result<tonic::Response<Self::listenStream>, tonic::Status> {
-        todo!()
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
+        let events = store.listen().await;
+        let stream = TonicReceiverStream::new(events);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    /// get the change history of a reservation
+    async fn history(
+        &self,
+        request: tonic::Request<HistoryRequest>,
+    ) -> Result<tonic::Response<HistoryResponse>, tonic::Status> {
+        let store = self
+            .store
+            .with_context(request_context(&request, &self.admins));
+        let request = request.into_inner();
+        let events = store.history(request.id).await?;
+        Ok(Response::new(HistoryResponse { events }))
+    }
+
+    /// filter reservation events of all reservations, order by event id (admin)
+    async fn events(
+        &self,
+        request: tonic::Request<EventsRequest>,
+    ) -> Result<tonic::Response<EventsResponse>, tonic::Status> {
+        let context = request_context(&request, &self.admins);
+        require_admin(&context)?;
+        let store = self.store.with_context(context);
+        let request = request.into_inner();
+        if request.filter.is_none() {
+            return Err(Status::invalid_argument("missing filter"));
+        }
+        let events = store.events(request.filter.unwrap()).await?;
+        Ok(Response::new(EventsResponse { events }))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use abi::{Reservation, ReservationStatus};
+    use abi::{Reservation, ReservationEventType, ReservationQueryBuilder, ReservationStatus};
 
     use crate::test_utils::TestConfig;
 
     use super::*;
 
+    #[test]
+    fn claimed_actor_should_not_be_admin() {
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert(ACTOR_ID_KEY, "support".parse().unwrap());
+        let context = request_context(&request, &["support".to_string()]);
+        assert_eq!(context.actor_id, "support");
+        assert!(!context.authenticated);
+        assert!(!context.admin);
+    }
+
+    #[tokio::test]
+    async fn rpc_events_should_require_admin() {
+        let config = TestConfig::default();
+        let service = ReservationService::from_config(&config).await.unwrap();
+        let mut request = tonic::Request::new(EventsRequest {
+            filter: Some(abi::ReservationEventFilter::default()),
+        });
+        request
+            .metadata_mut()
+            .insert(ACTOR_ID_KEY, "support".parse().unwrap());
+        let err = service.events(request).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    }
+
     #[tokio::test]
     async fn rpc_reserve_should_work() {
         let config = TestConfig::default();
@@ -186,5 +406,261 @@ mod tests {
         assert_eq!(reservation.end, source.end);
         assert_eq!(reservation.note, source.note);
         assert_eq!(reservation.status, source.status);
+
+        // the status is optional, same as the REST gateway
+        let source = Reservation {
+            status: ReservationStatus::Unknown as i32,
+            resource_id: "ixia-3231".to_string(),
+            ..source
+        };
+        let request = tonic::Request::new(ReserveRequest {
+            reservation: Some(source),
+        });
+        let response = service.reserve(request).await.unwrap();
+        let reservation = response.into_inner().reservation.unwrap();
+        assert_eq!(reservation.status, ReservationStatus::Pending as i32);
+        let request = tonic::Request::new(GetRequest { id: reservation.id });
+        let saved = service.get(request).await.unwrap().into_inner();
+        assert_eq!(saved.reservation.unwrap().status, reservation.status);
+    }
+
+    #[tokio::test]
+    async fn rpc_export_should_stream_ical_document() {
+        let config = TestConfig::default();
+
+        let service = ReservationService::from_config(&config).await.unwrap();
+        let source = Reservation::new(
+            "alon".to_string(),
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test".to_string(),
+            ReservationStatus::Pending,
+        );
+        let request = tonic::Request::new(ReserveRequest {
+            reservation: Some(source),
+        });
+        let reservation = service
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("alon")
+            .build()
+            .unwrap();
+        let request = tonic::Request::new(ExportRequest { query: Some(query) });
+        let stream = service.export(request).await.unwrap().into_inner();
+        let chunks: Vec<_> = stream.map(|chunk| chunk.unwrap().data).collect().await;
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("BEGIN:VCALENDAR"));
+        assert!(chunks[1].contains(&format!("UID:{}@reservation-service", reservation.id)));
+        assert!(chunks[1].contains("STATUS:TENTATIVE"));
+        assert_eq!(chunks[2], "END:VCALENDAR\r\n");
+    }
+
+    #[tokio::test]
+    async fn rpc_import_should_expand_recurring_events() {
+        let config = TestConfig::default();
+
+        let service = ReservationService::from_config(&config).await.unwrap();
+        let data = "BEGIN:VCALENDAR\r\n\
+                    BEGIN:VEVENT\r\n\
+                    DTSTART:20230102T090000Z\r\n\
+                    DTEND:20230102T100000Z\r\n\
+                    RRULE:FREQ=WEEKLY;COUNT=3\r\n\
+                    SUMMARY:weekly sync\r\n\
+                    END:VEVENT\r\n\
+                    END:VCALENDAR\r\n";
+        let request = tonic::Request::new(ImportRequest {
+            data: data.to_string(),
+            resource_id: "ixia-3230".to_string(),
+            user_id: "alon".to_string(),
+            dry_run: false,
+            atomic: true,
+        });
+        let response = service.import(request).await.unwrap().into_inner();
+        assert_eq!(response.reservations.len(), 3);
+        assert!(response.conflicts.is_empty());
+        assert!(response.reservations.iter().all(|r| r.id > 0));
+    }
+
+    #[tokio::test]
+    async fn rpc_history_should_work() {
+        let config = TestConfig::default();
+
+        let service = ReservationService::from_config(&config).await.unwrap();
+        let source = Reservation::new(
+            "alon".to_string(),
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test".to_string(),
+            ReservationStatus::Pending,
+        );
+        let request = tonic::Request::new(ReserveRequest {
+            reservation: Some(source),
+        });
+        let reservation = service
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let mut request = tonic::Request::new(CancelRequest { id: reservation.id });
+        request
+            .metadata_mut()
+            .insert(ACTOR_ID_KEY, "support".parse().unwrap());
+        service.cancel(request).await.unwrap();
+
+        let request = request_as("alon", HistoryRequest { id: reservation.id });
+        let events = service.history(request).await.unwrap().into_inner().events;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].event_type, ReservationEventType::Deleted as i32);
+        assert_eq!(events[1].old, Some(reservation));
+        assert_eq!(events[1].actor_id, "support");
+
+        // only the owner sees the owner
+        let request = request_as(
+            "support",
+            HistoryRequest {
+                id: events[1].reservation_id,
+            },
+        );
+        let events = service.history(request).await.unwrap().into_inner().events;
+        assert_eq!(events[1].old.as_ref().unwrap().user_id, "");
+    }
+
+    #[tokio::test]
+    async fn rpc_should_work_with_in_memory_store() {
+        let service = ReservationService::new(reservation::InMemoryReservationStore::new());
+        let source = Reservation::new(
+            "alon".to_string(),
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test".to_string(),
+            ReservationStatus::Pending,
+        );
+        let request = tonic::Request::new(ReserveRequest {
+            reservation: Some(source.clone()),
+        });
+        let reservation = service
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        assert_eq!(reservation.id, 1);
+
+        let request = tonic::Request::new(ReserveRequest {
+            reservation: Some(source),
+        });
+        let status = service.reserve(request).await.unwrap_err();
+        assert!(matches!(
+            abi::Error::from(status),
+            abi::Error::ConflictReservation(_)
+        ));
+
+        let request = request_as("alon", GetRequest { id: reservation.id });
+        let response = service.get(request).await.unwrap().into_inner();
+        assert_eq!(response.reservation, Some(reservation));
+    }
+
+    #[tokio::test]
+    async fn rpc_should_use_timezone_of_resource() {
+        let resources: abi::ResourcesConfig =
+            serde_yaml::from_str("ixia-3230:\n  timezone: America/Los_Angeles").unwrap();
+        let service = ReservationService::new(reservation::InMemoryReservationStore::new())
+            .with_resources(resources);
+        let reserve = |rid: &str, start: &str, end: &str| {
+            tonic::Request::new(ReserveRequest {
+                reservation: Some(Reservation::new(
+                    "alon",
+                    rid,
+                    start.parse().unwrap(),
+                    end.parse().unwrap(),
+                    "",
+                    ReservationStatus::Pending,
+                )),
+            })
+        };
+        let request = reserve(
+            "ixia-3230",
+            "2022-12-26T22:00:00-0800",
+            "2022-12-26T23:00:00-0800",
+        );
+        let reservation = service.reserve(request).await.unwrap().into_inner();
+        let reservation = reservation.reservation.unwrap();
+        assert_eq!(reservation.timezone, "America/Los_Angeles");
+        let request = reserve(
+            "ixia-3231",
+            "2022-12-26T22:00:00-0800",
+            "2022-12-26T23:00:00-0800",
+        );
+        let other = service.reserve(request).await.unwrap().into_inner();
+        assert_eq!(other.reservation.unwrap().timezone, "UTC");
+
+        // the local day is in the zone of the resource
+        let query = ReservationQueryBuilder::default()
+            .resource_id("ixia-3230")
+            .local_day("2022-12-26")
+            .build()
+            .unwrap();
+        let request = request_as("alon", QueryRequest { query: Some(query) });
+        let stream = service.query(request).await.unwrap().into_inner();
+        let reservations: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(reservations, vec![reservation]);
+    }
+
+    #[tokio::test]
+    async fn rpc_should_resolve_dates_with_resource_check_in() {
+        let resources: abi::ResourcesConfig = serde_yaml::from_str(
+            "ocean-view-room-713:\n  timezone: Asia/Tokyo\n  check_in: \"14:00\"\n  check_out: \"10:30\"",
+        )
+        .unwrap();
+        let service = ReservationService::new(reservation::InMemoryReservationStore::new())
+            .with_resources(resources);
+        let reservation = Reservation::new_for_dates(
+            "alon",
+            "ocean-view-room-713",
+            "2022-12-26".parse().unwrap(),
+            None,
+            "",
+            ReservationStatus::Pending,
+        );
+        let request = tonic::Request::new(ReserveRequest {
+            reservation: Some(reservation),
+        });
+        let reservation = service.reserve(request).await.unwrap().into_inner();
+        let reservation = reservation.reservation.unwrap();
+        assert_eq!(reservation.start_date, "2022-12-26");
+        assert_eq!(reservation.end_date, "2022-12-27");
+        assert_eq!(
+            reservation.start,
+            Some("2022-12-26T05:00:00Z".parse().unwrap())
+        );
+        assert_eq!(
+            reservation.end,
+            Some("2022-12-27T01:30:00Z".parse().unwrap())
+        );
+
+        let request = request_as("alon", GetRequest { id: reservation.id });
+        let response = service.get(request).await.unwrap().into_inner();
+        assert_eq!(response.reservation, Some(reservation));
+    }
+
+    /// a request from the caller claiming the actor id
+    fn request_as<T>(actor_id: &str, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        request
+            .metadata_mut()
+            .insert(ACTOR_ID_KEY, actor_id.parse().unwrap());
+        request
     }
 }
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+/// resolved on SIGINT, or SIGTERM on unix
+pub(crate) async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            eprintln!("Failed to listen for SIGINT: {:?}", err);
+            futures::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                eprintln!("Failed to listen for SIGTERM: {:?}", err);
+                futures::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = futures::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// resolved when the timeout is passed after draining started, never if it's not started
+pub(crate) async fn drain_deadline(draining: oneshot::Receiver<()>, timeout: Duration) {
+    if draining.await.is_err() {
+        // the server is stopped without shutdown
+        return futures::future::pending().await;
+    }
+    tokio::time::sleep(timeout).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        start_server_with_shutdown,
+        test_utils::{free_port, TestConfig},
+    };
+    use abi::{reservation_service_client::ReservationServiceClient, ListenRequest};
+    use futures::FutureExt;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn drain_deadline_should_start_with_draining() {
+        let (tx, rx) = oneshot::channel();
+        let deadline = drain_deadline(rx, Duration::from_millis(100));
+        tokio::pin!(deadline);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!((&mut deadline).now_or_never().is_none());
+
+        let start = Instant::now();
+        tx.send(()).unwrap();
+        deadline.await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn taken_http_ports_should_fail_the_start() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        for set_port in [
+            |config: &mut abi::Config, port| config.server.http_port = Some(port),
+            |config: &mut abi::Config, port| config.server.metrics_port = Some(port),
+        ] {
+            let mut config = TestConfig::default();
+            config.config.server.host = "127.0.0.1".to_string();
+            set_port(&mut config.config, port);
+            let result = tokio::time::timeout(
+                Duration::from_secs(5),
+                start_server_with_shutdown(&config.config, futures::future::pending()),
+            )
+            .await
+            .expect("server should not start");
+            assert!(result.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_should_end_listen_streams() {
+        let config = TestConfig::default();
+        let server_config = config.config.clone();
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let signal = async {
+                rx.await.ok();
+            };
+            start_server_with_shutdown(&server_config, signal).await
+        });
+
+        let mut client = None;
+        for _ in 0..50 {
+            if let Ok(c) = ReservationServiceClient::connect(config.server.url(false)).await {
+                client = Some(c);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let mut client = client.expect("server is not started");
+        let mut stream = client.listen(ListenRequest {}).await.unwrap().into_inner();
+
+        tx.send(()).unwrap();
+        let err = stream.message().await.unwrap_err();
+        assert_eq!(abi::Error::from(err), abi::Error::ShuttingDown);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server is not stopped");
+        result.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_should_drain_the_rest_gateway() {
+        let mut config = TestConfig::default();
+        let http_port = free_port();
+        config.config.server.http_port = Some(http_port);
+        let server_config = config.config.clone();
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let signal = async {
+                rx.await.ok();
+            };
+            start_server_with_shutdown(&server_config, signal).await
+        });
+
+        let uri: hyper::Uri = format!("http://127.0.0.1:{}/v1/reservations/listen", http_port)
+            .parse()
+            .unwrap();
+        let mut rsp = None;
+        for _ in 0..50 {
+            if let Ok(r) = hyper::Client::new().get(uri.clone()).await {
+                rsp = Some(r);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let rsp = rsp.expect("gateway is not started");
+
+        tx.send(()).unwrap();
+        // the stream is ended with the error event instead of being cut off
+        let body = hyper::body::to_bytes(rsp.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("event:error"), "{}", body);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server is not stopped");
+        result.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+use std::task::{Context, Poll};
+
+use abi::TracingConfig;
+use axum::extract::MatchedPath;
+use futures::future::BoxFuture;
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    sdk::{propagation::TraceContextPropagator, trace, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use tonic::codegen::http;
+use tower::{Layer, Service};
+use tracing::{field::Empty, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::metrics::grpc_code;
+
+/// initialize the global tracing subscriber, the level is set by RUST_LOG (default info)
+pub fn init_tracing(config: &TracingConfig) -> Result<(), anyhow::Error> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer());
+
+    match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let resource = Resource::new(vec![KeyValue::new(
+                "service.name",
+                config.service_name.clone(),
+            )]);
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(resource))
+                .install_batch(opentelemetry::runtime::Tokio)?;
+            registry
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .try_init()?;
+        }
+        None => registry.try_init()?,
+    }
+    Ok(())
+}
+
+/// flush the spans which are not exported yet
+pub fn shutdown_tracing() {
+    global::shutdown_tracer_provider();
+}
+
+/// start a span for every gRPC request or REST route, continuing the W3C trace context in the
+/// metadata or the headers
+#[derive(Debug, Clone, Default)]
+pub struct TraceLayer;
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = TraceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TraceService { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TraceService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for TraceService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        // the service which is ready is taken, leave the clone for the next call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        // the REST routes are matched if it's a route layer of the gateway
+        let route = req.extensions().get::<MatchedPath>().cloned();
+        let span = match &route {
+            Some(route) => tracing::info_span!(
+                "http",
+                otel.name = %format!("{} {}", req.method(), route.as_str()),
+                otel.kind = "server",
+                http.method = %req.method(),
+                http.route = %route.as_str(),
+                http.status_code = Empty,
+            ),
+            None => {
+                let path = req.uri().path();
+                let (service, method) = path
+                    .trim_start_matches('/')
+                    .split_once('/')
+                    .unwrap_or_default();
+                tracing::info_span!(
+                    "grpc",
+                    otel.name = %path,
+                    otel.kind = "server",
+                    rpc.system = "grpc",
+                    rpc.service = %service,
+                    rpc.method = %method,
+                    rpc.grpc.status_code = Empty,
+                )
+            }
+        };
+        let parent =
+            global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(req.headers())));
+        span.set_parent(parent);
+
+        Box::pin(
+            async move {
+                let rsp = inner.call(req).await?;
+                match route {
+                    Some(_) => Span::current().record("http.status_code", rsp.status().as_u16()),
+                    None => Span::current().record("rpc.grpc.status_code", grpc_code(&rsp) as i32),
+                };
+                Ok(rsp)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// gRPC metadata is carried in the HTTP/2 headers
+struct HeaderExtractor<'a>(&'a http::HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::TestConfig, ReservationService};
+    use abi::{reservation_service_server::ReservationServiceServer, ReserveRequest};
+    use opentelemetry::{
+        sdk::{
+            export::trace::{ExportResult, SpanData, SpanExporter},
+            trace::TracerProvider,
+        },
+        trace::{SpanId, TraceId, TracerProvider as _},
+        Key,
+    };
+    use prost::Message;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tower::ServiceExt;
+
+    const TRACE_PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[derive(Debug, Clone, Default)]
+    struct InMemoryExporter(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for InMemoryExporter {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.0.lock().unwrap().extend(batch);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    impl InMemoryExporter {
+        /// spans are exported in another thread, wait until the span is there
+        async fn wait_for(&self, name: &str) -> SpanData {
+            for _ in 0..50 {
+                let spans = self.0.lock().unwrap().clone();
+                if let Some(span) = spans.into_iter().find(|s| s.name == name) {
+                    return span;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("span {} is not exported", name);
+        }
+    }
+
+    /// a length-prefixed gRPC message
+    fn grpc_frame(msg: impl Message) -> Vec<u8> {
+        let data = msg.encode_to_vec();
+        let mut frame = vec![0];
+        frame.extend((data.len() as u32).to_be_bytes());
+        frame.extend(data);
+        frame
+    }
+
+    #[tokio::test]
+    async fn rpc_span_should_continue_incoming_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = InMemoryExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = TestConfig::default();
+        let service = ReservationService::from_config(&config).await.unwrap();
+        let service = TraceLayer.layer(ReservationServiceServer::new(service));
+
+        let reservation = abi::Reservation::new(
+            "alon",
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test device reservation",
+            abi::ReservationStatus::Pending,
+        );
+        let body = grpc_frame(ReserveRequest {
+            reservation: Some(reservation),
+        });
+        let req = http::Request::post("/reservation.ReservationService/reserve")
+            .header("content-type", "application/grpc")
+            .header("traceparent", TRACE_PARENT)
+            .body(hyper::Body::from(body))
+            .unwrap();
+        let rsp = service.oneshot(req).await.unwrap();
+        assert_eq!(rsp.status(), http::StatusCode::OK);
+        hyper::body::to_bytes(rsp.into_body()).await.unwrap();
+
+        let rpc = exporter
+            .wait_for("/reservation.ReservationService/reserve")
+            .await;
+        assert_eq!(
+            rpc.span_context.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        assert_eq!(
+            rpc.parent_span_id,
+            SpanId::from_hex("00f067aa0ba902b7").unwrap()
+        );
+        assert_eq!(
+            rpc.attributes
+                .get(&Key::new("rpc.grpc.status_code"))
+                .map(|v| v.to_string()),
+            Some("0".to_string())
+        );
+
+        let store = exporter.wait_for("reserve").await;
+        assert_eq!(store.parent_span_id, rpc.span_context.span_id());
+        let statement = store
+            .attributes
+            .get(&Key::new("db.statement"))
+            .unwrap()
+            .to_string();
+        assert!(statement.starts_with("INSERT INTO reservations"));
+        assert_eq!(
+            store
+                .attributes
+                .get(&Key::new("db.rows"))
+                .map(|v| v.to_string()),
+            Some("1".to_string())
+        );
+    }
+}
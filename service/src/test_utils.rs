@@ -21,10 +21,17 @@ impl TestConfig {
         let mut config = Config::load(filename).unwrap();
         let db = TestPg::new(config.db.server_url(), Path::new("../migrations"));
         config.db.name = db.dbname.clone();
+        config.server.port = free_port();
         Self { db, config }
     }
 }
 
+/// a port nobody listens on, so that the servers of the tests don't collide
+pub fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
 impl Default for TestConfig {
     fn default() -> Self {
         Self::new("fixtures/config.yml")
@@ -0,0 +1,303 @@
+use std::fs;
+
+use abi::TlsConfig;
+use anyhow::Context;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// load the PEM files, the client CA enables mutual TLS
+pub(crate) fn server_tls_config(config: &TlsConfig) -> Result<ServerTlsConfig, anyhow::Error> {
+    let read = |filename: &str| {
+        fs::read(filename).with_context(|| format!("failed to read TLS file {}", filename))
+    };
+    let identity = Identity::from_pem(read(&config.cert)?, read(&config.key)?);
+    let mut tls = ServerTlsConfig::new().identity(identity);
+    if let Some(ca) = &config.client_ca {
+        tls = tls.client_ca_root(Certificate::from_pem(read(ca)?));
+    }
+    Ok(tls)
+}
+
+/// the caller identity from the verified client certificate, None without mutual TLS
+pub(crate) fn peer_identity<T>(request: &tonic::Request<T>) -> Option<String> {
+    let certs = request.peer_certs()?;
+    // the peer certificates are DER encoded, the first one is the client's own
+    certs
+        .first()
+        .and_then(|cert| certificate_identity(cert.get_ref()))
+}
+
+/// common name of the subject, or the whole subject if it has no common name
+fn certificate_identity(der: &[u8]) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let subject = cert.subject();
+    let identity = match subject.iter_common_name().next() {
+        Some(cn) => cn.as_str().ok()?.to_string(),
+        None => subject.to_string(),
+    };
+    (!identity.is_empty()).then_some(identity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{service::ACTOR_ID_KEY, start_server, test_utils::TestConfig};
+    use abi::{
+        reservation_service_client::ReservationServiceClient,
+        webhook_service_client::WebhookServiceClient, HistoryRequest, ListWebhooksRequest,
+        ReservationStatus, ReserveRequest, SubscribeRequest, Webhook,
+    };
+    use rcgen::{
+        BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose,
+        IsCa,
+    };
+    use std::{path::PathBuf, time::Duration};
+    use tonic::transport::{Channel, ClientTlsConfig};
+
+    struct TestCerts {
+        dir: PathBuf,
+        ca: rcgen::Certificate,
+    }
+
+    impl TestCerts {
+        /// a CA and a server certificate for localhost signed by it
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params.distinguished_name = dn("reservation test ca");
+            let ca = rcgen::Certificate::from_params(params).unwrap();
+            fs::write(dir.join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+
+            let certs = Self { dir, ca };
+            let server = rcgen::Certificate::from_params(CertificateParams::new(vec![
+                "localhost".to_string()
+            ]))
+            .unwrap();
+            fs::write(certs.path("server.pem"), certs.sign(&server)).unwrap();
+            fs::write(certs.path("server.key"), server.serialize_private_key_pem()).unwrap();
+            certs
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.dir.join(name).to_str().unwrap().to_string()
+        }
+
+        fn sign(&self, cert: &rcgen::Certificate) -> String {
+            cert.serialize_pem_with_signer(&self.ca).unwrap()
+        }
+
+        fn tls_config(&self) -> TlsConfig {
+            TlsConfig {
+                cert: self.path("server.pem"),
+                key: self.path("server.key"),
+                client_ca: Some(self.path("ca.pem")),
+                ca: None,
+                domain: None,
+                client_cert: None,
+                client_key: None,
+            }
+        }
+
+        /// client certificate with the common name, signed by the CA
+        fn client_identity(&self, common_name: &str) -> Identity {
+            let (cert, key) = self.client_pem(common_name);
+            Identity::from_pem(cert, key)
+        }
+
+        /// PEM of the client certificate with the common name and its key
+        fn client_pem(&self, common_name: &str) -> (String, String) {
+            let mut params = CertificateParams::new(vec![]);
+            params.distinguished_name = dn(common_name);
+            // webpki rejects client certificates without the clientAuth usage
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+            let cert = rcgen::Certificate::from_params(params).unwrap();
+            (self.sign(&cert), cert.serialize_private_key_pem())
+        }
+
+        fn client_tls(&self) -> ClientTlsConfig {
+            ClientTlsConfig::new()
+                .domain_name("localhost")
+                .ca_certificate(Certificate::from_pem(self.ca.serialize_pem().unwrap()))
+        }
+    }
+
+    impl Drop for TestCerts {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+
+    fn dn(common_name: &str) -> DistinguishedName {
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, common_name);
+        dn
+    }
+
+    async fn connect(port: u16, tls: ClientTlsConfig) -> Result<Channel, tonic::transport::Error> {
+        let mut result = None;
+        for _ in 0..50 {
+            let endpoint = Channel::from_shared(format!("https://127.0.0.1:{}", port))
+                .unwrap()
+                .tls_config(tls.clone())?;
+            match endpoint.connect().await {
+                Ok(channel) => return Ok(channel),
+                Err(e) => result = Some(e),
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(result.unwrap())
+    }
+
+    #[test]
+    fn identity_should_fall_back_to_subject() {
+        let mut params = CertificateParams::new(vec![]);
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "acme");
+        let cert = rcgen::Certificate::from_params(params).unwrap();
+        let der = cert.serialize_der().unwrap();
+        assert_eq!(certificate_identity(&der), Some("O=acme".to_string()));
+
+        let mut params = CertificateParams::new(vec![]);
+        params.distinguished_name = dn("alice");
+        let cert = rcgen::Certificate::from_params(params).unwrap();
+        let der = cert.serialize_der().unwrap();
+        assert_eq!(certificate_identity(&der), Some("alice".to_string()));
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_should_map_client_subject_to_actor() {
+        let certs = TestCerts::new("reservation-mtls");
+        let mut config = TestConfig::default();
+        let port = config.server.port;
+        config.config.server.tls = Some(certs.tls_config());
+        let server_config = config.config.clone();
+        tokio::spawn(async move { start_server(&server_config).await.unwrap() });
+
+        let tls = certs.client_tls().identity(certs.client_identity("alice"));
+        let mut client = ReservationServiceClient::new(connect(port, tls).await.unwrap());
+        let reservation = abi::Reservation::new(
+            "alon",
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "mtls",
+            ReservationStatus::Pending,
+        );
+        let mut request = tonic::Request::new(ReserveRequest {
+            reservation: Some(reservation),
+        });
+        // the verified certificate wins over the metadata
+        request
+            .metadata_mut()
+            .insert(ACTOR_ID_KEY, "mallory".parse().unwrap());
+        let reservation = client
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let events = client
+            .history(HistoryRequest { id: reservation.id })
+            .await
+            .unwrap()
+            .into_inner()
+            .events;
+        assert_eq!(events[0].actor_id, "alice");
+
+        // without a client certificate the handshake is rejected
+        let result = match connect(port, certs.client_tls()).await {
+            Ok(channel) => ReservationServiceClient::new(channel)
+                .history(HistoryRequest { id: reservation.id })
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn clients_should_connect_with_the_tls_config() {
+        let certs = TestCerts::new("reservation-client-tls");
+        let (cert, key) = certs.client_pem("alice");
+        fs::write(certs.path("client.pem"), cert).unwrap();
+        fs::write(certs.path("client.key"), key).unwrap();
+        let mut config = TestConfig::default();
+        config.config.server.tls = Some(TlsConfig {
+            ca: Some(certs.path("ca.pem")),
+            domain: Some("localhost".to_string()),
+            client_cert: Some(certs.path("client.pem")),
+            client_key: Some(certs.path("client.key")),
+            ..certs.tls_config()
+        });
+        let server_config = config.config.clone();
+        tokio::spawn(async move { start_server(&server_config).await.unwrap() });
+
+        let mut channel = None;
+        for _ in 0..50 {
+            if let Ok(c) = config.server.endpoint().unwrap().connect().await {
+                channel = Some(c);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let mut client = ReservationServiceClient::new(channel.expect("server is not started"));
+        let reservation = abi::Reservation::new(
+            "alon",
+            "ixia-3231",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "client tls",
+            ReservationStatus::Pending,
+        );
+        let request = ReserveRequest {
+            reservation: Some(reservation),
+        };
+        let reservation = client.reserve(request).await.unwrap().into_inner();
+        let events = client
+            .history(HistoryRequest {
+                id: reservation.reservation.unwrap().id,
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .events;
+        assert_eq!(events[0].actor_id, "alice");
+    }
+
+    #[tokio::test]
+    async fn only_admins_should_manage_webhooks() {
+        let certs = TestCerts::new("reservation-webhook-admin");
+        let mut config = TestConfig::default();
+        let port = config.server.port;
+        config.config.server.tls = Some(certs.tls_config());
+        config.config.server.admins = vec!["admin".to_string()];
+        let server_config = config.config.clone();
+        tokio::spawn(async move { start_server(&server_config).await.unwrap() });
+
+        let tls = certs.client_tls().identity(certs.client_identity("admin"));
+        let mut admin = WebhookServiceClient::new(connect(port, tls).await.unwrap());
+        let request = SubscribeRequest {
+            webhook: Some(Webhook::new("https://example.com/hook", "secret")),
+        };
+        admin.subscribe(request).await.unwrap();
+        let webhooks = admin
+            .list(ListWebhooksRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .webhooks;
+        assert_eq!(webhooks.len(), 1);
+
+        let tls = certs.client_tls().identity(certs.client_identity("alice"));
+        let mut alice = WebhookServiceClient::new(connect(port, tls).await.unwrap());
+        let err = alice.list(ListWebhooksRequest {}).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    }
+}
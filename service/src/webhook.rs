@@ -0,0 +1,117 @@
+use abi::webhook_service_server::WebhookService as WebhookServiceTrait;
+use abi::{
+    ListWebhooksRequest, ListWebhooksResponse, SubscribeRequest, SubscribeResponse,
+    UnsubscribeRequest, UnsubscribeResponse, Validator,
+};
+use reservation::{seal_secret, ReservationStore, Webhook};
+use tonic::{Response, Status};
+
+use crate::{
+    service::{request_context, require_admin},
+    WebhookService,
+};
+
+impl WebhookService {
+    pub fn new(store: ReservationStore) -> Self {
+        Self {
+            store,
+            admins: vec![],
+            secret_key: None,
+        }
+    }
+
+    /// callers allowed to manage the webhooks, matched against the client certificate subjects
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    /// key to encrypt the secrets of the new webhooks, they are stored in plaintext without it
+    pub fn with_secret_key(mut self, secret_key: Option<String>) -> Self {
+        self.secret_key = secret_key;
+        self
+    }
+}
+
+#[tonic::async_trait]
+impl WebhookServiceTrait for WebhookService {
+    /// register a webhook (admin)
+    async fn subscribe(
+        &self,
+        request: tonic::Request<SubscribeRequest>,
+    ) -> Result<tonic::Response<SubscribeResponse>, tonic::Status> {
+        require_admin(&request_context(&request, &self.admins))?;
+        let request = request.into_inner();
+        if request.webhook.is_none() {
+            return Err(Status::invalid_argument("missing webhook"));
+        }
+        let mut webhook = request.webhook.unwrap();
+        webhook.validate()?;
+        let secret = webhook.secret.clone();
+        if let Some(key) = &self.secret_key {
+            webhook.secret = seal_secret(key, &webhook.secret)?;
+        }
+        let mut webhook = self.store.subscribe(webhook).await?;
+        webhook.secret = secret;
+        Ok(Response::new(SubscribeResponse {
+            webhook: Some(webhook),
+        }))
+    }
+
+    /// remove a webhook (admin)
+    async fn unsubscribe(
+        &self,
+        request: tonic::Request<UnsubscribeRequest>,
+    ) -> Result<tonic::Response<UnsubscribeResponse>, tonic::Status> {
+        require_admin(&request_context(&request, &self.admins))?;
+        let request = request.into_inner();
+        let webhook = self.store.unsubscribe(request.id).await?;
+        Ok(Response::new(UnsubscribeResponse {
+            webhook: Some(webhook),
+        }))
+    }
+
+    /// list all webhooks (admin)
+    async fn list(
+        &self,
+        request: tonic::Request<ListWebhooksRequest>,
+    ) -> Result<tonic::Response<ListWebhooksResponse>, tonic::Status> {
+        require_admin(&request_context(&request, &self.admins))?;
+        let webhooks = self.store.list_webhooks().await?;
+        Ok(Response::new(ListWebhooksResponse { webhooks }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abi::Webhook;
+
+    use crate::{service::ACTOR_ID_KEY, test_utils::TestConfig};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rpc_webhooks_should_require_admin() {
+        let config = TestConfig::default();
+
+        let store = ReservationStore::from_config(&config.db).await.unwrap();
+        let service = WebhookService::new(store).with_admins(vec!["admin".to_string()]);
+        // a claimed identity is never an admin
+        let mut request = tonic::Request::new(SubscribeRequest {
+            webhook: Some(Webhook::new("https://example.com/hook", "secret")),
+        });
+        request
+            .metadata_mut()
+            .insert(ACTOR_ID_KEY, "admin".parse().unwrap());
+        let err = service.subscribe(request).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let request = tonic::Request::new(UnsubscribeRequest { id: 1 });
+        let err = service.unsubscribe(request).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let request = tonic::Request::new(ListWebhooksRequest {});
+        let err = service.list(request).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    }
+}